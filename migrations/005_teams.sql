@@ -0,0 +1,30 @@
+-- Team participation: contests with team_size > 1 are played in teams
+
+ALTER TABLE contests ADD COLUMN team_size INTEGER NOT NULL DEFAULT 1;  -- 1 = individual contest
+
+CREATE TABLE teams (
+    id SERIAL PRIMARY KEY,
+    contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    invite_code TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL,
+    UNIQUE (contest_id, name)
+);
+
+CREATE INDEX idx_teams_contest ON teams(contest_id);
+
+-- A user can be in at most one team per contest
+CREATE TABLE team_members (
+    team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    joined_at BIGINT NOT NULL,
+    PRIMARY KEY (contest_id, username)
+);
+
+CREATE INDEX idx_team_members_team ON team_members(team_id);
+
+-- Submissions made in a team contest are attributed to the submitter's team
+ALTER TABLE submissions ADD COLUMN team_id INTEGER REFERENCES teams(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_submissions_team ON submissions(team_id);
@@ -0,0 +1,14 @@
+-- Per-contest access control
+
+ALTER TABLE contests ADD COLUMN access_mode TEXT NOT NULL DEFAULT 'public';  -- 'public', 'code', 'allowlist'
+ALTER TABLE contests ADD COLUMN join_code TEXT;  -- Required when access_mode = 'code'
+
+-- Optional contact email, matched against contest allow-lists
+ALTER TABLE users ADD COLUMN email TEXT;
+
+-- Usernames or emails (stored lowercase) allowed into an allow-list contest
+CREATE TABLE contest_allowlist (
+    contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+    entry TEXT NOT NULL,
+    PRIMARY KEY (contest_id, entry)
+);
@@ -0,0 +1,7 @@
+-- Moderation: participant standings and invalidated submissions
+
+-- 'ok', 'disqualified' (hidden from rankings and medals) or 'hors_concours' (shown but unranked)
+ALTER TABLE contest_participants ADD COLUMN standing TEXT NOT NULL DEFAULT 'ok';
+
+-- Non-NULL = submission is kept as evidence but excluded from scoring
+ALTER TABLE submissions ADD COLUMN invalidated_reason TEXT;
@@ -0,0 +1,2 @@
+-- Per-contest scoring system: 'ratio', 'bytes', 'anarchy' or 'time_decay' (see src/scoring.rs)
+ALTER TABLE contests ADD COLUMN scoring TEXT NOT NULL DEFAULT 'ratio';
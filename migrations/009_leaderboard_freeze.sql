@@ -0,0 +1,5 @@
+-- Leaderboard freeze: standings stop updating for participants N minutes before the end
+-- until an admin reveals the final board
+
+ALTER TABLE contests ADD COLUMN freeze_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE contests ADD COLUMN revealed BOOLEAN NOT NULL DEFAULT FALSE;
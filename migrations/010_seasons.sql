@@ -0,0 +1,19 @@
+-- Cross-contest rankings and public profiles
+
+-- When contestants' code becomes visible on profiles: 'after_end' or 'never'
+ALTER TABLE contests ADD COLUMN reveal_policy TEXT NOT NULL DEFAULT 'after_end';
+
+-- A season ranks users across a chosen set of contests (see src/rankings.rs)
+CREATE TABLE seasons (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    aggregation TEXT NOT NULL DEFAULT 'sum',  -- 'sum', 'best_n', 'average' or 'placement'
+    best_of INTEGER NOT NULL DEFAULT 0,  -- Contests counted by 'best_n'
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE season_contests (
+    season_id INTEGER NOT NULL REFERENCES seasons(id) ON DELETE CASCADE,
+    contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+    PRIMARY KEY (season_id, contest_id)
+);
@@ -0,0 +1,22 @@
+-- Problem versioning: submissions record the version of the problem they were judged against
+
+-- Every version of a problem package that has been judged against, keyed by content hash
+CREATE TABLE problem_snapshots (
+    problem_id TEXT NOT NULL,
+    hash TEXT NOT NULL,  -- SHA-256, see problems::content_hash
+    version INTEGER NOT NULL,  -- 1, 2, ... in the order versions were first seen
+    title TEXT NOT NULL,
+    statement TEXT NOT NULL,  -- Markdown without the title heading
+    test_input TEXT NOT NULL,
+    test_output TEXT NOT NULL,
+    grader TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (problem_id, hash),
+    UNIQUE (problem_id, version)
+);
+
+-- NULL for submissions judged before versioning
+ALTER TABLE submissions ADD COLUMN problem_hash TEXT;
+
+-- Version in use when the contest started, to detect edits made during it
+ALTER TABLE contest_problems ADD COLUMN start_hash TEXT;
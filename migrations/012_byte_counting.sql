@@ -0,0 +1,5 @@
+-- Byte-counting rules: 'raw', 'normalised', 'chars' or 'sbcs' (see src/counting.rs)
+ALTER TABLE contests ADD COLUMN byte_count TEXT NOT NULL DEFAULT 'raw';
+
+-- problem.json of each stored version, which can override the contest's rule
+ALTER TABLE problem_snapshots ADD COLUMN metadata TEXT;
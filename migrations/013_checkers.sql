@@ -0,0 +1,2 @@
+-- Custom checker (checker.py or a compiled checker) of each stored version, if it had one
+ALTER TABLE problem_snapshots ADD COLUMN checker BYTEA;
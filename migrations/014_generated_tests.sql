@@ -0,0 +1,6 @@
+-- Seed of the random tests a submission was judged on, if its problem has a generator
+ALTER TABLE submissions ADD COLUMN seed BIGINT;
+
+-- Generator and reference solution of each stored version, if it had them
+ALTER TABLE problem_snapshots ADD COLUMN generator TEXT;
+ALTER TABLE problem_snapshots ADD COLUMN solution TEXT;
@@ -0,0 +1,6 @@
+-- Per-contest problem settings: a display alias (default A, B, C... by order), a multiplier
+-- on the problem's points, and an optional window in minutes from the contest start
+ALTER TABLE contest_problems ADD COLUMN alias TEXT;
+ALTER TABLE contest_problems ADD COLUMN weight DOUBLE PRECISION NOT NULL DEFAULT 1;
+ALTER TABLE contest_problems ADD COLUMN open_minute INTEGER;
+ALTER TABLE contest_problems ADD COLUMN close_minute INTEGER;
@@ -0,0 +1,102 @@
+//! Who may join a contest: anyone, those who know its join code, or those on its allow-list
+
+use crate::models::Contest;
+
+/// Access modes stored in `contests.access_mode`
+pub const MODES: [&str; 3] = ["public", "code", "allowlist"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    Join,
+    AskForCode,
+    Refuse(&'static str),
+}
+
+/// Decide whether a user who is not yet registered may join, given the code they typed if
+/// any and whether they are on the contest's allow-list
+pub fn decide(contest: &Contest, code: Option<&str>, allowlisted: bool) -> Decision {
+    match contest.access_mode.as_str() {
+        "code" => match (code, &contest.join_code) {
+            (Some(given), Some(expected)) if given.trim() == expected => Decision::Join,
+            (Some(_), _) => Decision::Refuse("Incorrect join code"),
+            (None, _) => Decision::AskForCode,
+        },
+        "allowlist" if !allowlisted => {
+            Decision::Refuse("You are not on the participant list for this contest")
+        }
+        _ => Decision::Join,
+    }
+}
+
+/// Allow-list entries from the admin form, one username per line, stored lowercase
+pub fn parse_allowlist(text: &str) -> Vec<String> {
+    text.lines()
+        .map(normalize_entry)
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+pub fn normalize_entry(entry: &str) -> String {
+    entry.trim().to_lowercase()
+}
+
+/// Whether a username is on an allow-list, ignoring case
+pub fn is_allowlisted(entries: &[String], username: &str) -> bool {
+    entries.contains(&username.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contest(access_mode: &str, join_code: Option<&str>) -> Contest {
+        Contest {
+            access_mode: access_mode.to_string(),
+            join_code: join_code.map(str::to_string),
+            ..Contest::sample(1, "ratio")
+        }
+    }
+
+    #[test]
+    fn test_access_modes() {
+        let public = contest("public", None);
+        assert_eq!(decide(&public, None, false), Decision::Join);
+        assert_eq!(decide(&public, Some("anything"), false), Decision::Join);
+
+        let code = contest("code", Some("OWLS"));
+        assert_eq!(decide(&code, None, true), Decision::AskForCode);
+        assert_eq!(decide(&code, Some(" OWLS\n"), false), Decision::Join);
+        assert_eq!(
+            decide(&code, Some("owls"), true),
+            Decision::Refuse("Incorrect join code")
+        );
+        // A code contest without a code set cannot be joined by guessing
+        let unset = contest("code", None);
+        assert_eq!(
+            decide(&unset, Some(""), false),
+            Decision::Refuse("Incorrect join code")
+        );
+
+        let allowlist = contest("allowlist", Some("OWLS"));
+        assert_eq!(decide(&allowlist, None, true), Decision::Join);
+        assert!(matches!(
+            decide(&allowlist, Some("OWLS"), false),
+            Decision::Refuse(_)
+        ));
+
+        // Unknown modes, e.g. from an older server, stay open as before
+        assert_eq!(decide(&contest("", None), None, false), Decision::Join);
+    }
+
+    #[test]
+    fn test_allowlist_matching() {
+        let entries = parse_allowlist("  Alice \n\nbob.smith\r\n   \nCAROL");
+        assert_eq!(entries, ["alice", "bob.smith", "carol"]);
+        assert!(is_allowlisted(&entries, "alice"));
+        assert!(is_allowlisted(&entries, "ALICE"));
+        assert!(is_allowlisted(&entries, "Bob.Smith"));
+        assert!(!is_allowlisted(&entries, "bob"));
+        assert!(!is_allowlisted(&entries, "alice2"));
+        assert!(!is_allowlisted(&[], "alice"));
+    }
+}
@@ -0,0 +1,895 @@
+//! Contest bundles for moving events between instances
+//! A bundle is a gzipped tar holding `contest.json` (contest settings, problem order,
+//! participants, teams and optionally every submission) and the files of each problem
+//! under `problems/{id}/`. Bundles without submissions import as a fresh pending contest
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{
+    access,
+    counting::ByteCount,
+    models::{Contest, Submission},
+    problems, scoring,
+    teams::generate_invite_code,
+    usernames,
+};
+
+pub const FORMAT: &str = "golf-contest-bundle";
+pub const VERSION: u32 = 1;
+
+const MANIFEST: &str = "contest.json";
+
+/// Limits on what a bundle may unpack to, so a small upload cannot expand without bound
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_UNPACKED_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum BundleError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Db(sqlx::Error),
+    Invalid(String),
+    NotFound,
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Io(e) => write!(f, "Could not read bundle: {e}"),
+            BundleError::Json(e) => write!(f, "Invalid {MANIFEST}: {e}"),
+            BundleError::Db(e) => write!(f, "Database error: {e}"),
+            BundleError::Invalid(msg) => write!(f, "Invalid bundle: {msg}"),
+            BundleError::NotFound => write!(f, "Contest not found"),
+        }
+    }
+}
+
+impl From<io::Error> for BundleError {
+    fn from(e: io::Error) -> Self {
+        BundleError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BundleError {
+    fn from(e: serde_json::Error) -> Self {
+        BundleError::Json(e)
+    }
+}
+
+impl From<sqlx::Error> for BundleError {
+    fn from(e: sqlx::Error) -> Self {
+        BundleError::Db(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    pub contest: BundleContest,
+    pub problems: Vec<String>, // IDs in contest order, files under problems/{id}/
+    #[serde(default)]
+    pub problem_settings: Vec<BundleProblemSettings>, // Same order; empty in older bundles
+    pub users: Vec<BundleUser>,
+    pub participants: Vec<BundleParticipant>,
+    pub teams: Vec<BundleTeam>,
+    pub allowlist: Vec<String>,
+    pub submissions: Option<Vec<BundleSubmission>>,
+    #[serde(default)]
+    pub snapshots: Vec<BundleSnapshot>, // Problem versions the submissions were judged against
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleContest {
+    pub name: String,
+    pub duration: i32,
+    pub start_time: Option<i64>,
+    pub status: String,
+    pub created_at: i64,
+    pub team_size: i32,
+    pub access_mode: String,
+    pub join_code: Option<String>,
+    pub scoring: String,
+    pub freeze_minutes: i32,
+    pub revealed: bool,
+    pub reveal_policy: String,
+    #[serde(default)]
+    pub byte_count: String, // Empty in bundles from before counting rules, meaning raw
+}
+
+impl From<Contest> for BundleContest {
+    fn from(contest: Contest) -> Self {
+        BundleContest {
+            name: contest.name,
+            duration: contest.duration,
+            start_time: contest.start_time,
+            status: contest.status,
+            created_at: contest.created_at,
+            team_size: contest.team_size,
+            access_mode: contest.access_mode,
+            join_code: contest.join_code,
+            scoring: contest.scoring,
+            freeze_minutes: contest.freeze_minutes,
+            revealed: contest.revealed,
+            reveal_policy: contest.reveal_policy,
+            byte_count: contest.byte_count,
+        }
+    }
+}
+
+/// A problem's alias, weight and window in the contest, see models::ContestProblem
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BundleProblemSettings {
+    pub alias: Option<String>,
+    pub weight: f64,
+    pub open_minute: Option<i32>,
+    pub close_minute: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BundleUser {
+    pub username: String,
+    pub created_at: i64,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BundleParticipant {
+    pub username: String,
+    pub joined_at: i64,
+    pub standing: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleTeam {
+    pub name: String,
+    pub created_at: i64,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BundleSubmission {
+    pub username: String,
+    pub team: Option<String>,
+    pub problem_id: String,
+    pub verdict: String,
+    pub code_length: i32,
+    pub time: i32,
+    pub code: String,
+    pub created_at: i64,
+    pub invalidated_reason: Option<String>,
+    #[serde(default)]
+    pub problem_hash: Option<String>, // Version judged against, see problems::content_hash
+    #[serde(default)]
+    pub seed: Option<i64>, // Of the generated tests it was judged on
+}
+
+/// A stored problem version, see versions::ProblemVersion
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BundleSnapshot {
+    pub problem_id: String,
+    pub hash: String,
+    pub title: String,
+    pub statement: String,
+    pub test_input: String,
+    pub test_output: String,
+    pub grader: String,
+    pub metadata: Option<String>,
+    pub checker: Option<Vec<u8>>,
+    pub generator: Option<String>,
+    pub solution: Option<String>,
+    pub created_at: i64,
+}
+
+/// A file belonging to one of the bundled problems
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProblemFile {
+    pub problem_id: String,
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// File names become paths, so keep them to a safe alphabet
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Write a bundle as a gzipped tar
+pub fn pack(manifest: &Manifest, files: &[ProblemFile]) -> Result<Vec<u8>, BundleError> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+    let mut append = |path: &str, contents: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.exported_at.max(0) as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, path, contents)
+    };
+
+    append(MANIFEST, &serde_json::to_vec_pretty(manifest)?)?;
+    for file in files {
+        append(
+            &format!("problems/{}/{}", file.problem_id, file.name),
+            &file.contents,
+        )?;
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Read and validate a bundle written by `pack`
+pub fn unpack(bytes: &[u8]) -> Result<(Manifest, Vec<ProblemFile>), BundleError> {
+    unpack_with_limits(bytes, MAX_FILE_BYTES, MAX_UNPACKED_BYTES)
+}
+
+fn unpack_with_limits(
+    bytes: &[u8],
+    max_file: u64,
+    max_total: u64,
+) -> Result<(Manifest, Vec<ProblemFile>), BundleError> {
+    let mut unpacked = 0;
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut manifest = None;
+    let mut files = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        (&mut entry).take(max_file + 1).read_to_end(&mut contents)?;
+        unpacked += contents.len() as u64;
+        if contents.len() as u64 > max_file {
+            return Err(BundleError::Invalid(format!("{path} is too large")));
+        }
+        if unpacked > max_total {
+            return Err(BundleError::Invalid("contents are too large".to_string()));
+        }
+
+        let parts: Vec<&str> = path.split('/').collect();
+        match parts.as_slice() {
+            [MANIFEST] => manifest = Some(serde_json::from_slice::<Manifest>(&contents)?),
+            ["problems", id, name] if problems::is_valid_problem_id(id) && is_safe_name(name) => {
+                files.push(ProblemFile {
+                    problem_id: id.to_string(),
+                    name: name.to_string(),
+                    contents,
+                });
+            }
+            _ => return Err(BundleError::Invalid(format!("unexpected file {path}"))),
+        }
+    }
+
+    let manifest = manifest.ok_or(BundleError::Invalid(format!("missing {MANIFEST}")))?;
+    if manifest.format != FORMAT {
+        return Err(BundleError::Invalid("not a contest bundle".to_string()));
+    }
+    if manifest.version > VERSION {
+        return Err(BundleError::Invalid(format!(
+            "bundle version {} is newer than this server supports ({VERSION})",
+            manifest.version
+        )));
+    }
+    for id in &manifest.problems {
+        if !problems::is_valid_problem_id(id) || !files.iter().any(|f| &f.problem_id == id) {
+            return Err(BundleError::Invalid(format!(
+                "problem {id} is not packaged"
+            )));
+        }
+    }
+    check_settings(&manifest)?;
+
+    Ok((manifest, files))
+}
+
+/// Reject settings and names the admin pages and login could never have produced, so an
+/// edited or damaged manifest cannot create contests the rest of the server doesn't expect
+fn check_settings(manifest: &Manifest) -> Result<(), BundleError> {
+    let invalid =
+        |what: &str, value: &str| Err(BundleError::Invalid(format!("{what} \"{value}\"")));
+    let c = &manifest.contest;
+    if !access::MODES.contains(&c.access_mode.as_str()) {
+        return invalid("access mode", &c.access_mode);
+    }
+    if !scoring::all().iter().any(|s| s.id() == c.scoring) {
+        return invalid("scoring system", &c.scoring);
+    }
+    if !["after_end", "never"].contains(&c.reveal_policy.as_str()) {
+        return invalid("reveal policy", &c.reveal_policy);
+    }
+    if !c.byte_count.is_empty() && !ByteCount::ALL.iter().any(|b| b.id() == c.byte_count) {
+        return invalid("byte count", &c.byte_count);
+    }
+
+    for p in &manifest.participants {
+        if !["ok", "disqualified", "hors_concours"].contains(&p.standing.as_str()) {
+            return invalid("standing", &p.standing);
+        }
+    }
+    // Entries are stored as the allow-list form saves them
+    for entry in &manifest.allowlist {
+        if entry.is_empty() || *entry != access::normalize_entry(entry) {
+            return invalid("allow-list entry", entry);
+        }
+    }
+
+    let names = manifest
+        .users
+        .iter()
+        .map(|u| &u.username)
+        .chain(manifest.participants.iter().map(|p| &p.username))
+        .chain(manifest.teams.iter().flat_map(|t| &t.members))
+        .chain(manifest.submissions.iter().flatten().map(|s| &s.username));
+    for name in names {
+        if let Err(e) = usernames::validate(name) {
+            return Err(BundleError::Invalid(format!("username \"{name}\": {e}")));
+        }
+    }
+    Ok(())
+}
+
+/// Package a contest, its problems and optionally all of its submissions
+pub async fn export_contest(
+    db: &PgPool,
+    contest_id: i32,
+    include_submissions: bool,
+) -> Result<Vec<u8>, BundleError> {
+    let contest = sqlx::query_as::<_, Contest>("SELECT * FROM contests WHERE id = $1")
+        .bind(contest_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(BundleError::NotFound)?;
+
+    let problem_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT problem_id FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await?;
+    let problem_settings = sqlx::query_as::<_, BundleProblemSettings>(
+        "SELECT alias, weight, open_minute, close_minute FROM contest_problems
+         WHERE contest_id = $1 ORDER BY problem_order",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await?;
+
+    let participants = sqlx::query_as::<_, BundleParticipant>(
+        "SELECT username, joined_at::BIGINT AS joined_at, standing FROM contest_participants
+         WHERE contest_id = $1 ORDER BY joined_at, username",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await?;
+
+    let team_rows: Vec<(String, i64, Option<String>)> = sqlx::query_as(
+        "SELECT t.name, t.created_at, m.username FROM teams t
+         LEFT JOIN team_members m ON m.team_id = t.id
+         WHERE t.contest_id = $1
+         ORDER BY t.created_at, t.name, m.joined_at",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await?;
+    let mut teams: Vec<BundleTeam> = Vec::new();
+    for (name, created_at, member) in team_rows {
+        if teams.last().is_none_or(|t| t.name != name) {
+            teams.push(BundleTeam {
+                name,
+                created_at,
+                members: Vec::new(),
+            });
+        }
+        if let (Some(team), Some(member)) = (teams.last_mut(), member) {
+            team.members.push(member);
+        }
+    }
+
+    let allowlist: Vec<String> = sqlx::query_scalar(
+        "SELECT entry FROM contest_allowlist WHERE contest_id = $1 ORDER BY entry",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await?;
+
+    let submissions = if include_submissions {
+        Some(
+            sqlx::query_as::<_, BundleSubmission>(
+                "SELECT s.username, t.name AS team, s.problem_id, s.verdict, s.code_length,
+                        s.time, s.code, s.created_at, s.invalidated_reason, s.problem_hash, s.seed
+                 FROM submissions s
+                 LEFT JOIN teams t ON t.id = s.team_id
+                 WHERE s.contest_id = $1
+                 ORDER BY s.created_at, s.id",
+            )
+            .bind(contest_id)
+            .fetch_all(db)
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    let snapshots = if include_submissions {
+        sqlx::query_as::<_, BundleSnapshot>(
+            "SELECT problem_id, hash, title, statement, test_input, test_output, grader,
+                    metadata, checker, generator, solution, created_at
+             FROM problem_snapshots
+             WHERE (problem_id, hash) IN (
+                 SELECT problem_id, problem_hash FROM submissions WHERE contest_id = $1
+             )
+             ORDER BY problem_id, version",
+        )
+        .bind(contest_id)
+        .fetch_all(db)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    // Every user the bundle mentions, so imports can satisfy foreign keys
+    let users = sqlx::query_as::<_, BundleUser>(
+        "SELECT username, created_at, email FROM users
+         WHERE username IN (
+             SELECT username FROM contest_participants WHERE contest_id = $1
+             UNION SELECT username FROM team_members WHERE contest_id = $1
+             UNION SELECT username FROM submissions WHERE contest_id = $1 AND $2
+         )
+         ORDER BY username",
+    )
+    .bind(contest_id)
+    .bind(include_submissions)
+    .fetch_all(db)
+    .await?;
+
+    let mut files = Vec::new();
+    for id in &problem_ids {
+        for entry in fs::read_dir(problems::problem_dir(id))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_file() && is_safe_name(&name) {
+                files.push(ProblemFile {
+                    problem_id: id.clone(),
+                    name,
+                    contents: fs::read(entry.path())?,
+                });
+            }
+        }
+    }
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        exported_at: chrono::Utc::now().timestamp(),
+        contest: contest.into(),
+        problems: problem_ids,
+        problem_settings,
+        users,
+        participants,
+        teams,
+        allowlist,
+        submissions,
+        snapshots,
+    };
+    pack(&manifest, &files)
+}
+
+/// Problem directories written aside during an import and moved into place once it has
+/// committed. Whatever was not installed is removed on drop, so a failed import leaves
+/// nothing behind.
+struct Staging {
+    dir: PathBuf,
+    problems: Vec<String>,
+}
+
+impl Staging {
+    fn new() -> Self {
+        use rand::Rng;
+        let token: u64 = rand::rng().random();
+        Staging {
+            dir: problems::data_dir()
+                .join("imports")
+                .join(format!("{token:016x}")),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Pick the ID a bundled problem installs as, staging its files unless an identical
+    /// local copy can be reused. A different problem with the same ID gets a free one.
+    fn add(&mut self, id: &str, files: &[&ProblemFile]) -> Result<String, BundleError> {
+        let same_as_local = |candidate: &str| {
+            let dir = problems::problem_dir(candidate);
+            let local_count = fs::read_dir(&dir)
+                .map(|entries| {
+                    entries
+                        .filter(|e| e.as_ref().is_ok_and(|e| e.path().is_file()))
+                        .count()
+                })
+                .unwrap_or(0);
+            local_count == files.len()
+                && files
+                    .iter()
+                    .all(|f| fs::read(dir.join(&f.name)).is_ok_and(|local| local == f.contents))
+        };
+
+        let mut candidate = id.to_string();
+        let mut suffix = 1;
+        loop {
+            if same_as_local(&candidate) {
+                return Ok(candidate);
+            }
+            let taken =
+                problems::problem_dir(&candidate).exists() || self.problems.contains(&candidate);
+            if !taken && problems::is_valid_problem_id(&candidate) {
+                break;
+            }
+            suffix += 1;
+            candidate = format!("{id}-{suffix}");
+        }
+
+        let dir = self.dir.join(&candidate);
+        fs::create_dir_all(&dir)?;
+        for file in files {
+            fs::write(dir.join(&file.name), &file.contents)?;
+        }
+        self.problems.push(candidate.clone());
+        Ok(candidate)
+    }
+
+    /// Move the staged problems to where they are served from
+    fn install(&self) -> io::Result<()> {
+        for id in &self.problems {
+            let target = problems::writable_problem_dir(id);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(self.dir.join(id), target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Create a new contest from a bundle, returning its ID
+pub async fn import_contest(db: &PgPool, bytes: &[u8]) -> Result<i32, BundleError> {
+    let (manifest, files) = unpack(bytes)?;
+
+    // Problems are staged first, under new IDs when a different problem has the same one
+    let mut staging = Staging::new();
+    let mut problem_ids = HashMap::new();
+    for id in &manifest.problems {
+        let own: Vec<&ProblemFile> = files.iter().filter(|f| &f.problem_id == id).collect();
+        problem_ids.insert(id.clone(), staging.add(id, &own)?);
+    }
+
+    let c = &manifest.contest;
+    let with_results = manifest.submissions.is_some();
+    let mut tx = db.begin().await?;
+
+    // Without submissions the event is set up to be run again from scratch
+    let contest_id: i32 = sqlx::query_scalar(
+        "INSERT INTO contests (name, duration, start_time, status, created_at, team_size, access_mode,
+                               join_code, scoring, freeze_minutes, revealed, reveal_policy, byte_count)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING id",
+    )
+    .bind(&c.name)
+    .bind(c.duration)
+    .bind(if with_results { c.start_time } else { None })
+    .bind(if with_results && c.start_time.is_some() {
+        "ended"
+    } else {
+        "pending"
+    })
+    .bind(chrono::Utc::now().timestamp())
+    .bind(c.team_size.max(1))
+    .bind(&c.access_mode)
+    .bind(&c.join_code)
+    .bind(&c.scoring)
+    .bind(c.freeze_minutes.max(0))
+    .bind(with_results && c.revealed)
+    .bind(&c.reveal_policy)
+    .bind(ByteCount::parse(&c.byte_count).id())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for (order, id) in manifest.problems.iter().enumerate() {
+        let settings = manifest.problem_settings.get(order);
+        let weight = settings
+            .map(|s| s.weight)
+            .filter(|w| *w > 0.0)
+            .map_or(1.0, |w| w.min(scoring::MAX_WEIGHT));
+        sqlx::query(
+            "INSERT INTO contest_problems
+             (contest_id, problem_id, problem_order, alias, weight, open_minute, close_minute)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(contest_id)
+        .bind(&problem_ids[id])
+        .bind(order as i32)
+        .bind(settings.and_then(|s| s.alias.clone()))
+        .bind(weight)
+        .bind(settings.and_then(|s| s.open_minute))
+        .bind(settings.and_then(|s| s.close_minute))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for user in &manifest.users {
+        sqlx::query(
+            "INSERT INTO users (username, is_admin, created_at, email) VALUES ($1, FALSE, $2, $3)
+             ON CONFLICT (username) DO NOTHING",
+        )
+        .bind(&user.username)
+        .bind(user.created_at)
+        .bind(&user.email)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for p in &manifest.participants {
+        sqlx::query(
+            "INSERT INTO contest_participants (contest_id, username, joined_at, standing) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(contest_id)
+        .bind(&p.username)
+        .bind(p.joined_at)
+        .bind(&p.standing)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for entry in &manifest.allowlist {
+        sqlx::query("INSERT INTO contest_allowlist (contest_id, entry) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(contest_id)
+            .bind(entry)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Invite codes are unique across contests, so teams get fresh ones
+    let mut team_ids = HashMap::new();
+    for team in &manifest.teams {
+        let team_id: i32 = sqlx::query_scalar(
+            "INSERT INTO teams (contest_id, name, invite_code, created_at) VALUES ($1, $2, $3, $4) RETURNING id",
+        )
+        .bind(contest_id)
+        .bind(&team.name)
+        .bind(generate_invite_code())
+        .bind(team.created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+        for member in &team.members {
+            sqlx::query(
+                "INSERT INTO team_members (team_id, contest_id, username, joined_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(team_id)
+            .bind(contest_id)
+            .bind(member)
+            .bind(team.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        team_ids.insert(team.name.clone(), team_id);
+    }
+
+    // Stored versions keep their hashes but are numbered after any the problem already has
+    let mut snapshots = HashSet::new();
+    for v in &manifest.snapshots {
+        let problem_id = problem_ids.get(&v.problem_id).ok_or_else(|| {
+            BundleError::Invalid(format!("version of unknown problem {}", v.problem_id))
+        })?;
+        sqlx::query(
+            "INSERT INTO problem_snapshots (problem_id, hash, version, title, statement, test_input, test_output, grader, metadata, checker, generator, solution, created_at)
+             SELECT $1, $2, COALESCE(MAX(version), 0) + 1, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12
+             FROM problem_snapshots WHERE problem_id = $1
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(problem_id)
+        .bind(&v.hash)
+        .bind(&v.title)
+        .bind(&v.statement)
+        .bind(&v.test_input)
+        .bind(&v.test_output)
+        .bind(&v.grader)
+        .bind(&v.metadata)
+        .bind(&v.checker)
+        .bind(&v.generator)
+        .bind(&v.solution)
+        .bind(v.created_at)
+        .execute(&mut *tx)
+        .await?;
+        snapshots.insert((v.problem_id.as_str(), v.hash.as_str()));
+    }
+
+    for s in manifest.submissions.iter().flatten() {
+        let problem_id = problem_ids.get(&s.problem_id).ok_or_else(|| {
+            BundleError::Invalid(format!("submission to unknown problem {}", s.problem_id))
+        })?;
+        sqlx::query(
+            "INSERT INTO submissions (id, username, contest_id, problem_id, verdict, code_length, time, code,
+                                      created_at, team_id, invalidated_reason, problem_hash, seed)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+        )
+        .bind(Submission::generate_id())
+        .bind(&s.username)
+        .bind(contest_id)
+        .bind(problem_id)
+        .bind(&s.verdict)
+        .bind(s.code_length)
+        .bind(s.time)
+        .bind(&s.code)
+        .bind(s.created_at)
+        .bind(s.team.as_ref().and_then(|t| team_ids.get(t)))
+        .bind(&s.invalidated_reason)
+        // Older bundles carry no versions, so their hashes would point at nothing
+        .bind(s.problem_hash.as_ref().filter(|hash| {
+            snapshots.contains(&(s.problem_id.as_str(), hash.as_str()))
+        }))
+        .bind(s.seed)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    staging.install()?;
+    Ok(contest_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported_at: 1000,
+            contest: Contest::sample(1, "ratio").into(),
+            problems: vec!["0".to_string()],
+            problem_settings: Vec::new(),
+            users: Vec::new(),
+            participants: Vec::new(),
+            teams: Vec::new(),
+            allowlist: Vec::new(),
+            submissions: None,
+            snapshots: Vec::new(),
+        }
+    }
+
+    fn file(problem_id: &str, name: &str, contents: &str) -> ProblemFile {
+        ProblemFile {
+            problem_id: problem_id.to_string(),
+            name: name.to_string(),
+            contents: contents.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_rejects_settings_the_server_cannot_produce() {
+        let files = [file("0", "statement.md", "x")];
+        let participant = |username: &str, standing: &str| BundleParticipant {
+            username: username.to_string(),
+            joined_at: 1000,
+            standing: standing.to_string(),
+        };
+        let mut ok = manifest();
+        ok.participants = vec![participant("alice", "hors_concours")];
+        ok.allowlist = vec!["alice".to_string()];
+        ok.contest.byte_count = String::new();
+        assert!(unpack(&pack(&ok, &files).unwrap()).is_ok());
+
+        let edits: [fn(&mut Manifest); 7] = [
+            |m| m.contest.access_mode = "secret".to_string(),
+            |m| m.contest.scoring = "golf".to_string(),
+            |m| m.contest.reveal_policy = "always".to_string(),
+            |m| m.contest.byte_count = "bits".to_string(),
+            |m| m.participants[0].standing = "winner".to_string(),
+            |m| m.allowlist.push("Bob ".to_string()),
+            |m| m.participants[0].username = "admin".to_string(),
+        ];
+        for edit in edits {
+            let mut bad = ok.clone();
+            edit(&mut bad);
+            let bytes = pack(&bad, &files).unwrap();
+            assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+        }
+
+        let mut bad = ok.clone();
+        bad.teams = vec![BundleTeam {
+            name: "owls".to_string(),
+            created_at: 1000,
+            members: vec!["has space".to_string()],
+        }];
+        let bytes = pack(&bad, &files).unwrap();
+        assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let files = vec![
+            file("0", "statement.md", "# Hello"),
+            file("0", "grader.py", "print(1)"),
+        ];
+        let bytes = pack(&manifest(), &files).unwrap();
+        let (read, read_files) = unpack(&bytes).unwrap();
+        assert_eq!(read.contest.name, "Test");
+        assert_eq!(read.problems, vec!["0".to_string()]);
+        assert_eq!(read_files, files);
+    }
+
+    #[test]
+    fn test_rejects_newer_versions_and_missing_problems() {
+        let mut newer = manifest();
+        newer.version = VERSION + 1;
+        let bytes = pack(&newer, &[file("0", "statement.md", "x")]).unwrap();
+        assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+
+        let bytes = pack(&manifest(), &[]).unwrap();
+        assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_rejects_unsafe_paths() {
+        assert!(is_safe_name("input.txt"));
+        assert!(is_safe_name("rotate-2"));
+        assert!(!is_safe_name(".."));
+        assert!(!is_safe_name("a/b"));
+        assert!(!is_safe_name(""));
+
+        let bytes = pack(
+            &manifest(),
+            &[
+                file("0", "statement.md", "x"),
+                file("0", "notes 2.txt", "x"),
+            ],
+        )
+        .unwrap();
+        assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+
+        let mut dotted = manifest();
+        dotted.problems = vec!["a.b".to_string()];
+        let bytes = pack(&dotted, &[file("a.b", "statement.md", "x")]).unwrap();
+        assert!(matches!(unpack(&bytes), Err(BundleError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let files = vec![
+            file("0", "input.txt", &"1".repeat(100)),
+            file("0", "output.txt", &"2".repeat(100)),
+        ];
+        let bytes = pack(&manifest(), &files).unwrap();
+        assert!(unpack_with_limits(&bytes, 4096, 8192).is_ok());
+        assert!(matches!(
+            unpack_with_limits(&bytes, 99, 8192),
+            Err(BundleError::Invalid(_))
+        ));
+        let manifest_len = serde_json::to_vec_pretty(&manifest()).unwrap().len() as u64;
+        assert!(matches!(
+            unpack_with_limits(&bytes, 4096, manifest_len + 150),
+            Err(BundleError::Invalid(_))
+        ));
+    }
+}
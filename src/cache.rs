@@ -0,0 +1,293 @@
+//! In-memory cache of computed contest standings
+//! Leaderboard requests and live events are served from here; entries are dropped only
+//! when something that feeds the standings changes (a new per-entry best, moderation,
+//! participants or the contest itself), so a busy projector page costs no queries
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use sqlx::PgPool;
+
+use crate::{
+    leaderboard::{self, BestRow, LeaderboardEntry, Snapshot},
+    models::{Contest, ContestProblem},
+    plagiarism,
+    registry::ProblemRegistry,
+};
+
+/// Everything needed to serve any viewer's leaderboard for one contest
+pub struct CachedStandings {
+    pub contest: Contest,
+    pub problems: Vec<ContestProblem>,
+    pub problem_titles: Vec<String>,
+    live_rows: Vec<BestRow>,
+    live: Vec<LeaderboardEntry>,
+    frozen: Option<Vec<LeaderboardEntry>>, // As of the freeze time, if the contest has one
+    // Plagiarism checks by threshold percent. They compare each entry's best solutions,
+    // which change exactly when these standings are invalidated.
+    plagiarism: Mutex<HashMap<i32, Arc<plagiarism::Report>>>,
+}
+
+impl CachedStandings {
+    pub async fn load(
+        db: &PgPool,
+        problems: &ProblemRegistry,
+        contest: Contest,
+    ) -> Result<Self, sqlx::Error> {
+        let (contest_problems, problem_titles) =
+            leaderboard::contest_problems(db, problems, contest.id).await;
+        let live_rows = leaderboard::fetch_rows(db, &contest, None).await?;
+        let live = leaderboard::compute(&contest, &contest_problems, &live_rows);
+        let frozen = match contest.freeze_time() {
+            Some(t) => {
+                let rows = leaderboard::fetch_rows(db, &contest, Some(t)).await?;
+                Some(leaderboard::compute(&contest, &contest_problems, &rows))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            contest,
+            problems: contest_problems,
+            problem_titles,
+            live_rows,
+            live,
+            frozen,
+            plagiarism: Mutex::default(),
+        })
+    }
+
+    pub fn plagiarism_report(&self, threshold_percent: i32) -> Option<Arc<plagiarism::Report>> {
+        self.plagiarism
+            .lock()
+            .unwrap()
+            .get(&threshold_percent)
+            .cloned()
+    }
+
+    pub fn store_plagiarism_report(
+        &self,
+        threshold_percent: i32,
+        report: plagiarism::Report,
+    ) -> Arc<plagiarism::Report> {
+        let report = Arc::new(report);
+        self.plagiarism
+            .lock()
+            .unwrap()
+            .insert(threshold_percent, report.clone());
+        report
+    }
+
+    /// Current best length of an entry (user or team) on a problem
+    pub fn best_length(&self, entity: &str, problem_id: &str) -> Option<i32> {
+        self.live_rows
+            .iter()
+            .find(|r| r.entity == entity && r.problem_id.as_deref() == Some(problem_id))
+            .and_then(|r| r.code_length)
+    }
+
+    /// Live standings, or the frozen ones with `viewer`'s own newer results when `frozen`
+    pub fn view(&self, frozen: bool, viewer: Option<&str>) -> Snapshot {
+        let entries = match (&self.frozen, frozen) {
+            (Some(frozen_entries), true) => {
+                let mut entries = frozen_entries.clone();
+                if let Some(viewer) = viewer {
+                    leaderboard::overlay_own_results(
+                        &mut entries,
+                        &self.contest,
+                        &self.problems,
+                        &self.live_rows,
+                        viewer,
+                    );
+                }
+                entries
+            }
+            _ => self.live.clone(),
+        };
+
+        Snapshot {
+            entries,
+            problem_ids: self.problems.iter().map(|p| p.problem_id.clone()).collect(),
+            problem_labels: self.problems.iter().map(ContestProblem::label).collect(),
+            problem_titles: self.problem_titles.clone(),
+            frozen,
+        }
+    }
+
+    /// Blank the titles of problems that contestants cannot see yet
+    pub fn hide_unreleased(&self, snapshot: &mut Snapshot, now: i64) {
+        for (problem, title) in self.problems.iter().zip(&mut snapshot.problem_titles) {
+            if !problem.is_released(&self.contest, now) {
+                title.clear();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    pub cached_contests: usize,
+}
+
+impl CacheStats {
+    /// Share of lookups served from the cache, as a percentage
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 * 100.0 / total as f64
+    }
+}
+
+#[derive(Default)]
+pub struct StandingsCache {
+    entries: RwLock<HashMap<i32, Arc<CachedStandings>>>,
+    generation: AtomicU64, // Bumped on every invalidation
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl StandingsCache {
+    /// Look up a contest's standings, counting the hit or miss
+    pub fn get(&self, contest_id: i32) -> Option<Arc<CachedStandings>> {
+        let found = self.peek(contest_id);
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Look up a contest's standings without affecting the metrics
+    pub fn peek(&self, contest_id: i32) -> Option<Arc<CachedStandings>> {
+        self.entries.read().unwrap().get(&contest_id).cloned()
+    }
+
+    /// Take before loading, and pass to `insert` so a load that raced an invalidation is dropped
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn insert(
+        &self,
+        contest_id: i32,
+        standings: CachedStandings,
+        generation: u64,
+    ) -> Arc<CachedStandings> {
+        let standings = Arc::new(standings);
+        let mut entries = self.entries.write().unwrap();
+        if self.generation() == generation {
+            entries.insert(contest_id, standings.clone());
+        }
+        standings
+    }
+
+    pub fn invalidate(&self, contest_id: i32) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+        entries.remove(&contest_id);
+    }
+
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+        entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            cached_contests: self.entries.read().unwrap().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl CachedStandings {
+    /// Standings where alice solved the contest's one problem in 40 bytes, for tests
+    pub fn sample(contest: Contest) -> Self {
+        let live_rows = vec![BestRow {
+            entity: "alice".to_string(),
+            standing: "ok".to_string(),
+            problem_id: Some("0".to_string()),
+            code_length: Some(40),
+            author: Some("alice".to_string()),
+            created_at: Some(10),
+        }];
+        let problems = vec![ContestProblem {
+            contest_id: contest.id,
+            ..ContestProblem::sample("0", 0)
+        }];
+        let live = leaderboard::compute(&contest, &problems, &live_rows);
+        CachedStandings {
+            contest,
+            problem_titles: vec!["Zero".to_string()],
+            problems,
+            live_rows,
+            live,
+            frozen: None,
+            plagiarism: Mutex::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standings(contest_id: i32) -> CachedStandings {
+        CachedStandings::sample(Contest::sample(contest_id, "ratio"))
+    }
+
+    #[test]
+    fn test_hits_misses_and_invalidation() {
+        let cache = StandingsCache::default();
+        assert!(cache.get(1).is_none());
+
+        cache.insert(1, standings(1), cache.generation());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(1).is_some());
+        assert!(cache.peek(1).is_some());
+
+        cache.invalidate(1);
+        assert!(cache.get(1).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.invalidations), (2, 2, 1));
+        assert_eq!(stats.hit_rate(), 50.0);
+    }
+
+    #[test]
+    fn test_stale_load_is_not_cached() {
+        let cache = StandingsCache::default();
+        let generation = cache.generation();
+        cache.invalidate(1); // A new submission lands while the load is running
+        cache.insert(1, standings(1), generation);
+        assert!(cache.peek(1).is_none());
+    }
+
+    #[test]
+    fn test_best_length_lookup() {
+        let cached = standings(1);
+        assert_eq!(cached.best_length("alice", "0"), Some(40));
+        assert_eq!(cached.best_length("alice", "1"), None);
+        assert_eq!(cached.best_length("bob", "0"), None);
+        assert!(!cached.view(false, None).frozen);
+    }
+}
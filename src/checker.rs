@@ -0,0 +1,323 @@
+//! Deciding whether the output for a test case is correct
+//! Problems pick a built-in comparison in `problem.json`, or ship their own checker:
+//! `checker.py` or a testlib-style executable named `checker`, run as
+//! `checker <input> <contestant output> <expected output>` and exiting with 0 when the
+//! output is accepted, 1 (or testlib's 2) when it is wrong, and anything else on failure.
+//! Custom checkers run in the sandbox like submissions, see runner::CodeRunner
+
+/// File names a custom checker may have, in order of preference
+pub const CHECKER_FILES: [&str; 2] = ["checker.py", "checker"];
+
+/// Tolerance of the float checker when the problem does not set one
+pub const DEFAULT_FLOAT_TOLERANCE: f64 = 1e-6;
+
+/// How long a custom checker may take for one run
+pub const CHECKER_TIME_LIMIT_SECS: f64 = 10.0;
+
+/// The files a custom checker is given, in argument order
+const CHECKER_ARGS: [&str; 3] = ["input.txt", "output.txt", "answer.txt"];
+
+/// Built-in checkers offered in the problem editor: ID, description
+pub const BUILT_IN: [(&str, &str); 5] = [
+    ("exact", "Exact lines, ignoring trailing whitespace"),
+    ("tokens", "Whitespace-separated tokens"),
+    ("float", "Tokens, with numbers equal within a tolerance"),
+    ("unordered", "The same lines in any order"),
+    ("case_insensitive", "Tokens, ignoring case"),
+];
+
+/// A problem's own checker program
+#[derive(Debug, Clone)]
+pub struct CustomChecker {
+    pub file: &'static str, // One of CHECKER_FILES
+    pub contents: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Checker {
+    Exact,
+    Tokens,
+    Float(f64), // Largest absolute or relative difference accepted
+    Unordered,
+    CaseInsensitive,
+    Custom(CustomChecker),
+}
+
+impl CustomChecker {
+    /// Files to place next to each other before running the checker
+    pub fn files<'a>(
+        &'a self,
+        input: &'a str,
+        expected: &'a str,
+        actual: &'a str,
+    ) -> [(&'static str, &'a [u8]); 4] {
+        [
+            (CHECKER_ARGS[0], input.as_bytes()),
+            (CHECKER_ARGS[1], actual.as_bytes()),
+            (CHECKER_ARGS[2], expected.as_bytes()),
+            (self.file, &self.contents),
+        ]
+    }
+
+    /// Whether the checker is a program that must be made executable
+    pub fn is_executable(&self) -> bool {
+        !self.file.ends_with(".py")
+    }
+
+    /// Program and arguments running the checker from the directory holding its files
+    pub fn command(&self) -> (&'static str, Vec<&'static str>) {
+        if self.is_executable() {
+            ("./checker", CHECKER_ARGS.to_vec())
+        } else {
+            (
+                "/usr/bin/python3",
+                [self.file].into_iter().chain(CHECKER_ARGS).collect(),
+            )
+        }
+    }
+
+    /// The checker's decision from its exit code, None if it did not exit by itself
+    pub fn outcome(exit_code: Option<i32>, stdout: &str, stderr: &str) -> Result<Outcome, String> {
+        let message = format!("{}\n{}", stdout.trim(), stderr.trim())
+            .trim()
+            .to_string();
+        match exit_code {
+            Some(0) => Ok(Outcome {
+                accepted: true,
+                message,
+            }),
+            Some(1) | Some(2) => Ok(Outcome::reject(message)),
+            _ => Err(format!("Checker failed: {message}")),
+        }
+    }
+}
+
+/// A checker's decision about one test case
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub accepted: bool,
+    pub message: String, // Why the output was rejected, or the custom checker's comment
+}
+
+impl Outcome {
+    fn accept() -> Self {
+        Self {
+            accepted: true,
+            message: String::new(),
+        }
+    }
+
+    fn reject(message: String) -> Self {
+        Self {
+            accepted: false,
+            message,
+        }
+    }
+}
+
+impl Checker {
+    /// Build the checker a problem asks for. Without a `checker` setting a custom checker
+    /// file is used if there is one, otherwise `fallback`.
+    pub fn configure(
+        id: Option<&str>,
+        float_tolerance: Option<f64>,
+        custom: Option<CustomChecker>,
+        fallback: Checker,
+    ) -> Result<Self, String> {
+        let checker = match id {
+            None => custom.map(Checker::Custom).unwrap_or(fallback),
+            Some("exact") => Checker::Exact,
+            Some("tokens") => Checker::Tokens,
+            Some("float") => Checker::Float(float_tolerance.unwrap_or(DEFAULT_FLOAT_TOLERANCE)),
+            Some("unordered") => Checker::Unordered,
+            Some("case_insensitive") => Checker::CaseInsensitive,
+            Some("custom") => match custom {
+                Some(custom) => Checker::Custom(custom),
+                None => {
+                    return Err(format!(
+                        "checker is custom but there is no {}",
+                        CHECKER_FILES[0]
+                    ));
+                }
+            },
+            Some(other) => return Err(format!("unknown checker {other}")),
+        };
+        Ok(checker)
+    }
+
+    /// The custom checker's file, for storing with the problem version
+    pub fn custom(&self) -> Option<&CustomChecker> {
+        match self {
+            Checker::Custom(custom) => Some(custom),
+            _ => None,
+        }
+    }
+
+    /// Compare the output for one test case with the expected output using a built-in
+    /// checker. None for a custom checker, which has to be run in the sandbox.
+    pub fn compare(&self, expected: &str, actual: &str) -> Option<Outcome> {
+        let outcome = match self {
+            Checker::Exact => {
+                let (expected, actual) = (lines(expected), lines(actual));
+                outcome(expected == actual, || line_mismatch(&expected, &actual))
+            }
+            Checker::Tokens => compare_tokens(expected, actual, |e, a| e == a),
+            Checker::Float(tolerance) => compare_tokens(expected, actual, |e, a| {
+                match (e.parse::<f64>(), a.parse::<f64>()) {
+                    (Ok(e), Ok(a)) if e.is_finite() && a.is_finite() => {
+                        (e - a).abs() <= tolerance * e.abs().max(1.0)
+                    }
+                    _ => e == a,
+                }
+            }),
+            Checker::CaseInsensitive => compare_tokens(expected, actual, |e, a| {
+                e.to_lowercase() == a.to_lowercase()
+            }),
+            Checker::Unordered => {
+                let (mut expected, mut actual) = (lines(expected), lines(actual));
+                expected.sort_unstable();
+                actual.sort_unstable();
+                outcome(expected == actual, || {
+                    match expected.iter().find(|line| !actual.contains(line)) {
+                        Some(line) => format!("Missing line: {line}"),
+                        None => "Unexpected lines in the output".to_string(),
+                    }
+                })
+            }
+            Checker::Custom(_) => return None,
+        };
+        Some(outcome)
+    }
+}
+
+fn outcome(accepted: bool, message: impl FnOnce() -> String) -> Outcome {
+    if accepted {
+        Outcome::accept()
+    } else {
+        Outcome::reject(message())
+    }
+}
+
+/// Lines without trailing whitespace, ignoring blank lines at the end
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn line_mismatch(expected: &[&str], actual: &[&str]) -> String {
+    // Expected and actual are shown anyway, so only point into longer outputs
+    if expected.len() <= 1 && actual.len() <= 1 {
+        return String::new();
+    }
+    match expected.iter().zip(actual).position(|(e, a)| e != a) {
+        Some(i) => format!("Line {} differs", i + 1),
+        None => format!("Expected {} lines, got {}", expected.len(), actual.len()),
+    }
+}
+
+fn compare_tokens(expected: &str, actual: &str, equal: impl Fn(&str, &str) -> bool) -> Outcome {
+    let expected: Vec<&str> = expected.split_whitespace().collect();
+    let actual: Vec<&str> = actual.split_whitespace().collect();
+    if let Some(i) = expected.iter().zip(&actual).position(|(e, a)| !equal(e, a)) {
+        return Outcome::reject(format!(
+            "Token {}: expected {}, got {}",
+            i + 1,
+            expected[i],
+            actual[i]
+        ));
+    }
+    outcome(expected.len() == actual.len(), || {
+        format!("Expected {} tokens, got {}", expected.len(), actual.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(checker: Checker, expected: &str, actual: &str) -> bool {
+        checker.compare(expected, actual).unwrap().accepted
+    }
+
+    #[test]
+    fn test_built_in_checkers() {
+        assert!(check(Checker::Exact, "1 2\n3\n", "1 2  \n3\n\n"));
+        assert!(!check(Checker::Exact, "1 2\n3\n", "1  2\n3\n"));
+        assert!(check(Checker::Tokens, "1 2\n3\n", "1  2 3"));
+        assert!(!check(Checker::Tokens, "1 2 3", "1 2"));
+
+        assert!(check(Checker::Float(1e-6), "0.5 x", "0.5000001 x"));
+        assert!(check(Checker::Float(1e-6), "1e9", "1000000500"));
+        assert!(!check(Checker::Float(1e-6), "0.5", "0.51"));
+        assert!(!check(Checker::Float(1e-6), "nan", "inf"));
+
+        assert!(check(Checker::Unordered, "a\nb\nb\n", "b\na\nb"));
+        assert!(!check(Checker::Unordered, "a\nb\nb\n", "a\na\nb"));
+        assert!(check(Checker::CaseInsensitive, "Yes\nNO", "YES no"));
+    }
+
+    #[test]
+    fn test_configure() {
+        let custom = CustomChecker {
+            file: "checker.py",
+            contents: Vec::new(),
+        };
+        let configure = |id, custom| Checker::configure(id, None, custom, Checker::Exact);
+
+        assert!(matches!(configure(None, None), Ok(Checker::Exact)));
+        assert!(matches!(
+            configure(None, Some(custom.clone())),
+            Ok(Checker::Custom(_))
+        ));
+        assert!(matches!(
+            configure(Some("tokens"), Some(custom)),
+            Ok(Checker::Tokens)
+        ));
+        assert!(configure(Some("custom"), None).is_err());
+        assert!(configure(Some("fuzzy"), None).is_err());
+        assert!(matches!(
+            Checker::configure(Some("float"), None, None, Checker::Exact),
+            Ok(Checker::Float(t)) if t == DEFAULT_FLOAT_TOLERANCE
+        ));
+    }
+
+    #[test]
+    fn test_custom_checker_protocol() {
+        let python = CustomChecker {
+            file: "checker.py",
+            contents: b"exit(0)".to_vec(),
+        };
+        assert_eq!(
+            python.command(),
+            (
+                "/usr/bin/python3",
+                vec!["checker.py", "input.txt", "output.txt", "answer.txt"]
+            )
+        );
+        assert!(Checker::Custom(python).compare("1", "1").is_none());
+        let binary = CustomChecker {
+            file: "checker",
+            contents: Vec::new(),
+        };
+        assert!(binary.is_executable());
+        assert_eq!(binary.command().0, "./checker");
+        assert_eq!(
+            binary.files("in", "want", "got")[1],
+            ("output.txt", &b"got"[..])
+        );
+
+        assert!(
+            CustomChecker::outcome(Some(0), "ok\n", "")
+                .unwrap()
+                .accepted
+        );
+        let rejected = CustomChecker::outcome(Some(2), "", "wrong answer\n").unwrap();
+        assert!(!rejected.accepted);
+        assert_eq!(rejected.message, "wrong answer");
+        assert!(CustomChecker::outcome(Some(3), "", "").is_err());
+        assert!(CustomChecker::outcome(None, "", "").is_err());
+    }
+}
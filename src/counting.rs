@@ -0,0 +1,172 @@
+//! How a submission's length is measured, selectable per contest and overridable per problem
+
+use sqlx::PgPool;
+
+use crate::registry::ProblemRegistry;
+
+/// Rule turning source code into the length that is scored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteCount {
+    Raw,
+    Normalised,
+    Chars,
+    Sbcs,
+}
+
+impl ByteCount {
+    pub const ALL: [ByteCount; 4] = [
+        ByteCount::Raw,
+        ByteCount::Normalised,
+        ByteCount::Chars,
+        ByteCount::Sbcs,
+    ];
+
+    /// Look up a rule by its stored ID, falling back to `Raw`
+    pub fn parse(id: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.id() == id)
+            .unwrap_or(ByteCount::Raw)
+    }
+
+    /// The problem's own rule if it has one, otherwise the contest's
+    pub fn effective(contest_rule: &str, problem_rule: Option<&str>) -> Self {
+        Self::parse(problem_rule.unwrap_or(contest_rule))
+    }
+
+    /// Identifier stored in `contests.byte_count` and `problem.json`
+    pub fn id(&self) -> &'static str {
+        match self {
+            ByteCount::Raw => "raw",
+            ByteCount::Normalised => "normalised",
+            ByteCount::Chars => "chars",
+            ByteCount::Sbcs => "sbcs",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ByteCount::Raw => "Raw bytes",
+            ByteCount::Normalised => "Normalised bytes",
+            ByteCount::Chars => "Characters",
+            ByteCount::Sbcs => "Latin-1 bytes",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ByteCount::Raw => "UTF-8 bytes exactly as submitted.",
+            ByteCount::Normalised => {
+                "UTF-8 bytes after converting CRLF line endings to LF and removing trailing newlines."
+            }
+            ByteCount::Chars => "Unicode characters (code points), so é or λ count as one.",
+            ByteCount::Sbcs => {
+                "One byte per character when every character is in Latin-1 (ISO 8859-1), the single-byte encoding Python reads with a coding declaration; UTF-8 bytes otherwise."
+            }
+        }
+    }
+
+    /// Unit shown next to lengths measured with this rule
+    pub fn unit(&self) -> &'static str {
+        match self {
+            ByteCount::Chars => "chars",
+            _ => "bytes",
+        }
+    }
+
+    /// Scored length of some code
+    pub fn count(&self, code: &str) -> i32 {
+        let length = match self {
+            ByteCount::Raw => code.len(),
+            ByteCount::Normalised => normalise(code).len(),
+            ByteCount::Chars => code.chars().count(),
+            ByteCount::Sbcs => match latin1_len(code) {
+                Some(length) => length,
+                None => code.len(),
+            },
+        };
+        length as i32
+    }
+}
+
+/// Length of some code encoded as Latin-1, whose 256 bytes are U+0000 to U+00FF,
+/// if it can be
+fn latin1_len(code: &str) -> Option<usize> {
+    code.chars()
+        .try_fold(0, |n, c| (c as u32 <= 0xFF).then_some(n + 1))
+}
+
+/// CRLF line endings as LF, without trailing newlines
+pub fn normalise(code: &str) -> String {
+    code.replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+/// Re-measure a contest's stored submissions after its rule or a problem's rule changed,
+/// optionally only those for one problem. Returns how many lengths changed
+pub async fn recount(
+    db: &PgPool,
+    problems: &ProblemRegistry,
+    contest_id: i32,
+    problem_id: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let contest_rule: String = sqlx::query_scalar("SELECT byte_count FROM contests WHERE id = $1")
+        .bind(contest_id)
+        .fetch_one(db)
+        .await?;
+    let submissions: Vec<(String, String, String, i32)> = sqlx::query_as(
+        "SELECT id, problem_id, code, code_length FROM submissions
+         WHERE contest_id = $1 AND ($2::TEXT IS NULL OR problem_id = $2)",
+    )
+    .bind(contest_id)
+    .bind(problem_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut changed = 0;
+    for (id, problem_id, code, old_length) in submissions {
+        let problem_rule = problems
+            .get(&problem_id)
+            .and_then(|p| p.metadata.byte_count.clone());
+        let length = ByteCount::effective(&contest_rule, problem_rule.as_deref()).count(&code);
+        if length != old_length {
+            sqlx::query("UPDATE submissions SET code_length = $2 WHERE id = $1")
+                .bind(&id)
+                .bind(length)
+                .execute(db)
+                .await?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_rules() {
+        let code = "print('λ')\r\n\r\n";
+        assert_eq!(ByteCount::Raw.count(code), 15);
+        assert_eq!(ByteCount::Normalised.count(code), 11);
+        assert_eq!(ByteCount::Chars.count(code), 14);
+        // λ has no Latin-1 byte
+        assert_eq!(ByteCount::Sbcs.count(code), 15);
+
+        let latin = "print('é×ÿ')";
+        assert_eq!(ByteCount::Raw.count(latin), 15);
+        assert_eq!(ByteCount::Sbcs.count(latin), 12);
+    }
+
+    #[test]
+    fn test_problem_rule_overrides_contest() {
+        assert_eq!(ByteCount::effective("chars", None), ByteCount::Chars);
+        assert_eq!(
+            ByteCount::effective("chars", Some("normalised")),
+            ByteCount::Normalised
+        );
+        assert_eq!(ByteCount::parse("unknown"), ByteCount::Raw);
+    }
+}
@@ -0,0 +1,194 @@
+//! Live contest events, pushed to leaderboard and waiting-room clients over SSE
+//! Every event carries enough to bring a client fully up to date, so a client that
+//! lags behind the broadcast buffer can simply skip the events it missed
+
+use std::sync::Arc;
+
+use axum::response::sse::Event;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+use crate::{cache::CachedStandings, leaderboard::Snapshot};
+
+/// Events buffered per subscriber before it starts lagging
+const CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContestEvent {
+    /// Standings changed. No snapshot while the leaderboard is frozen,
+    /// since each viewer then sees a different board and must refetch it
+    Standings {
+        contest_id: i32,
+        snapshot: Option<Arc<Snapshot>>,
+    },
+    /// Contest started, ended or was extended
+    Status {
+        contest_id: i32,
+        status: String,
+        end_time: Option<i64>,
+    },
+}
+
+impl ContestEvent {
+    /// The public board for everyone, built once and shared by every subscriber
+    pub fn standings(standings: &CachedStandings, now: i64) -> Self {
+        let snapshot = (!standings.contest.is_frozen(now)).then(|| {
+            let mut snapshot = standings.view(false, None);
+            standings.hide_unreleased(&mut snapshot, now);
+            Arc::new(snapshot)
+        });
+        ContestEvent::Standings {
+            contest_id: standings.contest.id,
+            snapshot,
+        }
+    }
+
+    pub fn contest_id(&self) -> i32 {
+        match self {
+            ContestEvent::Standings { contest_id, .. }
+            | ContestEvent::Status { contest_id, .. } => *contest_id,
+        }
+    }
+
+    pub fn to_sse(&self) -> Event {
+        let name = match self {
+            ContestEvent::Standings { .. } => "standings",
+            ContestEvent::Status { .. } => "status",
+        };
+        Event::default()
+            .event(name)
+            .json_data(self)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<ContestEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ContestEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Whether anyone is listening (so work to build an event can be skipped)
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, event: ContestEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    /// Events for one contest. Lagged subscribers just skip ahead, since every event is
+    /// a full update.
+    pub fn contest_events(&self, contest_id: i32) -> impl Stream<Item = ContestEvent> + use<> {
+        BroadcastStream::new(self.subscribe())
+            .filter_map(move |event| event.ok().filter(|e| e.contest_id() == contest_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Contest;
+
+    fn status(contest_id: i32, status: &str) -> ContestEvent {
+        ContestEvent::Status {
+            contest_id,
+            status: status.to_string(),
+            end_time: None,
+        }
+    }
+
+    fn status_of(event: Option<ContestEvent>) -> String {
+        match event {
+            Some(ContestEvent::Status { status, .. }) => status,
+            other => panic!("expected a status event, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_get_their_contests_events() {
+        let hub = EventHub::new();
+        assert!(!hub.has_subscribers());
+        let mut events = Box::pin(hub.contest_events(1));
+        assert!(hub.has_subscribers());
+
+        hub.publish(status(2, "active"));
+        hub.publish(ContestEvent::standings(
+            &CachedStandings::sample(Contest::sample(1, "ratio")),
+            1000,
+        ));
+        match events.next().await {
+            Some(ContestEvent::Standings {
+                contest_id: 1,
+                snapshot: Some(snapshot),
+            }) => {
+                assert_eq!(snapshot.entries[0].username, "alice");
+                assert!(!snapshot.frozen);
+            }
+            other => panic!("expected standings, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_frozen_board_sends_no_snapshot() {
+        let contest = Contest {
+            freeze_minutes: 10,
+            ..Contest::sample(1, "ratio")
+        };
+        let standings = CachedStandings::sample(contest);
+        let freeze = standings.contest.freeze_time().unwrap();
+        assert!(matches!(
+            ContestEvent::standings(&standings, freeze - 1),
+            ContestEvent::Standings {
+                snapshot: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            ContestEvent::standings(&standings, freeze),
+            ContestEvent::Standings { snapshot: None, .. }
+        ));
+
+        // The snapshot is public again once the final standings are revealed
+        let revealed = CachedStandings::sample(Contest {
+            revealed: true,
+            ..standings.contest.clone()
+        });
+        assert!(matches!(
+            ContestEvent::standings(&revealed, freeze),
+            ContestEvent::Standings {
+                snapshot: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscribers_skip_ahead() {
+        let hub = EventHub::new();
+        let mut events = Box::pin(hub.contest_events(1));
+        for i in 0..CAPACITY + 10 {
+            hub.publish(status(1, &i.to_string()));
+        }
+        hub.publish(status(1, "latest"));
+
+        // The oldest events were dropped, and the stream carries on from the oldest kept
+        assert_eq!(status_of(events.next().await), "11");
+        for _ in 12..CAPACITY + 10 {
+            events.next().await;
+        }
+        assert_eq!(status_of(events.next().await), "latest");
+    }
+}
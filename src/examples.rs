@@ -0,0 +1,305 @@
+//! Worked examples in statements, expanded from `{{examples N}}` directives so they always
+//! match the tests. Full programs show a test case's input and output. For function problems
+//! the grader is run with a stand-in `f` that records how it is called and returns the
+//! expected output, so the calls shown are exactly those the tests make.
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::problems::{self, ProblemKind};
+
+/// Start of the directive; `{{examples}}` shows every chosen example, `{{examples 2}}` the first two
+pub const DIRECTIVE: &str = "{{examples";
+
+/// Most examples a problem can show
+pub const MAX_EXAMPLES: usize = 10;
+
+/// How long the grader may take to make the calls for the examples
+const GRADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stand-in submission recording each call of `f` to calls.txt
+const RECORDER: &str = r#"import ast
+
+with open("expected.txt") as __file:
+    __expected = __file.read().splitlines()
+__count = 0
+
+
+def f(*args, **kwargs):
+    global __count
+    __count += 1
+    __args = [repr(a) for a in args] + [f"{k}={v!r}" for k, v in kwargs.items()]
+    with open("calls.txt", "a") as __file:
+        __file.write(f"f({', '.join(__args)})\n")
+    if __count >= LIMIT:
+        raise SystemExit
+    try:
+        return ast.literal_eval(__expected[__count - 1])
+    except (IndexError, ValueError, SyntaxError):
+        return None
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Example {
+    /// A call of `f` and the result it should return
+    Call { call: String, result: String },
+    /// A test case's input and expected output
+    Run { input: String, output: String },
+}
+
+/// A problem's examples, or why they could not be worked out
+pub type Examples = Result<Vec<Example>, String>;
+
+/// Test cases to show, numbered from 1: those chosen in `problem.json`, else the first ones
+fn chosen(designated: Option<&[usize]>) -> Vec<usize> {
+    match designated {
+        Some(numbers) => numbers.iter().copied().take(MAX_EXAMPLES).collect(),
+        None => (1..=MAX_EXAMPLES).collect(),
+    }
+}
+
+/// Work out the examples of a problem from its tests. Without designated test cases, as many
+/// of the first ten as exist are used.
+pub fn collect(
+    kind: ProblemKind,
+    grader: &str,
+    test_input: &str,
+    test_output: &str,
+    designated: Option<&[usize]>,
+) -> Examples {
+    let numbers = chosen(designated);
+    let examples = match kind {
+        ProblemKind::Program => problems::split_tests(test_input, test_output)?
+            .into_iter()
+            .map(|(input, output)| Example::Run { input, output })
+            .collect(),
+        ProblemKind::Function => {
+            let results: Vec<&str> = test_output.lines().collect();
+            let limit = numbers
+                .iter()
+                .copied()
+                .max()
+                .unwrap_or(0)
+                .min(results.len());
+            record_calls(grader, test_input, test_output, limit)?
+                .into_iter()
+                .zip(results)
+                .map(|(call, result)| Example::Call {
+                    call,
+                    result: result.trim_end().to_string(),
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    match designated {
+        Some(_) => numbers
+            .iter()
+            .map(|&n| {
+                n.checked_sub(1)
+                    .and_then(|i| examples.get(i))
+                    .cloned()
+                    .ok_or_else(|| format!("there is no test case {n}"))
+            })
+            .collect(),
+        None => Ok(examples.into_iter().take(MAX_EXAMPLES).collect()),
+    }
+}
+
+/// Run the grader on the tests with the recorder as the submission, until `limit` calls
+fn record_calls(
+    grader: &str,
+    test_input: &str,
+    test_output: &str,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+    static NEXT_DIR: AtomicU32 = AtomicU32::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "golf-examples-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create a directory: {e}"))?;
+    let result = record_calls_in(&dir, grader, test_input, test_output, limit);
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn record_calls_in(
+    dir: &Path,
+    grader: &str,
+    test_input: &str,
+    test_output: &str,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    let recorder = RECORDER.replace("LIMIT", &limit.to_string());
+    for (name, contents) in [
+        ("grader.py", grader),
+        ("submission.py", &recorder),
+        ("expected.txt", test_output),
+    ] {
+        fs::write(dir.join(name), contents).map_err(|e| format!("Failed to write {name}: {e}"))?;
+    }
+
+    let mut child = Command::new("python3")
+        .arg("grader.py")
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run the grader: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The grader may stop reading early, once the recorder has seen enough calls
+        let _ = stdin.write_all(test_input.as_bytes());
+    }
+    let deadline = Instant::now() + GRADER_TIMEOUT;
+    while child.try_wait().ok().flatten().is_none() {
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("the grader timed out".to_string());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let calls = fs::read_to_string(dir.join("calls.txt")).unwrap_or_default();
+    let calls: Vec<String> = calls.lines().map(str::to_string).collect();
+    if calls.is_empty() {
+        return Err("the grader did not call f".to_string());
+    }
+    Ok(calls)
+}
+
+/// Replace each `{{examples N}}` in a statement with markdown showing the examples
+pub fn expand(markdown: &str, examples: &Examples) -> String {
+    let mut out = String::new();
+    let mut rest = markdown;
+    while let Some(start) = rest.find(DIRECTIVE) {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        let argument = rest[start + DIRECTIVE.len()..end].trim();
+        let count = match argument {
+            "" => Some(MAX_EXAMPLES),
+            n => n.parse::<usize>().ok(),
+        };
+        let Some(count) = count else {
+            // Not a directive after all, such as {{examplesX}}
+            out.push_str(&rest[..end + 2]);
+            rest = &rest[end + 2..];
+            continue;
+        };
+
+        out.push_str(&rest[..start]);
+        out.push_str("\n\n");
+        match examples {
+            Ok(examples) => {
+                for example in examples.iter().take(count) {
+                    out.push_str(&example_markdown(example));
+                }
+            }
+            Err(e) => out.push_str(&format!("*Examples are unavailable: {e}.*\n\n")),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn example_markdown(example: &Example) -> String {
+    match example {
+        Example::Call { call, result } => {
+            format!("{} returns {}\n\n", code_span(call), code_span(result))
+        }
+        Example::Run { input, output } => format!(
+            "**Input**\n\n{}\n**Output**\n\n{}\n",
+            code_block(input),
+            code_block(output)
+        ),
+    }
+}
+
+/// Longest run of backticks in some text
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+fn code_span(text: &str) -> String {
+    let fence = "`".repeat(longest_backtick_run(text) + 1);
+    // Padding keeps a leading or trailing backtick from joining the fence
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{fence} {text} {fence}")
+    } else {
+        format!("{fence}{text}{fence}")
+    }
+}
+
+fn code_block(text: &str) -> String {
+    let fence = "`".repeat(longest_backtick_run(text).max(2) + 1);
+    let newline = if text.ends_with('\n') { "" } else { "\n" };
+    format!("{fence}\n{text}{newline}{fence}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(call: &str, result: &str) -> Example {
+        Example::Call {
+            call: call.to_string(),
+            result: result.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let examples = Ok(vec![call("f(1)", "2"), call("f('`')", "'``'")]);
+        assert_eq!(
+            expand("Examples:\n\n{{examples 1}}\nMore", &examples),
+            "Examples:\n\n\n\n`f(1)` returns `2`\n\n\nMore"
+        );
+        assert!(expand("{{examples}}", &examples).contains("``f('`')`` returns ```'``'```"));
+        assert_eq!(
+            expand("{{examplesX}} {{x}}", &examples),
+            "{{examplesX}} {{x}}"
+        );
+        assert!(
+            expand("{{examples 2}}", &Err("no grader".to_string()))
+                .contains("*Examples are unavailable: no grader.*")
+        );
+    }
+
+    #[test]
+    fn test_program_examples() {
+        let (input, output) = ("1 2\n---\n3 4\n---\n5 6\n", "3\n---\n7\n---\n11\n");
+        let run = |input: &str, output: &str| Example::Run {
+            input: input.to_string(),
+            output: output.to_string(),
+        };
+        assert_eq!(
+            collect(ProblemKind::Program, "", input, output, None),
+            Ok(vec![
+                run("1 2\n", "3\n"),
+                run("3 4\n", "7\n"),
+                run("5 6\n", "11\n")
+            ])
+        );
+        assert_eq!(
+            collect(ProblemKind::Program, "", input, output, Some(&[3, 1])),
+            Ok(vec![run("5 6\n", "11\n"), run("1 2\n", "3\n")])
+        );
+        assert!(collect(ProblemKind::Program, "", input, output, Some(&[4])).is_err());
+        assert!(collect(ProblemKind::Program, "", input, output, Some(&[0])).is_err());
+    }
+}
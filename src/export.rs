@@ -0,0 +1,99 @@
+//! Contest result exports: standings as CSV for spreadsheets
+//! (the JSON dump and static HTML archive are rendered in `routes::export`)
+
+use crate::leaderboard::Snapshot;
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    fields.join(",") + "\r\n"
+}
+
+/// Final standings, one row per entry with its best length on each problem
+pub fn standings_csv(snapshot: &Snapshot) -> String {
+    let mut header: Vec<String> = [
+        "rank", "name", "solved", "points", "bytes", "diamonds", "golds",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    header.extend(snapshot.problem_titles.iter().cloned());
+
+    let mut out = csv_row(&header);
+    for entry in &snapshot.entries {
+        let mut row = vec![
+            entry.rank.map(|r| r.to_string()).unwrap_or_default(),
+            entry.username.clone(),
+            entry.problems_solved.to_string(),
+            entry.total_score.to_string(),
+            entry.total_bytes.to_string(),
+            entry.diamonds.to_string(),
+            entry.golds.to_string(),
+        ];
+        row.extend(entry.problem_results.iter().map(|r| {
+            r.as_ref()
+                .map(|r| r.code_length.to_string())
+                .unwrap_or_default()
+        }));
+        out.push_str(&csv_row(&row));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaderboard::{LeaderboardEntry, Medal, ProblemResult};
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_standings_csv() {
+        let entry = |username: &str, rank: Option<usize>, length: Option<i32>| LeaderboardEntry {
+            username: username.to_string(),
+            total_score: length.map_or(0, |_| 10000),
+            problems_solved: length.map_or(0, |_| 1),
+            total_bytes: length.unwrap_or(0) as i64,
+            diamonds: length.map_or(0, |_| 1),
+            golds: 0,
+            problem_results: vec![
+                length.map(|code_length| ProblemResult {
+                    code_length,
+                    medal: Medal::Diamond,
+                    points: 10000,
+                    author: None,
+                    after_freeze: false,
+                }),
+                None,
+            ],
+            rank,
+        };
+        let snapshot = Snapshot {
+            entries: vec![entry("alice", Some(1), Some(42)), entry("bob", None, None)],
+            problem_ids: vec!["0".to_string(), "1".to_string()],
+            problem_labels: vec!["A".to_string(), "B".to_string()],
+            problem_titles: vec!["Hello, World".to_string(), "Fizz".to_string()],
+            frozen: false,
+        };
+
+        assert_eq!(
+            standings_csv(&snapshot),
+            "rank,name,solved,points,bytes,diamonds,golds,\"Hello, World\",Fizz\r\n\
+             1,alice,1,10000,42,1,0,42,\r\n\
+             ,bob,0,0,0,0,0,,\r\n"
+        );
+    }
+}
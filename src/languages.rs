@@ -4,7 +4,6 @@
 #[derive(Debug, Clone)]
 pub struct Language {
     pub id: &'static str,
-    #[allow(dead_code)]
     pub name: &'static str,
     #[allow(dead_code)]
     pub file_extension: &'static str,
@@ -51,13 +50,26 @@ impl Language {
 }
 
 // Language definitions
-static LANGUAGES: &[Language] = &[Language {
-    id: "python3.11_function_f",
-    name: "Python 3.11 (function f)",
-    file_extension: ".py",
-    compile_command: None,
-    run_command: RunCommand {
-        program: "/usr/bin/python3",
-        args: &["main.py"],
+static LANGUAGES: &[Language] = &[
+    Language {
+        id: "python3.11_function_f",
+        name: "Python 3.11 (function f)",
+        file_extension: ".py",
+        compile_command: None,
+        run_command: RunCommand {
+            program: "/usr/bin/python3",
+            args: &["main.py"],
+        },
+    },
+    // Runs the submission itself, with the test input on stdin
+    Language {
+        id: "python3.11_program",
+        name: "Python 3.11 (full program)",
+        file_extension: ".py",
+        compile_command: None,
+        run_command: RunCommand {
+            program: "/usr/bin/python3",
+            args: &["submission.py"],
+        },
     },
-}];
+];
@@ -0,0 +1,564 @@
+//! Contest standings, shared by the leaderboard page and its JSON API
+//! Rows are users, or teams in team contests. Invalidated submissions never count,
+//! disqualified entries are hidden, and medals are only decided among entries in good standing
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    models::{Contest, ContestProblem},
+    registry::ProblemRegistry,
+    scoring::{self, ScoringSystem},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Medal {
+    Diamond, // Unique shortest solution
+    Gold,    // Tied shortest solution
+    None,
+}
+
+impl Medal {
+    pub fn is_diamond(&self) -> bool {
+        *self == Medal::Diamond
+    }
+
+    pub fn is_gold(&self) -> bool {
+        *self == Medal::Gold
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProblemResult {
+    pub code_length: i32,
+    pub medal: Medal,
+    pub points: i32,
+    pub author: Option<String>, // Team member who submitted it (team contests only)
+    pub after_freeze: bool,     // Viewer's own result from after the freeze, not yet scored
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardEntry {
+    pub username: String, // Team name in team contests
+    pub total_score: i32,
+    pub problems_solved: i64,
+    pub total_bytes: i64,
+    pub diamonds: i32,
+    pub golds: i32,
+    pub problem_results: Vec<Option<ProblemResult>>, // One per problem in order
+    pub rank: Option<usize>,                         // None for hors concours entries
+}
+
+/// Standings with the contest's problem columns, as served to leaderboard clients
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub entries: Vec<LeaderboardEntry>,
+    pub problem_ids: Vec<String>,
+    pub problem_labels: Vec<String>, // Aliases, or A, B, C... by order
+    pub problem_titles: Vec<String>,
+    pub frozen: bool,
+}
+
+/// One leaderboard row (user or team) with its best accepted submission to one problem.
+/// Entries without any accepted submission appear once with the submission fields NULL
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BestRow {
+    pub entity: String,
+    pub standing: String, // 'ok', 'disqualified' or 'hors_concours'
+    pub problem_id: Option<String>,
+    pub code_length: Option<i32>,
+    pub author: Option<String>,
+    pub created_at: Option<i64>,
+}
+
+const INDIVIDUAL_SQL: &str = r#"
+    WITH best AS (
+        SELECT DISTINCT ON (problem_id, username)
+               username AS entity, problem_id, code_length, username AS author, created_at
+        FROM submissions
+        WHERE contest_id = $1 AND verdict = 'AC' AND invalidated_reason IS NULL
+        AND ($2::BIGINT IS NULL OR created_at < $2)
+        ORDER BY problem_id, username, code_length ASC, created_at ASC
+    ),
+    entities AS (
+        SELECT username AS entity, standing FROM contest_participants WHERE contest_id = $1
+    )
+    SELECT COALESCE(e.entity, b.entity) AS entity, COALESCE(e.standing, 'ok') AS standing,
+           b.problem_id, b.code_length, b.author, b.created_at
+    FROM entities e
+    FULL OUTER JOIN best b ON b.entity = e.entity
+"#;
+
+// A team takes the worst standing of its members
+const TEAM_SQL: &str = r#"
+    WITH best AS (
+        SELECT DISTINCT ON (s.problem_id, s.team_id)
+               s.team_id, s.problem_id, s.code_length, s.username AS author, s.created_at
+        FROM submissions s
+        WHERE s.contest_id = $1 AND s.team_id IS NOT NULL
+        AND s.verdict = 'AC' AND s.invalidated_reason IS NULL
+        AND ($2::BIGINT IS NULL OR s.created_at < $2)
+        ORDER BY s.problem_id, s.team_id, s.code_length ASC, s.created_at ASC
+    ),
+    entities AS (
+        SELECT t.id, t.name AS entity,
+               COALESCE((SELECT CASE
+                    WHEN bool_or(p.standing = 'disqualified') THEN 'disqualified'
+                    WHEN bool_or(p.standing = 'hors_concours') THEN 'hors_concours'
+                    ELSE 'ok'
+                END
+                FROM team_members m
+                JOIN contest_participants p ON p.contest_id = m.contest_id AND p.username = m.username
+                WHERE m.team_id = t.id), 'ok') AS standing
+        FROM teams t
+        WHERE t.contest_id = $1
+    )
+    SELECT e.entity, e.standing, b.problem_id, b.code_length, b.author, b.created_at
+    FROM entities e
+    LEFT JOIN best b ON b.team_id = e.id
+"#;
+
+/// The stricter of two participant standings, for when two accounts become one:
+/// disqualified beats hors concours, which beats ok
+pub fn stricter_standing<'a>(a: &'a str, b: &'a str) -> &'a str {
+    let severity = |standing: &str| match standing {
+        "disqualified" => 2,
+        "hors_concours" => 1,
+        _ => 0,
+    };
+    if severity(b) > severity(a) { b } else { a }
+}
+
+/// Fetch every entry's best submissions for a contest in one query,
+/// counting only submissions made before `before` if given
+pub async fn fetch_rows(
+    db: &PgPool,
+    contest: &Contest,
+    before: Option<i64>,
+) -> Result<Vec<BestRow>, sqlx::Error> {
+    let sql = if contest.is_team_contest() {
+        TEAM_SQL
+    } else {
+        INDIVIDUAL_SQL
+    };
+    sqlx::query_as::<_, BestRow>(sql)
+        .bind(contest.id)
+        .bind(before)
+        .fetch_all(db)
+        .await
+}
+
+/// Problems of a contest with their settings and titles, in order
+pub async fn contest_problems(
+    db: &PgPool,
+    problems: &ProblemRegistry,
+    contest_id: i32,
+) -> (Vec<ContestProblem>, Vec<String>) {
+    let contest_problems = sqlx::query_as::<_, ContestProblem>(
+        "SELECT * FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+
+    let problem_titles: Vec<String> = contest_problems
+        .iter()
+        .map(|cp| problems.title(&cp.problem_id))
+        .collect();
+
+    (contest_problems, problem_titles)
+}
+
+/// Show `viewer` their own post-freeze results without changing scores, medals or order
+pub fn overlay_own_results(
+    entries: &mut [LeaderboardEntry],
+    contest: &Contest,
+    problems: &[ContestProblem],
+    live_rows: &[BestRow],
+    viewer: &str,
+) {
+    let Some(entry) = entries.iter_mut().find(|e| e.username == viewer) else {
+        return;
+    };
+    for row in live_rows.iter().filter(|r| r.entity == viewer) {
+        let (Some(pid), Some(code_length)) = (&row.problem_id, row.code_length) else {
+            continue;
+        };
+        let Some(slot) = problems.iter().position(|p| p.problem_id == *pid) else {
+            continue;
+        };
+        let improved = entry.problem_results[slot]
+            .as_ref()
+            .is_none_or(|r| code_length < r.code_length);
+        if improved {
+            entry.problem_results[slot] = Some(ProblemResult {
+                code_length,
+                medal: Medal::None,
+                points: 0,
+                author: row.author.clone().filter(|_| contest.is_team_contest()),
+                after_freeze: true,
+            });
+        }
+    }
+}
+
+/// Standings after revealing each problem in turn: step 0 is the frozen board,
+/// step k has the final results for the first k problems
+pub fn reveal_steps(
+    contest: &Contest,
+    problems: &[ContestProblem],
+    frozen_rows: &[BestRow],
+    final_rows: &[BestRow],
+) -> Vec<Vec<LeaderboardEntry>> {
+    (0..=problems.len())
+        .map(|k| {
+            let revealed = &problems[..k];
+            let is_revealed = |row: &BestRow| {
+                row.problem_id
+                    .as_ref()
+                    .is_some_and(|pid| revealed.iter().any(|p| p.problem_id == *pid))
+            };
+            // Entries with no frozen results still need a row
+            let placeholders = final_rows.iter().map(|row| BestRow {
+                problem_id: None,
+                code_length: None,
+                author: None,
+                created_at: None,
+                ..row.clone()
+            });
+            let rows: Vec<BestRow> = final_rows
+                .iter()
+                .filter(|row| is_revealed(row))
+                .cloned()
+                .chain(
+                    frozen_rows
+                        .iter()
+                        .filter(|row| row.problem_id.is_some() && !is_revealed(row))
+                        .cloned(),
+                )
+                .chain(placeholders)
+                .collect();
+            compute(contest, problems, &rows)
+        })
+        .collect()
+}
+
+/// Build sorted, ranked standings from best-submission rows
+pub fn compute(
+    contest: &Contest,
+    problems: &[ContestProblem],
+    rows: &[BestRow],
+) -> Vec<LeaderboardEntry> {
+    let scoring = scoring::get(&contest.scoring);
+    let team_mode = contest.is_team_contest();
+
+    // Lengths in good standing per problem decide the best length, medals and ranks
+    let mut ranked_lengths: HashMap<&str, Vec<i32>> = HashMap::new();
+    for row in rows {
+        if let (Some(pid), Some(len)) = (&row.problem_id, row.code_length)
+            && row.standing == "ok"
+        {
+            ranked_lengths.entry(pid.as_str()).or_default().push(len);
+        }
+    }
+
+    let mut entries: Vec<LeaderboardEntry> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+
+    for row in rows {
+        if row.standing == "disqualified" {
+            continue;
+        }
+
+        let i = *index.entry(row.entity.as_str()).or_insert_with(|| {
+            entries.push(LeaderboardEntry {
+                username: row.entity.clone(),
+                total_score: 0,
+                problems_solved: 0,
+                total_bytes: 0,
+                diamonds: 0,
+                golds: 0,
+                problem_results: vec![None; problems.len()],
+                rank: (row.standing == "ok").then_some(0),
+            });
+            entries.len() - 1
+        });
+        let entry = &mut entries[i];
+
+        let (Some(pid), Some(code_length)) = (&row.problem_id, row.code_length) else {
+            continue;
+        };
+        let Some(slot) = problems.iter().position(|p| p.problem_id == *pid) else {
+            continue;
+        };
+
+        let lengths = ranked_lengths
+            .get(pid.as_str())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let best_length = lengths.iter().copied().min();
+
+        // Unranked entries can't take medals
+        let medal = match best_length {
+            Some(best) if row.standing == "ok" && code_length == best => {
+                if lengths.iter().filter(|&&l| l == best).count() == 1 {
+                    entry.diamonds += 1;
+                    Medal::Diamond
+                } else {
+                    entry.golds += 1;
+                    Medal::Gold
+                }
+            }
+            _ => Medal::None,
+        };
+
+        // An unranked entry may be the only solver, in which case it sets its own best
+        let points = scoring.problem_points(&scoring::ProblemResult {
+            code_length,
+            best_length: best_length.unwrap_or(code_length),
+            rank: scoring::rank_of(code_length, lengths),
+            solvers: lengths.len(),
+            elapsed: row.created_at.unwrap_or(0) - contest.start_time.unwrap_or(0),
+            duration: contest.duration as i64,
+        });
+        let points = scoring::weighted(points, problems[slot].weight);
+
+        entry.total_score += points;
+        entry.problems_solved += 1;
+        entry.total_bytes += code_length as i64;
+        entry.problem_results[slot] = Some(ProblemResult {
+            code_length,
+            medal,
+            points,
+            author: if team_mode { row.author.clone() } else { None },
+            after_freeze: false,
+        });
+    }
+
+    sort_entries(&mut entries, scoring);
+    assign_ranks(&mut entries);
+    entries
+}
+
+/// Sort entries best first under the scoring system, then by name
+fn sort_entries(entries: &mut [LeaderboardEntry], scoring: &dyn ScoringSystem) {
+    let totals = |e: &LeaderboardEntry| scoring::Totals {
+        score: e.total_score,
+        solved: e.problems_solved,
+        bytes: e.total_bytes,
+    };
+    entries.sort_by(|a, b| {
+        scoring
+            .compare(&totals(a), &totals(b))
+            .then(a.username.cmp(&b.username))
+    });
+}
+
+/// Number ranked entries 1, 2, 3... in their sorted order, skipping hors concours entries
+fn assign_ranks(entries: &mut [LeaderboardEntry]) {
+    let mut next = 1;
+    for entry in entries.iter_mut() {
+        if entry.rank.is_some() {
+            entry.rank = Some(next);
+            next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contest(scoring: &str) -> Contest {
+        Contest::sample(1, scoring)
+    }
+
+    fn row(entity: &str, problem: &str, code_length: i32) -> BestRow {
+        BestRow {
+            entity: entity.to_string(),
+            standing: "ok".to_string(),
+            problem_id: Some(problem.to_string()),
+            code_length: Some(code_length),
+            author: Some(entity.to_string()),
+            created_at: Some(1000),
+        }
+    }
+
+    fn participant(entity: &str, standing: &str) -> BestRow {
+        BestRow {
+            entity: entity.to_string(),
+            standing: standing.to_string(),
+            problem_id: None,
+            code_length: None,
+            author: None,
+            created_at: None,
+        }
+    }
+
+    fn problem(id: &str, order: i32) -> ContestProblem {
+        ContestProblem::sample(id, order)
+    }
+
+    fn problems() -> Vec<ContestProblem> {
+        vec![problem("0", 0), problem("1", 1)]
+    }
+
+    fn entry<'a>(entries: &'a [LeaderboardEntry], name: &str) -> &'a LeaderboardEntry {
+        entries.iter().find(|e| e.username == name).unwrap()
+    }
+
+    #[test]
+    fn test_diamond_and_gold() {
+        let rows = vec![
+            row("alice", "0", 40),
+            row("bob", "0", 50),
+            row("alice", "1", 30),
+            row("bob", "1", 30),
+        ];
+        let entries = compute(&contest("ratio"), &problems(), &rows);
+
+        let alice = entry(&entries, "alice");
+        assert_eq!((alice.diamonds, alice.golds), (1, 1));
+        assert_eq!(
+            alice.problem_results[0].as_ref().unwrap().medal,
+            Medal::Diamond
+        );
+        assert_eq!(
+            alice.problem_results[1].as_ref().unwrap().medal,
+            Medal::Gold
+        );
+        assert_eq!(alice.total_score, 20000);
+
+        let bob = entry(&entries, "bob");
+        assert_eq!((bob.diamonds, bob.golds), (0, 1));
+        assert_eq!(bob.problem_results[0].as_ref().unwrap().medal, Medal::None);
+        assert_eq!(bob.total_score, 8000 + 10000);
+
+        assert_eq!(entries[0].username, "alice");
+        assert_eq!(entries[0].rank, Some(1));
+        assert_eq!(entries[1].rank, Some(2));
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut problems = problems();
+        problems[1].weight = 2.5;
+        let rows = vec![
+            row("alice", "0", 40),
+            row("bob", "0", 80),
+            row("bob", "1", 30),
+        ];
+        let entries = compute(&contest("ratio"), &problems, &rows);
+
+        let bob = entry(&entries, "bob");
+        assert_eq!(bob.problem_results[1].as_ref().unwrap().points, 25000);
+        assert_eq!(bob.total_score, 5000 + 25000);
+        assert_eq!(entries[0].username, "bob");
+        assert_eq!(entry(&entries, "alice").total_score, 10000);
+    }
+
+    #[test]
+    fn test_ties_are_broken_by_name() {
+        let rows = vec![row("carol", "0", 40), row("bob", "0", 40)];
+        let entries = compute(&contest("bytes"), &problems(), &rows);
+        let names: Vec<&str> = entries.iter().map(|e| e.username.as_str()).collect();
+        assert_eq!(names, ["bob", "carol"]);
+        assert_eq!(entries[0].total_bytes, entries[1].total_bytes);
+        assert_eq!(entries[1].rank, Some(2));
+    }
+
+    #[test]
+    fn test_zero_solves() {
+        let rows = vec![row("alice", "0", 40), participant("dave", "ok")];
+        let entries = compute(&contest("ratio"), &problems(), &rows);
+
+        let dave = entry(&entries, "dave");
+        assert_eq!(dave.problems_solved, 0);
+        assert_eq!(dave.total_score, 0);
+        assert_eq!(dave.total_bytes, 0);
+        assert!(dave.problem_results.iter().all(Option::is_none));
+        assert_eq!(entries.last().unwrap().username, "dave");
+        assert_eq!(dave.rank, Some(2));
+    }
+
+    #[test]
+    fn test_unranked_entries() {
+        let mut hc = row("erin", "0", 30);
+        hc.standing = "hors_concours".to_string();
+        let mut dq = row("mallory", "0", 10);
+        dq.standing = "disqualified".to_string();
+        let rows = vec![row("alice", "0", 40), hc, dq];
+        let entries = compute(&contest("ratio"), &problems(), &rows);
+
+        // Disqualified entries are hidden and don't affect the best length
+        assert_eq!(entries.len(), 2);
+        let alice = entry(&entries, "alice");
+        assert_eq!(
+            alice.problem_results[0].as_ref().unwrap().medal,
+            Medal::Diamond
+        );
+        assert_eq!(alice.rank, Some(1));
+
+        let erin = entry(&entries, "erin");
+        assert_eq!(erin.problem_results[0].as_ref().unwrap().medal, Medal::None);
+        assert_eq!(erin.rank, None);
+    }
+
+    #[test]
+    fn test_stricter_standing() {
+        assert_eq!(stricter_standing("ok", "disqualified"), "disqualified");
+        assert_eq!(stricter_standing("disqualified", "ok"), "disqualified");
+        assert_eq!(
+            stricter_standing("hors_concours", "disqualified"),
+            "disqualified"
+        );
+        assert_eq!(stricter_standing("ok", "hors_concours"), "hors_concours");
+        assert_eq!(stricter_standing("ok", "ok"), "ok");
+    }
+
+    #[test]
+    fn test_own_results_after_freeze() {
+        let frozen = vec![row("alice", "0", 40), row("bob", "0", 50)];
+        let live = vec![
+            row("alice", "0", 40),
+            row("bob", "0", 30),
+            row("bob", "1", 20),
+        ];
+        let mut entries = compute(&contest("ratio"), &problems(), &frozen);
+        overlay_own_results(&mut entries, &contest("ratio"), &problems(), &live, "bob");
+
+        // Bob sees his new lengths, but nothing is rescored or reordered
+        assert_eq!(entries[0].username, "alice");
+        let bob = entry(&entries, "bob");
+        let first = bob.problem_results[0].as_ref().unwrap();
+        assert_eq!((first.code_length, first.after_freeze), (30, true));
+        assert!(bob.problem_results[1].as_ref().unwrap().after_freeze);
+        assert_eq!(bob.problems_solved, 1);
+        assert_eq!(bob.total_score, 8000);
+        let alice = entry(&entries, "alice");
+        assert!(!alice.problem_results[0].as_ref().unwrap().after_freeze);
+    }
+
+    #[test]
+    fn test_reveal_steps() {
+        let frozen = vec![row("alice", "0", 40), participant("bob", "ok")];
+        let live = vec![
+            row("alice", "0", 40),
+            row("bob", "0", 30),
+            row("bob", "1", 20),
+        ];
+        let steps = reveal_steps(&contest("ratio"), &problems(), &frozen, &live);
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0][0].username, "alice");
+        assert_eq!(entry(&steps[0], "bob").problems_solved, 0);
+        assert_eq!(steps[1][0].username, "bob");
+        assert_eq!(entry(&steps[1], "bob").problems_solved, 1);
+        assert_eq!(entry(&steps[2], "bob").problems_solved, 2);
+        assert_eq!(steps[2].len(), 2);
+    }
+}
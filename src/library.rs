@@ -0,0 +1,171 @@
+//! The admin problem library: every loaded problem with its tags, difficulty, author and
+//! the contests that used it, filtered by a search
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{problems, registry::ProblemRegistry};
+
+/// A problem as listed in the library
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub statement: String, // Markdown, searched but not shown
+    pub tags: Vec<String>,
+    pub difficulty: Option<String>,
+    pub author: Option<String>,
+    pub uploaded: bool,
+    pub contests: Vec<(i32, String, String)>, // Contests using it, newest first: id, name, status
+}
+
+/// Search and filters from the library's query string. Empty fields match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Filter {
+    #[serde(default)]
+    pub q: String, // Words that must all appear in the ID, title, statement, tags or author
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub difficulty: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub unused: bool, // Only problems no contest has used
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let text = format!(
+            "{} {} {} {} {}",
+            entry.id,
+            entry.title,
+            entry.statement,
+            entry.tags.join(" "),
+            entry.author.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        let same = |wanted: &str, value: Option<&str>| {
+            wanted.is_empty() || value.is_some_and(|v| v.eq_ignore_ascii_case(wanted))
+        };
+
+        self.q
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| text.contains(word))
+            && (self.tag.is_empty() || entry.tags.contains(&self.tag))
+            && same(&self.difficulty, entry.difficulty.as_deref())
+            && same(&self.author, entry.author.as_deref())
+            && (!self.unused || entry.contests.is_empty())
+    }
+}
+
+/// Tags from a comma-separated list, lowercased and without duplicates
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',') {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Every loaded problem, in the registry's order
+pub async fn entries(db: &PgPool, problems: &ProblemRegistry) -> Vec<Entry> {
+    let rows: Vec<(String, i32, String, String)> = sqlx::query_as(
+        "SELECT cp.problem_id, c.id, c.name, c.status FROM contest_problems cp
+         JOIN contests c ON c.id = cp.contest_id
+         ORDER BY c.created_at DESC",
+    )
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+    let mut contests: HashMap<String, Vec<(i32, String, String)>> = HashMap::new();
+    for (problem_id, id, name, status) in rows {
+        contests
+            .entry(problem_id)
+            .or_default()
+            .push((id, name, status));
+    }
+
+    problems
+        .list()
+        .into_iter()
+        .map(|package| {
+            let problem = &package.problem;
+            let metadata = &package.metadata;
+            Entry {
+                id: problem.id.clone(),
+                title: problem.title.clone(),
+                statement: problem.statement.clone(),
+                tags: metadata.tags.clone().unwrap_or_default(),
+                difficulty: metadata.difficulty.clone(),
+                author: metadata.author.clone(),
+                uploaded: problems::is_uploaded(&problem.id),
+                contests: contests.remove(&problem.id).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Distinct values of a field across the library, sorted, for the filter menus
+pub fn values<'a>(entries: &'a [Entry], field: impl Fn(&'a Entry) -> Vec<&'a str>) -> Vec<String> {
+    let mut values: Vec<String> = entries.iter().flat_map(field).map(str::to_string).collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" Strings, math,,strings , DP "),
+            vec!["strings", "math", "dp"]
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let entry = Entry {
+            id: "7".to_string(),
+            title: "Sum of Squares".to_string(),
+            statement: "Return the sum of the squares of a list.".to_string(),
+            tags: vec!["math".to_string()],
+            difficulty: Some("Easy".to_string()),
+            author: Some("alice".to_string()),
+            ..Entry::default()
+        };
+        let filter = |q: &str, tag: &str, difficulty: &str| Filter {
+            q: q.to_string(),
+            tag: tag.to_string(),
+            difficulty: difficulty.to_string(),
+            ..Filter::default()
+        };
+
+        assert!(filter("", "", "").matches(&entry));
+        assert!(filter("SQUARES list", "math", "easy").matches(&entry));
+        assert!(filter("alice", "", "").matches(&entry));
+        assert!(!filter("squares cube", "", "").matches(&entry));
+        assert!(!filter("", "strings", "").matches(&entry));
+        assert!(!filter("", "", "hard").matches(&entry));
+
+        let mut used = entry.clone();
+        used.contests
+            .push((1, "Round 1".to_string(), "ended".to_string()));
+        let unused = Filter {
+            unused: true,
+            ..Filter::default()
+        };
+        assert!(unused.matches(&entry));
+        assert!(!unused.matches(&used));
+    }
+}
@@ -1,27 +1,44 @@
+mod access;
+mod bundle;
+mod cache;
+mod checker;
+mod counting;
+mod events;
+mod examples;
+mod export;
 mod languages;
+mod leaderboard;
+mod library;
 mod markdown;
+mod math;
 mod models;
+mod plagiarism;
 mod problems;
+mod rankings;
+mod registry;
 mod routes;
 mod runner;
+mod scoring;
 mod session;
 mod state;
+mod stats;
+mod teams;
+mod usernames;
+mod versions;
 
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
 };
-use sqlx::postgres::PgPoolOptions;
+use sqlx::{PgPool, postgres::PgPoolOptions};
 use state::AppState;
 use std::env;
 use tower_http::services::ServeDir;
 
 #[tokio::main]
 async fn main() {
-    // Generate admin token
-    let admin_token = generate_token();
-    println!("https://golf.cucats.org/auth/{admin_token}");
-
     // Connect to database
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let pool = PgPoolOptions::new()
@@ -36,6 +53,16 @@ async fn main() {
         .await
         .expect("Failed to run migrations");
 
+    // Command-line tools run against the database instead of starting the server
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(run_command(&pool, &args).await);
+    }
+
+    // Generate admin token
+    let admin_token = generate_token();
+    println!("https://golf.cucats.org/auth/{admin_token}");
+
     // Initialize state
     let state = AppState::new(pool, admin_token);
 
@@ -49,12 +76,86 @@ async fn main() {
         }
     });
 
+    // Poll problem directories so edited packages are served without a restart
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(2));
+        loop {
+            interval.tick().await;
+            state_clone.poll_problems().await;
+        }
+    });
+
     // Build router
     let app = Router::new()
         .route("/", get(routes::index))
         .route("/login", get(routes::login_page).post(routes::login_post))
         .route("/auth/{token}", get(routes::admin_auth))
         .route("/admin", get(routes::admin_dashboard))
+        .route("/admin/users", get(routes::users::admin_users))
+        .route(
+            "/admin/import",
+            get(routes::bundle::admin_import_page)
+                .post(routes::bundle::admin_import)
+                .layer(DefaultBodyLimit::max(routes::bundle::MAX_UPLOAD_BYTES)),
+        )
+        .route(
+            "/admin/problems",
+            get(routes::library::admin_problem_library)
+                .post(routes::problem_editor::admin_create_problem)
+                .layer(DefaultBodyLimit::max(
+                    routes::problem_editor::MAX_UPLOAD_BYTES,
+                )),
+        )
+        .route(
+            "/admin/problems/new",
+            get(routes::problem_editor::admin_new_problem_page),
+        )
+        .route(
+            "/admin/problems/{id}",
+            get(routes::problem_editor::admin_edit_problem_page)
+                .post(routes::problem_editor::admin_update_problem)
+                .layer(DefaultBodyLimit::max(
+                    routes::problem_editor::MAX_UPLOAD_BYTES,
+                )),
+        )
+        .route(
+            "/admin/problems/{id}/versions",
+            get(routes::problem_editor::admin_problem_versions),
+        )
+        .route(
+            "/admin/problems/{id}/versions/{version}",
+            get(routes::problem_editor::admin_problem_version),
+        )
+        .route(
+            "/admin/problems/{id}/files/{file}",
+            get(routes::problem_editor::admin_problem_file),
+        )
+        .route(
+            "/api/admin/problems/preview",
+            post(routes::problem_editor::api_preview_statement),
+        )
+        .route(
+            "/api/admin/problems/{id}/preview",
+            get(routes::library::api_problem_preview),
+        )
+        .route(
+            "/admin/seasons",
+            get(routes::rankings::admin_seasons).post(routes::rankings::admin_create_season),
+        )
+        .route(
+            "/admin/seasons/{id}",
+            post(routes::rankings::admin_update_season),
+        )
+        .route(
+            "/admin/seasons/{id}/delete",
+            post(routes::rankings::admin_delete_season),
+        )
+        .route(
+            "/admin/users/rename",
+            post(routes::users::admin_rename_user),
+        )
+        .route("/admin/users/merge", post(routes::users::admin_merge_users))
         .route(
             "/admin/contests/new",
             get(routes::admin_create_contest_page),
@@ -66,6 +167,42 @@ async fn main() {
             post(routes::admin_start_contest),
         )
         .route("/admin/contests/{id}/end", post(routes::admin_end_contest))
+        .route(
+            "/admin/contests/{id}/extend",
+            post(routes::admin_extend_contest),
+        )
+        .route(
+            "/admin/contests/{id}/settings",
+            post(routes::admin_update_settings),
+        )
+        .route(
+            "/admin/contests/{id}/recount",
+            post(routes::admin_recount_contest),
+        )
+        .route(
+            "/admin/contests/{id}/problems/{problem_id}",
+            post(routes::admin_update_contest_problem),
+        )
+        .route(
+            "/admin/contests/{id}/access",
+            post(routes::admin_update_access),
+        )
+        .route(
+            "/admin/contests/{id}/participants/remove",
+            post(routes::admin_remove_participant),
+        )
+        .route(
+            "/admin/contests/{id}/participants/standing",
+            post(routes::admin_set_standing),
+        )
+        .route(
+            "/admin/submissions/{id}/invalidate",
+            post(routes::admin_invalidate_submission),
+        )
+        .route(
+            "/admin/submissions/{id}/reproduce",
+            get(routes::reproduce::admin_reproduce_submission),
+        )
         .route(
             "/admin/contests/{id}/delete",
             post(routes::admin_delete_contest),
@@ -74,10 +211,58 @@ async fn main() {
             "/admin/contests/{id}/submissions",
             get(routes::admin_submissions),
         )
-        .route("/contest/{id}/join", get(routes::contest_join))
+        .route(
+            "/admin/contests/{id}/reveal",
+            get(routes::reveal::admin_reveal_page).post(routes::reveal::admin_reveal),
+        )
+        .route(
+            "/api/admin/contests/{id}/reveal",
+            get(routes::reveal::api_admin_reveal_steps),
+        )
+        .route(
+            "/admin/contests/{id}/bundle",
+            get(routes::bundle::admin_export_bundle),
+        )
+        .route(
+            "/admin/contests/{id}/export/standings.csv",
+            get(routes::export::export_standings_csv),
+        )
+        .route(
+            "/admin/contests/{id}/export/contest.json",
+            get(routes::export::export_contest_json),
+        )
+        .route(
+            "/admin/contests/{id}/export/archive.html",
+            get(routes::export::export_archive_html),
+        )
+        .route(
+            "/admin/contests/{id}/plagiarism",
+            get(routes::plagiarism::admin_plagiarism),
+        )
+        .route(
+            "/contest/{id}/join",
+            get(routes::contest_join).post(routes::contest_join_with_code),
+        )
+        .route("/contest/{id}/team", get(routes::teams::contest_team))
+        .route(
+            "/contest/{id}/team/create",
+            post(routes::teams::contest_create_team),
+        )
+        .route(
+            "/contest/{id}/team/join",
+            post(routes::teams::contest_join_team),
+        )
+        .route("/rankings", get(routes::rankings::all_time_rankings))
+        .route("/seasons/{id}", get(routes::rankings::season_rankings))
+        .route("/user/{name}", get(routes::profiles::user_profile))
         .route("/contest/{id}/waiting", get(routes::contest_waiting))
         .route("/contest/{id}/problems", get(routes::contest_problems))
         .route("/contest/{id}/problems/{pid}", get(routes::contest_problem))
+        .route("/problems/{pid}/images/{file}", get(routes::problem_image))
+        .route(
+            "/contest/{id}/problems/{pid}/stats",
+            get(routes::stats::problem_stats),
+        )
         .route(
             "/contest/{id}/problems/{pid}/submit",
             post(routes::contest_submit),
@@ -91,10 +276,16 @@ async fn main() {
             "/api/contest/{id}/leaderboard",
             get(routes::api_contest_leaderboard),
         )
+        .route("/api/contest/{id}/events", get(routes::api_contest_events))
         .route(
             "/api/admin/contests/{id}/submissions",
             get(routes::api_admin_submissions),
         )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            session::drop_missing_user,
+        ))
+        // Assets need no session checks
         .nest_service("/static", ServeDir::new("static"))
         .layer(session::session_layer())
         .with_state(state);
@@ -106,6 +297,45 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// `golf export-contest <id> <file> [--with-submissions]` or `golf import-contest <file>`
+async fn run_command(pool: &PgPool, args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["export-contest", id, file, flags @ ..] => {
+            let Ok(contest_id) = id.parse::<i32>() else {
+                eprintln!("Invalid contest ID: {id}");
+                return 2;
+            };
+            let with_submissions = flags.contains(&"--with-submissions");
+            bundle::export_contest(pool, contest_id, with_submissions)
+                .await
+                .and_then(|bytes| Ok(std::fs::write(file, bytes)?))
+                .map(|_| println!("Exported contest {contest_id} to {file}"))
+        }
+        ["import-contest", file] => match std::fs::read(file) {
+            Ok(bytes) => bundle::import_contest(pool, &bytes)
+                .await
+                .map(|id| println!("Imported {file} as contest {id}")),
+            Err(e) => Err(e.into()),
+        },
+        _ => {
+            eprintln!("Usage:");
+            eprintln!("  golf                  Start the server");
+            eprintln!("  golf export-contest <id> <file> [--with-submissions]");
+            eprintln!("  golf import-contest <file>");
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
 fn generate_token() -> String {
     use base64::Engine;
     use rand::Rng;
@@ -1,22 +1,183 @@
+use std::{fs, sync::LazyLock};
+
 use ammonia::Builder;
-use pulldown_cmark::{Parser, html};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd, html};
+use syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use crate::{
+    examples::{self, Examples},
+    math, problems,
+};
+
+/// Prefix of the classes given to highlighted code, styled in style.css
+const HIGHLIGHT_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
 
-/// Render markdown to HTML with sanitization
-/// Allows math (MathJax will handle $...$ and $$...$$)
+/// MathML elements produced by math::to_mathml, with their attributes
+const MATHML_TAGS: [(&str, &[&str]); 19] = [
+    ("math", &["display"]),
+    ("mrow", &[]),
+    ("mi", &["mathvariant"]),
+    ("mn", &[]),
+    ("mo", &["stretchy"]),
+    ("mtext", &[]),
+    ("mspace", &["width"]),
+    ("msub", &[]),
+    ("msup", &[]),
+    ("msubsup", &[]),
+    ("mfrac", &["linethickness"]),
+    ("msqrt", &[]),
+    ("mroot", &[]),
+    ("mover", &["accent"]),
+    ("munder", &[]),
+    ("mtable", &["columnalign"]),
+    ("mtr", &[]),
+    ("mtd", &[]),
+    ("merror", &[]),
+];
+
+/// Render markdown to HTML with sanitization.
+/// Tables, strikethrough, footnotes and task lists are enabled, `$...$` and `$$...$$` become
+/// MathML and fenced code blocks in a known language are highlighted.
 pub fn render_markdown(markdown: &str) -> String {
-    // Parse markdown to HTML
-    let parser = Parser::new(markdown);
+    render(markdown, None)
+}
+
+/// Render a problem statement, expanding `{{examples}}` and serving relative image links
+/// from the problem's directory
+pub fn render_statement(markdown: &str, problem_id: &str, examples: &Examples) -> String {
+    render(&examples::expand(markdown, examples), Some(problem_id))
+}
+
+/// Render a problem statement for a page saved on its own, with the images its relative
+/// links name embedded as data: URIs
+pub fn render_standalone_statement(
+    markdown: &str,
+    problem_id: &str,
+    examples: &Examples,
+) -> String {
+    let html = render_statement(markdown, problem_id, examples);
+    let dir = problems::problem_dir(problem_id);
+    let images = problems::list_images(problem_id)
+        .into_iter()
+        .filter_map(|name| Some((fs::read(dir.join(&name)).ok()?, name)));
+    embed_images(html, problem_id, images)
+}
+
+/// Replace links to a problem's served images with the images' contents
+fn embed_images(
+    mut html: String,
+    problem_id: &str,
+    images: impl Iterator<Item = (Vec<u8>, String)>,
+) -> String {
+    use base64::Engine;
+    for (contents, name) in images {
+        let (Some(url), Some(content_type)) =
+            (image_url(problem_id, &name), problems::image_type(&name))
+        else {
+            continue;
+        };
+        let data = base64::engine::general_purpose::STANDARD.encode(contents);
+        html = html.replace(
+            &format!("src=\"{url}\""),
+            &format!("src=\"data:{content_type};base64,{data}\""),
+        );
+    }
+    html
+}
+
+fn render(markdown: &str, problem_id: Option<&str>) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH;
+
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None; // Language and text of a fenced block
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::InlineMath(tex) => {
+                events.push(Event::InlineHtml(math::to_mathml(&tex, false).into()))
+            }
+            Event::DisplayMath(tex) => {
+                events.push(Event::InlineHtml(math::to_mathml(&tex, true).into()))
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+                if SYNTAXES.find_syntax_by_token(lang).is_some() =>
+            {
+                code = Some((lang.to_string(), String::new()));
+            }
+            Event::Text(ref text) if code.is_some() => {
+                if let Some((_, code)) = code.as_mut() {
+                    code.push_str(text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) if code.is_some() => {
+                if let Some((lang, text)) = code.take() {
+                    events.push(Event::Html(highlight(&lang, &text).into()));
+                }
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let dest_url = match problem_id {
+                    Some(problem_id) => {
+                        image_url(problem_id, &dest_url).map_or(dest_url, CowStr::from)
+                    }
+                    None => dest_url,
+                };
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            event => events.push(event),
+        }
+    }
     let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
+    html::push_html(&mut html_output, events.into_iter());
 
-    // Sanitize HTML with ammonia
-    // Allow safe tags and preserve math delimiters
-    Builder::default()
-        .add_tags(&["span", "div"]) // For math blocks
-        .add_tag_attributes("span", &["class"]) // For inline math styling
-        .add_tag_attributes("div", &["class"]) // For display math styling
-        .clean(&html_output)
-        .to_string()
+    // Sanitize HTML with ammonia, keeping the MathML and highlighting generated above
+    let mut builder = Builder::default();
+    builder
+        .add_tags(&["span", "div"])
+        .add_tag_attributes("span", &["class"])
+        .add_tag_attributes("div", &["class"]);
+    for (tag, attributes) in MATHML_TAGS {
+        builder.add_tags([tag]).add_tag_attributes(tag, attributes);
+    }
+    builder.clean(&html_output).to_string()
+}
+
+/// Where a relative image link in a problem's statement is served, if it names one of its images
+fn image_url(problem_id: &str, dest: &str) -> Option<String> {
+    let name = dest.strip_prefix("./").unwrap_or(dest);
+    problems::image_type(name)?;
+    Some(format!("/problems/{problem_id}/images/{name}"))
+}
+
+fn highlight(lang: &str, code: &str) -> String {
+    let Some(syntax) = SYNTAXES.find_syntax_by_token(lang) else {
+        return String::new();
+    };
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, HIGHLIGHT_CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        // Only fails on malformed syntax definitions, which the bundled ones are not
+        let _ = generator.parse_html_for_line_which_includes_newline(line);
+    }
+    format!("<pre><code>{}</code></pre>\n", generator.finalize())
 }
 
 #[cfg(test)]
@@ -39,10 +200,39 @@ mod tests {
     }
 
     #[test]
-    fn test_math_preservation() {
+    fn test_math_rendering() {
         let md = "Inline math: $x^2$ and display: $$\\sum_{i=1}^{n} i$$";
         let html = render_markdown(md);
-        // Math delimiters should be preserved in text content
-        assert!(html.contains("$x^2$") || html.contains("x^2"));
+        assert!(html.contains("<math display=\"inline\"><mrow><msup><mi>x</mi><mn>2</mn></msup>"));
+        assert!(html.contains("<math display=\"block\">"));
+        assert!(html.contains("<mo>∑</mo>"));
+    }
+
+    #[test]
+    fn test_extensions_and_highlighting() {
+        let md = "| a | b |\n|---|---|\n| 1 | ~~2~~ |\n\n```python\ndef f(x): return x\n```\n";
+        let html = render_markdown(md);
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<del>2</del>"));
+        assert!(html.contains("<span class=\"hl-"));
+        assert!(html.contains("def"));
+    }
+
+    #[test]
+    fn test_statement_images() {
+        let md = "![tree](./tree.png) ![x](../secret.png) ![y](https://example.com/y.png)";
+        let html = render_statement(md, "7", &Ok(Vec::new()));
+        assert!(html.contains("src=\"/problems/7/images/tree.png\""));
+        assert!(html.contains("src=\"../secret.png\""));
+        assert!(html.contains("src=\"https://example.com/y.png\""));
+    }
+
+    #[test]
+    fn test_embedded_images() {
+        let html = render_statement("![tree](tree.png) ![y](y.png)", "7", &Ok(Vec::new()));
+        let images = [(b"PNG".to_vec(), "tree.png".to_string())];
+        let html = embed_images(html, "7", images.into_iter());
+        assert!(html.contains("src=\"data:image/png;base64,UE5H\""));
+        assert!(html.contains("src=\"/problems/7/images/y.png\""));
     }
 }
@@ -0,0 +1,588 @@
+//! Rendering the LaTeX subset used in statements as MathML, so math needs no client-side
+//! script. Unknown commands are shown in an `<merror>` instead of failing the statement.
+
+/// Commands whose braced argument is taken as raw text rather than math
+const TEXT_COMMANDS: [&str; 11] = [
+    "text",
+    "textrm",
+    "textit",
+    "textbf",
+    "mbox",
+    "operatorname",
+    "mathrm",
+    "mathbb",
+    "mathbf",
+    "mathcal",
+    "mathit",
+];
+
+/// Render `tex` as a `<math>` element, displayed as a block or inline
+pub fn to_mathml(tex: &str, display: bool) -> String {
+    let mut parser = Parser {
+        tokens: tokenize(tex),
+        pos: 0,
+    };
+    let mut body = String::new();
+    while parser.peek().is_some() {
+        body.push_str(&parser.row());
+        // Stray closing braces and alignment marks outside an environment are dropped
+        parser.next();
+    }
+    format!(
+        "<math display=\"{}\"><mrow>{body}</mrow></math>",
+        if display { "block" } else { "inline" }
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Command(String), // Without the backslash
+    Text(String),    // Raw argument of a text command
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Align,  // &
+    NewRow, // \\
+    Number(String),
+    Letter(char),
+    Symbol(char),
+}
+
+fn tokenize(tex: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = tex.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '\\' => match chars.next() {
+                Some('\\') => tokens.push(Token::NewRow),
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut name = c.to_string();
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) {
+                        name.push(c);
+                        chars.next();
+                    }
+                    let is_text = TEXT_COMMANDS.contains(&name.as_str());
+                    tokens.push(Token::Command(name));
+                    if is_text {
+                        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                        if chars.next_if_eq(&'{').is_some() {
+                            let mut text = String::new();
+                            let mut depth = 0;
+                            for c in chars.by_ref() {
+                                match c {
+                                    '{' => depth += 1,
+                                    '}' if depth == 0 => break,
+                                    '}' => depth -= 1,
+                                    _ => {}
+                                }
+                                text.push(c);
+                            }
+                            tokens.push(Token::Text(text));
+                        }
+                    }
+                }
+                Some(c) => tokens.push(Token::Command(c.to_string())),
+                None => tokens.push(Token::Symbol('\\')),
+            },
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '^' => tokens.push(Token::Sup),
+            '_' => tokens.push(Token::Sub),
+            '&' => tokens.push(Token::Align),
+            '0'..='9' | '.' if c != '.' || chars.peek().is_some_and(char::is_ascii_digit) => {
+                let mut number = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    let decimal_point = c == '.'
+                        && !number.contains('.')
+                        && chars.clone().nth(1).is_some_and(|c| c.is_ascii_digit());
+                    if !c.is_ascii_digit() && !decimal_point {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() => tokens.push(Token::Letter(c)),
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    tokens
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn mo(op: &str) -> String {
+    format!("<mo>{}</mo>", escape(op))
+}
+
+fn space(width: &str) -> String {
+    format!("<mspace width=\"{width}\"></mspace>")
+}
+
+fn error(what: &str) -> String {
+    format!("<merror><mtext>{}</mtext></merror>", escape(what))
+}
+
+fn greek(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "rho" => "ρ",
+        "sigma" => "σ",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        _ => return None,
+    })
+}
+
+fn operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" | "neq" => "≠",
+        "lt" => "<",
+        "gt" => ">",
+        "times" => "×",
+        "cdot" => "⋅",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "equiv" => "≡",
+        "approx" => "≈",
+        "sim" => "∼",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "in" => "∈",
+        "notin" => "∉",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "neg" | "lnot" => "¬",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "circ" => "∘",
+        "forall" => "∀",
+        "exists" => "∃",
+        "mid" => "∣",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "sum" => "∑",
+        "prod" => "∏",
+        "int" => "∫",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lbrace" | "{" => "{",
+        "rbrace" | "}" => "}",
+        "|" => "‖",
+        "%" => "%",
+        "$" => "$",
+        "#" => "#",
+        "_" => "_",
+        "&" => "&",
+        _ => return None,
+    })
+}
+
+fn identifier(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "infty" => "∞",
+        "ell" => "ℓ",
+        "emptyset" | "varnothing" => "∅",
+        "partial" => "∂",
+        "nabla" => "∇",
+        _ => return None,
+    })
+}
+
+const FUNCTIONS: [&str; 20] = [
+    "log", "ln", "lg", "exp", "sin", "cos", "tan", "sec", "csc", "cot", "max", "min", "sup", "inf",
+    "gcd", "lcm", "lim", "det", "deg", "arg",
+];
+
+fn accent(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "hat" | "widehat" => "^",
+        "bar" | "overline" => "¯",
+        "vec" => "→",
+        "tilde" | "widetilde" => "~",
+        "dot" => "˙",
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Nodes up to the end, a closing brace, an alignment mark or the end of an environment
+    fn row(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Close | Token::Align | Token::NewRow => break,
+                Token::Command(name) if name == "end" => break,
+                _ => out.push_str(&self.scripted()),
+            }
+        }
+        out
+    }
+
+    /// An atom with any subscript and superscript
+    fn scripted(&mut self) -> String {
+        let base = self.atom();
+        let (mut sub, mut sup) = (None, None);
+        loop {
+            if sub.is_none() && self.eat(&Token::Sub) {
+                sub = Some(self.atom());
+            } else if sup.is_none() && self.eat(&Token::Sup) {
+                sup = Some(self.atom());
+            } else {
+                break;
+            }
+        }
+        match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<msub>{base}{sub}</msub>"),
+            (None, Some(sup)) => format!("<msup>{base}{sup}</msup>"),
+            (Some(sub), Some(sup)) => format!("<msubsup>{base}{sub}{sup}</msubsup>"),
+        }
+    }
+
+    fn atom(&mut self) -> String {
+        match self.next() {
+            None => "<mrow></mrow>".to_string(),
+            Some(Token::Open) => {
+                let inner = self.row();
+                self.eat(&Token::Close);
+                format!("<mrow>{inner}</mrow>")
+            }
+            Some(Token::Number(n)) => format!("<mn>{n}</mn>"),
+            Some(Token::Letter(c)) => format!("<mi>{}</mi>", escape(&c.to_string())),
+            Some(Token::Symbol('-')) => mo("−"),
+            Some(Token::Symbol('\'')) => mo("′"),
+            Some(Token::Symbol('*')) => mo("∗"),
+            Some(Token::Symbol(c)) => mo(&c.to_string()),
+            Some(Token::Text(text)) => format!("<mtext>{}</mtext>", escape(&text)),
+            Some(Token::Command(name)) => self.command(&name),
+            Some(Token::Sup | Token::Sub) => {
+                // A script with no base
+                self.pos -= 1;
+                "<mrow></mrow>".to_string()
+            }
+            Some(Token::Close | Token::Align | Token::NewRow) => String::new(),
+        }
+    }
+
+    fn text_argument(&mut self) -> String {
+        match self.peek() {
+            Some(Token::Text(_)) => match self.next() {
+                Some(Token::Text(text)) => escape(&text),
+                _ => unreachable!(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn command(&mut self, name: &str) -> String {
+        if let Some(letter) = greek(name) {
+            let variant = if name.starts_with(char::is_uppercase) {
+                " mathvariant=\"normal\""
+            } else {
+                ""
+            };
+            return format!("<mi{variant}>{letter}</mi>");
+        }
+        if let Some(op) = operator(name) {
+            return mo(op);
+        }
+        if let Some(id) = identifier(name) {
+            return format!("<mi>{id}</mi>");
+        }
+        if FUNCTIONS.contains(&name) {
+            return format!("<mi>{name}</mi>");
+        }
+        if let Some(mark) = accent(name) {
+            let base = self.atom();
+            return format!("<mover accent=\"true\">{base}{}</mover>", mo(mark));
+        }
+
+        match name {
+            "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+                format!("<mtext>{}</mtext>", self.text_argument())
+            }
+            "operatorname" => format!("<mi>{}</mi>", self.text_argument()),
+            "mathrm" | "mathbb" | "mathbf" | "mathcal" | "mathit" => {
+                let variant = match name {
+                    "mathrm" => "normal",
+                    "mathbb" => "double-struck",
+                    "mathbf" => "bold",
+                    "mathcal" => "script",
+                    _ => "italic",
+                };
+                format!(
+                    "<mi mathvariant=\"{variant}\">{}</mi>",
+                    self.text_argument()
+                )
+            }
+            "frac" | "dfrac" | "tfrac" => {
+                let (num, den) = (self.atom(), self.atom());
+                format!("<mfrac>{num}{den}</mfrac>")
+            }
+            "binom" => {
+                let (n, k) = (self.atom(), self.atom());
+                format!(
+                    "<mrow>{}<mfrac linethickness=\"0\">{n}{k}</mfrac>{}</mrow>",
+                    mo("("),
+                    mo(")")
+                )
+            }
+            "sqrt" => {
+                if self.eat(&Token::Symbol('[')) {
+                    let mut index = String::new();
+                    while self.peek().is_some() && !self.eat(&Token::Symbol(']')) {
+                        index.push_str(&self.scripted());
+                    }
+                    let radicand = self.atom();
+                    format!("<mroot>{radicand}<mrow>{index}</mrow></mroot>")
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.atom())
+                }
+            }
+            "underline" => format!("<munder>{}{}</munder>", self.atom(), mo("_")),
+            "bmod" => mo("mod"),
+            "pmod" => format!(
+                "<mrow>{}<mi>mod</mi>{}{}</mrow>",
+                mo("("),
+                self.atom(),
+                mo(")")
+            ),
+            // Delimiters are drawn as plain stretchy operators
+            "left" | "right" | "big" | "Big" | "bigl" | "bigr" | "Bigl" | "Bigr" => {
+                let delimiter = match self.next() {
+                    Some(Token::Symbol('.')) | None => return String::new(),
+                    Some(Token::Symbol(c)) => c.to_string(),
+                    Some(Token::Command(name)) => match operator(&name) {
+                        Some(op) => op.to_string(),
+                        None => return error(&format!("\\{name}")),
+                    },
+                    Some(_) => return String::new(),
+                };
+                format!("<mo stretchy=\"true\">{}</mo>", escape(&delimiter))
+            }
+            "begin" => self.environment(),
+            "," => space("0.167em"),
+            ":" | ">" => space("0.222em"),
+            ";" => space("0.278em"),
+            " " => space("0.25em"),
+            "quad" => space("1em"),
+            "qquad" => space("2em"),
+            "!" => String::new(),
+            _ => error(&format!("\\{name}")),
+        }
+    }
+
+    /// The name in braces after `\begin` or `\end`
+    fn environment_name(&mut self) -> String {
+        let mut name = String::new();
+        if self.eat(&Token::Open) {
+            while let Some(token) = self.next() {
+                match token {
+                    Token::Close => break,
+                    Token::Letter(c) | Token::Symbol(c) => name.push(c),
+                    _ => {}
+                }
+            }
+        }
+        name
+    }
+
+    fn environment(&mut self) -> String {
+        let name = self.environment_name();
+        if name == "array" && self.eat(&Token::Open) {
+            // Column alignment is ignored
+            while !matches!(self.next(), Some(Token::Close) | None) {}
+        }
+
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(format!("<mtd>{}</mtd>", self.row()));
+            match self.next() {
+                Some(Token::Align) => {}
+                Some(Token::NewRow) => rows.push(std::mem::take(&mut cells)),
+                Some(Token::Close) => {}
+                Some(Token::Command(end)) if end == "end" => {
+                    self.environment_name();
+                    break;
+                }
+                _ => break,
+            }
+        }
+        // A trailing \\ leaves an empty last row
+        if cells.len() > 1 || cells.first().is_some_and(|c| c != "<mtd></mtd>") {
+            rows.push(cells);
+        }
+
+        let align = match name.trim_end_matches('*') {
+            "cases" => " columnalign=\"left\"",
+            "align" | "aligned" => " columnalign=\"right left\"",
+            _ => "",
+        };
+        let table = format!(
+            "<mtable{align}>{}</mtable>",
+            rows.iter()
+                .map(|cells| format!("<mtr>{}</mtr>", cells.concat()))
+                .collect::<String>()
+        );
+        let (open, close) = match name.trim_end_matches('*') {
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "vmatrix" => ("|", "|"),
+            "cases" => ("{", ""),
+            _ => return table,
+        };
+        let fence = |d: &str| match d {
+            "" => String::new(),
+            d => format!("<mo stretchy=\"true\">{}</mo>", escape(d)),
+        };
+        format!("<mrow>{}{table}{}</mrow>", fence(open), fence(close))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inner(tex: &str) -> String {
+        to_mathml(tex, false)
+            .trim_start_matches("<math display=\"inline\"><mrow>")
+            .trim_end_matches("</mrow></math>")
+            .to_string()
+    }
+
+    #[test]
+    fn test_scripts_and_fractions() {
+        assert_eq!(inner("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            inner("a_{i+1}"),
+            "<msub><mi>a</mi><mrow><mi>i</mi><mo>+</mo><mn>1</mn></mrow></msub>"
+        );
+        assert_eq!(
+            inner("\\sum_{i=1}^n i"),
+            "<msubsup><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></msubsup><mi>i</mi>"
+        );
+        assert_eq!(
+            inner("\\frac{1}{2} \\le 0.5"),
+            "<mfrac><mrow><mn>1</mn></mrow><mrow><mn>2</mn></mrow></mfrac><mo>≤</mo><mn>0.5</mn>"
+        );
+        assert_eq!(inner("\\sqrt x"), "<msqrt><mi>x</mi></msqrt>");
+    }
+
+    #[test]
+    fn test_text_and_environments() {
+        assert_eq!(
+            inner("n \\text{ is odd}"),
+            "<mi>n</mi><mtext> is odd</mtext>"
+        );
+        assert_eq!(
+            inner("\\mathbb{R}"),
+            "<mi mathvariant=\"double-struck\">R</mi>"
+        );
+        assert_eq!(
+            inner("\\begin{pmatrix} 1 & 2 \\\\ 3 & 4 \\end{pmatrix}"),
+            "<mrow><mo stretchy=\"true\">(</mo><mtable>\
+             <mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr>\
+             <mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr>\
+             </mtable><mo stretchy=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_unknown_commands() {
+        assert_eq!(inner("a<b"), "<mi>a</mi><mo>&lt;</mo><mi>b</mi>");
+        assert_eq!(inner("\\text{<script>}"), "<mtext>&lt;script&gt;</mtext>");
+        assert_eq!(
+            inner("\\frobnicate"),
+            "<merror><mtext>\\frobnicate</mtext></merror>"
+        );
+        // Unbalanced input still renders
+        assert!(inner("{x^").starts_with("<mrow><msup>"));
+        assert_eq!(inner("}x"), "<mi>x</mi>");
+    }
+}
@@ -9,6 +9,7 @@ pub struct User {
     pub username: String,
     pub is_admin: bool,
     pub created_at: i64,
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +29,83 @@ pub struct Contest {
     pub start_time: Option<i64>,
     pub status: String,
     pub created_at: i64,
+    pub team_size: i32,      // 1 = individual contest
+    pub access_mode: String, // 'public', 'code', 'allowlist'
+    pub join_code: Option<String>,
+    pub scoring: String,       // Scoring system ID, see scoring::get
+    pub freeze_minutes: i32,   // 0 = never frozen
+    pub revealed: bool,        // Final standings published after a freeze
+    pub reveal_policy: String, // 'after_end' or 'never', for code and problem statistics
+    pub byte_count: String,    // Counting rule ID, see counting::ByteCount
+}
+
+impl Contest {
+    pub fn is_team_contest(&self) -> bool {
+        self.team_size > 1
+    }
+
+    /// Whether users must be registered through `/join` before taking part
+    pub fn is_restricted(&self) -> bool {
+        self.access_mode != "public"
+    }
+
+    /// When the public leaderboard stops updating, if the contest has a freeze
+    pub fn freeze_time(&self) -> Option<i64> {
+        let start = self.start_time?;
+        (self.freeze_minutes > 0)
+            .then(|| start + self.duration as i64 - self.freeze_minutes as i64 * 60)
+    }
+
+    /// Whether non-admins currently see the frozen leaderboard
+    pub fn is_frozen(&self, now: i64) -> bool {
+        !self.revealed && self.freeze_time().is_some_and(|t| now >= t)
+    }
+
+    /// Whether contestants' code and per-problem statistics may be shown to everyone
+    pub fn solutions_public(&self, now: i64) -> bool {
+        self.reveal_policy == "after_end" && self.status == "ended" && !self.is_frozen(now)
+    }
+}
+
+#[cfg(test)]
+impl Contest {
+    /// An active individual contest that started at t=1000, for tests
+    pub fn sample(id: i32, scoring: &str) -> Self {
+        Contest {
+            id,
+            name: "Test".to_string(),
+            duration: 3600,
+            start_time: Some(1000),
+            status: "active".to_string(),
+            created_at: 0,
+            team_size: 1,
+            access_mode: "public".to_string(),
+            join_code: None,
+            scoring: scoring.to_string(),
+            freeze_minutes: 0,
+            revealed: false,
+            reveal_policy: "after_end".to_string(),
+            byte_count: "raw".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Season {
+    pub id: i32,
+    pub name: String,
+    pub aggregation: String, // Aggregation rule ID, see rankings::Aggregation
+    pub best_of: i32,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Team {
+    pub id: i32,
+    pub contest_id: i32,
+    pub name: String,
+    pub invite_code: String,
+    pub created_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -35,6 +113,63 @@ pub struct ContestProblem {
     pub contest_id: i32,
     pub problem_id: String, // Filesystem identifier
     pub problem_order: i32,
+    pub alias: Option<String>, // Shown instead of the letter from its order
+    pub weight: f64,           // Multiplies the problem's points
+    pub open_minute: Option<i32>, // Minutes after the start it opens, if released late
+    pub close_minute: Option<i32>, // Minutes after the start it stops taking submissions
+}
+
+#[cfg(test)]
+impl ContestProblem {
+    /// A problem of contest 1 with default settings, for tests
+    pub fn sample(problem_id: &str, problem_order: i32) -> Self {
+        ContestProblem {
+            contest_id: 1,
+            problem_id: problem_id.to_string(),
+            problem_order,
+            alias: None,
+            weight: 1.0,
+            open_minute: None,
+            close_minute: None,
+        }
+    }
+}
+
+impl ContestProblem {
+    /// Column and list label: the alias, else A, B, ..., Z, AA, AB... by order
+    pub fn label(&self) -> String {
+        if let Some(alias) = &self.alias {
+            return alias.clone();
+        }
+        let mut n = self.problem_order.max(0) as u32 + 1;
+        let mut label = Vec::new();
+        while n > 0 {
+            n -= 1;
+            label.push(char::from_u32('A' as u32 + n % 26).unwrap_or('?'));
+            n /= 26;
+        }
+        label.iter().rev().collect()
+    }
+
+    /// When it opens, once the contest has started
+    pub fn opens_at(&self, contest: &Contest) -> Option<i64> {
+        Some(contest.start_time? + self.open_minute.unwrap_or(0) as i64 * 60)
+    }
+
+    /// When it stops taking submissions, if before the end of the contest
+    pub fn closes_at(&self, contest: &Contest) -> Option<i64> {
+        Some(contest.start_time? + self.close_minute? as i64 * 60)
+    }
+
+    /// Whether contestants may see it: from its opening on, and always once the contest is over
+    pub fn is_released(&self, contest: &Contest, now: i64) -> bool {
+        contest.status == "ended" || self.opens_at(contest).is_some_and(|t| now >= t)
+    }
+
+    /// Whether it takes submissions, as far as its own window goes
+    pub fn is_open(&self, contest: &Contest, now: i64) -> bool {
+        self.is_released(contest, now) && self.closes_at(contest).is_none_or(|t| now < t)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -48,6 +183,16 @@ pub struct Submission {
     pub time: i32,
     pub code: String,
     pub created_at: i64,
+    pub team_id: Option<i32>,
+}
+
+impl Submission {
+    pub fn generate_id() -> String {
+        use base64::Engine;
+        use rand::Rng;
+        let bytes: [u8; 16] = rand::rng().random();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
 }
 
 // View models for API responses
@@ -60,6 +205,24 @@ pub struct ProblemWithStatement {
     pub order: i32,
 }
 
+/// A submission as listed for admins
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SubmissionView {
+    pub id: String,
+    pub username: String,
+    pub problem_id: String,
+    #[sqlx(default)]
+    pub problem_title: String, // From the problem registry, not the query
+    pub verdict: String,
+    pub code_length: i32,
+    pub time: i32,
+    pub code: String,
+    pub created_at: i64,
+    pub invalidated_reason: Option<String>,
+    pub problem_version: Option<i32>, // None if judged before versioning
+    pub seed: Option<i64>,            // Of the generated tests, if any were run
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContestWithProblems {
     pub id: i32,
@@ -70,18 +233,44 @@ pub struct ContestWithProblems {
     pub problems: Vec<ProblemWithStatement>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LeaderboardEntry {
-    pub username: String,
-    pub solved: i32,
-    pub score: i32,
-    pub golds: Vec<i32>,
-    pub diamonds: Vec<i32>,
-    pub problem_scores: Vec<ProblemScore>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProblemScore {
-    pub score: i32,
-    pub verdict: String,
+    #[test]
+    fn test_problem_labels() {
+        let mut p = ContestProblem::sample("7", 0);
+        assert_eq!(p.label(), "A");
+        p.problem_order = 25;
+        assert_eq!(p.label(), "Z");
+        p.problem_order = 27;
+        assert_eq!(p.label(), "AB");
+        p.alias = Some("Bonus".to_string());
+        assert_eq!(p.label(), "Bonus");
+    }
+
+    #[test]
+    fn test_problem_window() {
+        let contest = Contest::sample(1, "ratio");
+        let mut p = ContestProblem::sample("0", 0);
+        assert!(p.is_open(&contest, 1000));
+        p.open_minute = Some(30);
+        p.close_minute = Some(45);
+        assert!(!p.is_released(&contest, 1000 + 1799));
+        assert!(p.is_open(&contest, 1000 + 1800));
+        assert!(!p.is_open(&contest, 1000 + 2700));
+        assert!(p.is_released(&contest, 1000 + 2700));
+
+        let pending = Contest {
+            start_time: None,
+            status: "pending".to_string(),
+            ..contest.clone()
+        };
+        assert!(!ContestProblem::sample("0", 0).is_released(&pending, 1000));
+        let ended = Contest {
+            status: "ended".to_string(),
+            ..contest
+        };
+        assert!(p.is_released(&ended, 0));
+    }
 }
@@ -0,0 +1,261 @@
+//! Code similarity checks for spotting copied golf solutions
+//! Submissions are reduced to a normalised token stream (identifiers renamed in order of
+//! first use, literals collapsed, comments and whitespace dropped) and compared by edit distance
+
+use std::collections::HashMap;
+
+/// An accepted submission to compare
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub submission_id: String,
+    pub owner: String, // User, or team in team contests (same-owner pairs are skipped)
+    pub username: String,
+    pub problem_id: String,
+    pub code: String,
+    pub code_length: i32,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuspiciousPair {
+    pub problem_id: String,
+    pub first: Candidate, // Submitted earlier
+    pub second: Candidate,
+    pub similarity: f64, // 0.0 - 1.0
+}
+
+impl SuspiciousPair {
+    pub fn seconds_apart(&self) -> i64 {
+        self.second.created_at - self.first.created_at
+    }
+
+    pub fn percent(&self) -> i32 {
+        (self.similarity * 100.0).round() as i32
+    }
+}
+
+/// Most submissions compared in one check, since comparing is quadratic
+pub const MAX_CANDIDATES: usize = 1000;
+
+/// Suspicious pairs found at one threshold, see `find_suspicious_pairs`
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub pairs: Vec<SuspiciousPair>,
+    pub compared: usize,
+    pub truncated: bool, // More than MAX_CANDIDATES were eligible, only the earliest compared
+}
+
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Tokenise Python source into a normalised token stream
+pub fn normalise(code: &str) -> Vec<String> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens = Vec::new();
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == '\\' {
+            i += 1;
+        } else if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '"' || c == '\'' {
+            // String literal (single, or triple quoted)
+            let triple = i + 2 < chars.len() && chars[i + 1] == c && chars[i + 2] == c;
+            i += if triple { 3 } else { 1 };
+            while i < chars.len() {
+                if chars[i] == '\\' {
+                    i += 2;
+                } else if triple {
+                    if i + 2 < chars.len() && chars[i..i + 3] == [c, c, c] {
+                        i += 3;
+                        break;
+                    }
+                    i += 1;
+                } else if chars[i] == c || chars[i] == '\n' {
+                    i += 1;
+                    break;
+                } else {
+                    i += 1;
+                }
+            }
+            tokens.push("STR".to_string());
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push("NUM".to_string());
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                tokens.push(word);
+            } else {
+                // Identifiers become v0, v1, ... so renaming variables doesn't hide a copy
+                let next = names.len();
+                let index = *names.entry(word).or_insert(next);
+                tokens.push(format!("v{index}"));
+            }
+        } else {
+            tokens.push(c.to_string());
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Similarity of two token streams: 1 - (edit distance / longer length)
+pub fn similarity(a: &[String], b: &[String]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+fn edit_distance(a: &[String], b: &[String]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ta) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, tb) in b.iter().enumerate() {
+            let cost = if ta == tb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Compare accepted submissions per problem and return owner pairs at or above `threshold`,
+/// keeping the most similar submission pair for each owner pair, most similar first
+pub fn find_suspicious_pairs(candidates: &[Candidate], threshold: f64) -> Vec<SuspiciousPair> {
+    let normalised: Vec<Vec<String>> = candidates.iter().map(|c| normalise(&c.code)).collect();
+    let mut best: HashMap<(String, String, String), SuspiciousPair> = HashMap::new();
+
+    for i in 0..candidates.len() {
+        for j in (i + 1)..candidates.len() {
+            let (a, b) = (&candidates[i], &candidates[j]);
+            if a.problem_id != b.problem_id || a.owner == b.owner {
+                continue;
+            }
+
+            let score = similarity(&normalised[i], &normalised[j]);
+            if score < threshold {
+                continue;
+            }
+
+            let (first, second) =
+                if (a.created_at, &a.submission_id) <= (b.created_at, &b.submission_id) {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+            let key = if a.owner < b.owner {
+                (a.problem_id.clone(), a.owner.clone(), b.owner.clone())
+            } else {
+                (a.problem_id.clone(), b.owner.clone(), a.owner.clone())
+            };
+
+            let replace = best.get(&key).is_none_or(|p| score > p.similarity);
+            if replace {
+                best.insert(
+                    key,
+                    SuspiciousPair {
+                        problem_id: a.problem_id.clone(),
+                        first: first.clone(),
+                        second: second.clone(),
+                        similarity: score,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut pairs: Vec<SuspiciousPair> = best.into_values().collect();
+    pairs.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(a.problem_id.cmp(&b.problem_id))
+            .then(a.first.created_at.cmp(&b.first.created_at))
+    });
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, owner: &str, problem: &str, code: &str, at: i64) -> Candidate {
+        Candidate {
+            submission_id: id.to_string(),
+            owner: owner.to_string(),
+            username: owner.to_string(),
+            problem_id: problem.to_string(),
+            code: code.to_string(),
+            code_length: code.len() as i32,
+            created_at: at,
+        }
+    }
+
+    #[test]
+    fn test_renamed_identifiers_are_identical() {
+        let a = normalise("f=lambda m,n:n and f([*zip(*m[::-1])],n-1)or m");
+        let b = normalise("f=lambda a,k:k and f([*zip(*a[::-1])],k-1)or a # mine");
+        assert_eq!(a, b);
+        assert_eq!(similarity(&a, &b), 1.0);
+    }
+
+    #[test]
+    fn test_different_solutions_are_dissimilar() {
+        let a = normalise("f=lambda m,n:n and f([*zip(*m[::-1])],n-1)or m");
+        let b = normalise(
+            "def f(m,n):\n for _ in range(n%4):m=[list(r)for r in zip(*m[::-1])]\n return m",
+        );
+        assert!(similarity(&a, &b) < 0.6);
+    }
+
+    #[test]
+    fn test_strings_and_comments() {
+        assert_eq!(normalise("print('a#b') # c"), normalise("print(\"xyz\")"));
+        assert_eq!(normalise("x='''a\n'b'''"), vec!["v0", "=", "STR"]);
+    }
+
+    #[test]
+    fn test_pairs_are_ordered_by_submission_time() {
+        let candidates = vec![
+            candidate("1", "bob", "0", "f=lambda x:x*2", 200),
+            candidate("2", "alice", "0", "f=lambda y:y*2", 100),
+            candidate("3", "carol", "0", "def f(n):\n return sum(range(n))", 150),
+            candidate("4", "alice", "1", "f=lambda y:y*2", 100),
+            candidate("5", "bob", "0", "f=lambda x:x+x", 300),
+        ];
+        let pairs = find_suspicious_pairs(&candidates, 0.9);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].first.username, "alice");
+        assert_eq!(pairs[0].second.submission_id, "1");
+        assert_eq!(pairs[0].seconds_apart(), 100);
+        assert_eq!(pairs[0].percent(), 100);
+    }
+
+    #[test]
+    fn test_same_owner_is_skipped() {
+        let candidates = vec![
+            candidate("1", "team:1", "0", "f=lambda x:x*2", 100),
+            candidate("2", "team:1", "0", "f=lambda x:x*2", 200),
+        ];
+        assert!(find_suspicious_pairs(&candidates, 0.5).is_empty());
+    }
+}
@@ -1,41 +1,484 @@
-use crate::models::Problem;
+use crate::{
+    checker::{CHECKER_FILES, Checker, CustomChecker},
+    examples::{self, Examples},
+    models::Problem,
+};
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const PROBLEMS_DIR: &str = "problems";
+const DEFAULT_DATA_DIR: &str = "data";
 const TIME_LIMIT_SECS: f64 = 1.0;
 const MEMORY_LIMIT_KB: u64 = 262144; // 256MB
 
-/// Load a problem from the filesystem
-pub fn load_problem(problem_id: &str) -> Result<Problem, std::io::Error> {
-    let problem_dir = PathBuf::from(PROBLEMS_DIR).join(problem_id);
+/// Writable directory for state that outlives a deploy (`DATA_DIR`, default `data`)
+pub fn data_dir() -> PathBuf {
+    std::env::var_os("DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// Problems created or edited through the admin UI, which shadow the bundled ones
+fn uploaded_problems_dir() -> PathBuf {
+    data_dir().join("problems")
+}
+
+/// Directory holding a problem's statement, tests and grader
+pub fn problem_dir(problem_id: &str) -> PathBuf {
+    let uploaded = writable_problem_dir(problem_id);
+    if uploaded.is_dir() {
+        uploaded
+    } else {
+        PathBuf::from(PROBLEMS_DIR).join(problem_id)
+    }
+}
+
+/// Where changes to a problem are written, whichever copy is currently served
+pub fn writable_problem_dir(problem_id: &str) -> PathBuf {
+    uploaded_problems_dir().join(problem_id)
+}
+
+/// Whether the problem has been created or edited through the admin UI
+pub fn is_uploaded(problem_id: &str) -> bool {
+    writable_problem_dir(problem_id).is_dir()
+}
+
+/// Problem IDs are directory names, so keep them to a short, path-safe alphabet
+pub fn is_valid_problem_id(problem_id: &str) -> bool {
+    !problem_id.is_empty()
+        && problem_id.len() <= 64
+        && problem_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Images a statement can show, stored next to it: extension, content type
+const IMAGE_TYPES: [(&str, &str); 6] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+];
+
+/// The content type of a statement image, if `name` is a path-safe image file name
+pub fn image_type(name: &str) -> Option<&'static str> {
+    let (stem, extension) = name.rsplit_once('.')?;
+    let safe = !stem.is_empty()
+        && !stem.starts_with('.')
+        && name.len() <= 128
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    IMAGE_TYPES
+        .iter()
+        .find(|(ext, _)| safe && ext.eq_ignore_ascii_case(extension))
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Names of the statement images in a problem's directory, sorted
+pub fn list_images(problem_id: &str) -> Vec<String> {
+    let mut images: Vec<String> = fs::read_dir(problem_dir(problem_id))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| image_type(name).is_some())
+        .collect();
+    images.sort();
+    images
+}
+
+/// Files the admin editor may replace
+pub const EDITABLE_FILES: [&str; 4] = ["statement.md", "input.txt", "output.txt", "grader.py"];
+
+/// Optional per-problem settings, see Metadata
+pub const METADATA_FILE: &str = "problem.json";
+
+/// Optional generator of random tests and the reference solution producing their answers
+pub const GENERATOR_FILES: [&str; 2] = ["generator.py", "solution.py"];
+
+/// Line separating test cases in a full-program problem's `input.txt` and `output.txt`
+pub const TEST_SEPARATOR: &str = "---";
+
+/// Settings from a problem's `problem.json`. Every field is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Byte-counting rule overriding the contest's, see counting::ByteCount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byte_count: Option<String>,
+    /// How submissions are run, see ProblemKind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Built-in checker ID, or "custom" for the problem's checker file, see checker::Checker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<String>,
+    /// Tolerance of the float checker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub float_tolerance: Option<f64>,
+    /// Library tags, lowercase, see library::parse_tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Free-form difficulty shown in the library, such as "easy"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Test cases shown by `{{examples}}` in the statement, numbered from 1, see examples.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub examples: Option<Vec<usize>>,
+}
+
+/// How submissions to a problem are run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// `grader.py` imports `f` from the submission and prints its result for each test case
+    Function,
+    /// The submission is a whole program, run once per test case with its input on stdin
+    Program,
+}
+
+impl ProblemKind {
+    pub const ALL: [ProblemKind; 2] = [ProblemKind::Function, ProblemKind::Program];
+
+    /// Look up a kind by its `problem.json` ID, falling back to `Function`
+    pub fn parse(id: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|k| k.id() == id)
+            .unwrap_or(ProblemKind::Function)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            ProblemKind::Function => "function",
+            ProblemKind::Program => "program",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProblemKind::Function => "Function f, called by grader.py",
+            ProblemKind::Program => "Full program reading stdin and writing stdout",
+        }
+    }
+
+    /// Checker used when the problem does not choose one. Function graders print one
+    /// line per test case, while programs are free to space their output as they like.
+    pub fn default_checker(&self) -> Checker {
+        match self {
+            ProblemKind::Function => Checker::Exact,
+            ProblemKind::Program => Checker::Tokens,
+        }
+    }
+
+    /// Entry in languages.rs submissions are run with
+    pub fn language_id(&self) -> &'static str {
+        match self {
+            ProblemKind::Function => "python3.11_function_f",
+            ProblemKind::Program => "python3.11_program",
+        }
+    }
+}
+
+/// Pair up the test cases of a full-program problem, which are separated by
+/// TEST_SEPARATOR lines. Inputs keep a trailing newline, as if typed.
+pub fn split_tests(input: &str, output: &str) -> Result<Vec<(String, String)>, String> {
+    let (inputs, outputs) = (split_cases(input), split_cases(output));
+    if inputs.len() != outputs.len() {
+        return Err(format!(
+            "input.txt has {} test cases but output.txt has {}",
+            inputs.len(),
+            outputs.len()
+        ));
+    }
+    Ok(inputs.into_iter().zip(outputs).collect())
+}
+
+/// The test cases of a full-program problem's file, see split_tests
+pub fn split_cases(text: &str) -> Vec<String> {
+    let mut cases = vec![String::new()];
+    for line in text.lines() {
+        if line.trim_end() == TEST_SEPARATOR {
+            cases.push(String::new());
+        } else {
+            let case = cases.last_mut().unwrap();
+            case.push_str(line);
+            case.push('\n');
+        }
+    }
+    cases
+}
+
+/// A problem's random test generator, run as `python3 generator.py <seed>` to print
+/// input in the same format as `input.txt`, and the reference solution whose output
+/// on that input is taken as the answer
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub generator: String,
+    pub solution: String,
+}
+
+/// Read a problem's `problem.json` as stored, if it has one
+fn read_metadata_file(dir: &Path) -> Result<Option<String>, std::io::Error> {
+    match fs::read_to_string(dir.join(METADATA_FILE)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_metadata(contents: &str) -> Result<Metadata, std::io::Error> {
+    serde_json::from_str(contents).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{METADATA_FILE}: {e}"),
+        )
+    })
+}
+
+/// A problem's current settings, defaults if it has no `problem.json`
+pub fn read_metadata(problem_id: &str) -> Result<Metadata, std::io::Error> {
+    match read_metadata_file(&problem_dir(problem_id))? {
+        Some(contents) => parse_metadata(&contents),
+        None => Ok(Metadata::default()),
+    }
+}
+
+/// Contents of a `problem.json` holding these settings
+pub fn metadata_json(metadata: &Metadata) -> Vec<u8> {
+    let json = serde_json::to_string_pretty(metadata).unwrap_or_default();
+    format!("{json}\n").into_bytes()
+}
+
+/// Check that a problem still loads with some of its files replaced, using a staging copy
+/// so the directory it is served from is untouched until the save is known to be good
+pub fn check_problem_files(problem_id: &str, files: &[(&str, Vec<u8>)]) -> std::io::Result<()> {
+    load_staged_package(problem_id, files, true).map(|_| ())
+}
+
+/// Load a package from the given files, on top of the problem's current ones if `merge`,
+/// in a temporary directory that is removed afterwards
+pub fn load_staged_package(
+    problem_id: &str,
+    files: &[(&str, Vec<u8>)],
+    merge: bool,
+) -> std::io::Result<Package> {
+    use rand::Rng;
+    let token: u64 = rand::rng().random();
+    let staging = std::env::temp_dir()
+        .join("golf-staging")
+        .join(format!("{problem_id}-{token:016x}"));
+    let result = stage_problem_files(problem_id, files, merge, &staging)
+        .and_then(|()| load_package(problem_id, &staging));
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn stage_problem_files(
+    problem_id: &str,
+    files: &[(&str, Vec<u8>)],
+    merge: bool,
+    staging: &Path,
+) -> std::io::Result<()> {
+    fs::create_dir_all(staging)?;
+    let current = problem_dir(problem_id);
+    if merge && current.is_dir() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), staging.join(entry.file_name()))?;
+            }
+        }
+    }
+    for (name, contents) in files {
+        fs::write(staging.join(name), contents)?;
+    }
+    Ok(())
+}
+
+/// Write some of a problem's files to the data directory. Editing a bundled
+/// problem copies it there first, so the shadowing copy is always complete.
+pub fn save_problem_files(problem_id: &str, files: &[(&str, Vec<u8>)]) -> std::io::Result<()> {
+    let dir = writable_problem_dir(problem_id);
+    if !dir.is_dir() {
+        let bundled = PathBuf::from(PROBLEMS_DIR).join(problem_id);
+        fs::create_dir_all(&dir)?;
+        if bundled.is_dir() {
+            for entry in fs::read_dir(&bundled)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    fs::copy(entry.path(), dir.join(entry.file_name()))?;
+                }
+            }
+        }
+    }
+
+    // Write then rename, so a submission being judged never reads a half-written file
+    for (name, contents) in files {
+        let temp = dir.join(format!(".{name}.tmp"));
+        fs::write(&temp, contents)?;
+        fs::rename(&temp, dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Everything a submission is judged against, read together so it is one consistent version
+pub struct Package {
+    pub problem: Problem,
+    pub grader: String, // Empty for full-program problems without one
+    pub checker: Checker,
+    pub generator: Option<Generator>,
+    pub metadata: Metadata,
+    pub metadata_json: Option<String>, // problem.json as stored, if the problem has one
+    pub hash: String,                  // See content_hash
+    pub examples: Examples,            // Empty unless the statement asks for examples
+}
+
+impl Package {
+    pub fn kind(&self) -> ProblemKind {
+        ProblemKind::parse(self.metadata.kind.as_deref().unwrap_or_default())
+    }
+}
+
+/// Load a problem from its directory with its grader and content hash
+pub fn load_package(problem_id: &str, dir: &Path) -> Result<Package, std::io::Error> {
+    let [statement, test_input, test_output, grader] = EDITABLE_FILES.map(|name| {
+        fs::read_to_string(dir.join(name))
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{name}: {e}")))
+    });
+    let metadata_json = read_metadata_file(dir)?;
+    let metadata = match &metadata_json {
+        Some(contents) => parse_metadata(contents)?,
+        None => Metadata::default(),
+    };
+    let kind = ProblemKind::parse(metadata.kind.as_deref().unwrap_or_default());
+
+    let (statement, test_input, test_output) = (statement?, test_input?, test_output?);
+    // Full programs are judged without a grader, so they need not have one
+    let grader = match grader {
+        Err(e) if kind == ProblemKind::Program && e.kind() == std::io::ErrorKind::NotFound => None,
+        grader => Some(grader?),
+    };
+    if kind == ProblemKind::Program {
+        split_tests(&test_input, &test_output)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    }
+
+    let mut custom_checker = None;
+    for file in CHECKER_FILES {
+        match fs::read(dir.join(file)) {
+            Ok(contents) => {
+                custom_checker = Some(CustomChecker { file, contents });
+                break;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(std::io::Error::new(e.kind(), format!("{file}: {e}"))),
+        }
+    }
+    let [generator, solution] =
+        GENERATOR_FILES.map(|name| match fs::read_to_string(dir.join(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(std::io::Error::new(e.kind(), format!("{name}: {e}"))),
+        });
+    let generator = match (generator?, solution?) {
+        (Some(generator), Some(solution)) => Some(Generator {
+            generator,
+            solution,
+        }),
+        (None, None) => None,
+        (Some(_), None) => return Err(missing_file("generator.py needs a reference solution.py")),
+        (None, Some(_)) => return Err(missing_file("solution.py is only used with generator.py")),
+    };
+
+    let checker = Checker::configure(
+        metadata.checker.as_deref(),
+        metadata.float_tolerance,
+        custom_checker.clone(),
+        kind.default_checker(),
+    )
+    .map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{METADATA_FILE}: {e}"),
+        )
+    })?;
 
-    let statement = fs::read_to_string(problem_dir.join("statement.md"))?;
-    let test_input = fs::read_to_string(problem_dir.join("input.txt"))?;
-    let test_output = fs::read_to_string(problem_dir.join("output.txt"))?;
+    // Optional files are only hashed when present, so adding them did not change old hashes
+    let mut files = vec![
+        ("statement.md", statement.as_bytes()),
+        ("input.txt", test_input.as_bytes()),
+        ("output.txt", test_output.as_bytes()),
+    ];
+    if let Some(contents) = &grader {
+        files.push(("grader.py", contents.as_bytes()));
+    }
+    if let Some(contents) = &metadata_json {
+        files.push((METADATA_FILE, contents.as_bytes()));
+    }
+    if let Some(custom) = &custom_checker {
+        files.push((custom.file, &custom.contents));
+    }
+    if let Some(g) = &generator {
+        files.push((GENERATOR_FILES[0], g.generator.as_bytes()));
+        files.push((GENERATOR_FILES[1], g.solution.as_bytes()));
+    }
+    let hash = content_hash(&files);
+    let (title, statement) = extract_and_remove_title(&statement);
 
-    // Extract title from markdown and remove it from statement
-    let (title, statement_without_title) = extract_and_remove_title(&statement);
+    let examples = if statement.contains(examples::DIRECTIVE) {
+        examples::collect(
+            kind,
+            grader.as_deref().unwrap_or_default(),
+            &test_input,
+            &test_output,
+            metadata.examples.as_deref(),
+        )
+    } else {
+        Ok(Vec::new())
+    };
 
-    Ok(Problem {
-        id: problem_id.to_string(),
-        title,
-        statement: statement_without_title,
-        test_input,
-        test_output,
+    Ok(Package {
+        problem: Problem {
+            id: problem_id.to_string(),
+            title,
+            statement,
+            test_input,
+            test_output,
+        },
+        grader: grader.unwrap_or_default(),
+        checker,
+        generator,
+        metadata,
+        metadata_json,
+        hash,
+        examples,
     })
 }
 
-/// Load custom grader for a problem (required)
-pub fn load_custom_grader(problem_id: &str) -> Result<String, std::io::Error> {
-    let problem_dir = PathBuf::from(PROBLEMS_DIR).join(problem_id);
-    let grader_path = problem_dir.join("grader.py");
+fn missing_file(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::NotFound, message)
+}
+
+/// SHA-256 over a problem's files, identifying a version of it. Each file is
+/// length-prefixed so moving bytes between files changes the hash.
+pub fn content_hash(files: &[(&str, &[u8])]) -> String {
+    use sha2::{Digest, Sha256};
 
-    fs::read_to_string(grader_path)
+    let mut hasher = Sha256::new();
+    for (name, contents) in files {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(contents);
+    }
+    format!("{:x}", hasher.finalize())
 }
 
 /// Extract title from markdown (first # heading) and remove it from the content
-fn extract_and_remove_title(markdown: &str) -> (String, String) {
+pub fn extract_and_remove_title(markdown: &str) -> (String, String) {
     let lines: Vec<&str> = markdown.lines().collect();
 
     for (i, line) in lines.iter().enumerate() {
@@ -66,31 +509,98 @@ pub fn get_memory_limit() -> u64 {
     MEMORY_LIMIT_KB
 }
 
-/// List all available problems (scans the problems directory)
-pub fn list_problems() -> Result<Vec<String>, std::io::Error> {
+/// List every problem directory, bundled or uploaded, whether or not it loads
+pub fn list_problem_dirs() -> Result<Vec<String>, std::io::Error> {
     let mut problem_ids = Vec::new();
 
-    for entry in fs::read_dir(PROBLEMS_DIR)? {
+    let uploaded = uploaded_problems_dir();
+    let mut dirs = vec![fs::read_dir(PROBLEMS_DIR)?];
+    if uploaded.is_dir() {
+        dirs.push(fs::read_dir(uploaded)?);
+    }
+
+    for entry in dirs.into_iter().flatten() {
         let entry = entry?;
         if entry.file_type()?.is_dir()
             && let Some(name) = entry.file_name().to_str()
+            && !problem_ids.iter().any(|id| id == name)
         {
-            // Verify this looks like a problem directory
-            let problem_dir = entry.path();
-            if problem_dir.join("statement.md").exists()
-                && problem_dir.join("input.txt").exists()
-                && problem_dir.join("output.txt").exists()
-            {
-                problem_ids.push(name.to_string());
-            }
+            problem_ids.push(name.to_string());
         }
     }
 
-    // Sort numerically if possible
-    problem_ids.sort_by(|a, b| match (a.parse::<i32>(), b.parse::<i32>()) {
+    problem_ids.sort_by(|a, b| compare_ids(a, b));
+    Ok(problem_ids)
+}
+
+/// Sort numerically if possible
+pub fn compare_ids(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<i32>(), b.parse::<i32>()) {
         (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
         _ => a.cmp(b),
-    });
+    }
+}
 
-    Ok(problem_ids)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem_ids_are_path_safe() {
+        assert!(is_valid_problem_id("8"));
+        assert!(is_valid_problem_id("nested-list_2"));
+        assert!(!is_valid_problem_id(""));
+        assert!(!is_valid_problem_id(".."));
+        assert!(!is_valid_problem_id("a/b"));
+        assert!(!is_valid_problem_id("has space"));
+        assert!(!is_valid_problem_id(&"x".repeat(65)));
+    }
+
+    #[test]
+    fn test_image_names_are_path_safe() {
+        assert_eq!(image_type("tree.png"), Some("image/png"));
+        assert_eq!(image_type("Graph-2.JPG"), Some("image/jpeg"));
+        assert_eq!(image_type("grader.py"), None);
+        assert_eq!(image_type("../tree.png"), None);
+        assert_eq!(image_type(".hidden.png"), None);
+        assert_eq!(image_type("png"), None);
+    }
+
+    #[test]
+    fn test_content_hash_covers_every_file() {
+        let hash = |input: &str, output: &str| {
+            content_hash(&[
+                ("input.txt", input.as_bytes()),
+                ("output.txt", output.as_bytes()),
+            ])
+        };
+        assert_eq!(hash("1 2", "3").len(), 64);
+        assert_eq!(hash("1 2", "3"), hash("1 2", "3"));
+        assert_ne!(hash("1 2", "3"), hash("1 2", "4"));
+        assert_ne!(hash("1 2", "3"), hash("1 2 ", ""));
+        assert_ne!(hash("1 2", "3"), hash("1 2", "3\n"));
+    }
+
+    #[test]
+    fn test_split_tests() {
+        let tests = split_tests("1 2\n---\n3 4\n", "3\n---\n7").unwrap();
+        assert_eq!(
+            tests,
+            vec![
+                ("1 2\n".to_string(), "3\n".to_string()),
+                ("3 4\n".to_string(), "7\n".to_string()),
+            ]
+        );
+        assert!(split_tests("1\n---\n2\n", "1\n").is_err());
+    }
+
+    #[test]
+    fn test_title_extraction() {
+        let (title, statement) = extract_and_remove_title("# Golf\n\nDo the thing.");
+        assert_eq!(title, "Golf");
+        assert_eq!(statement, "Do the thing.");
+
+        let (title, _) = extract_and_remove_title("No heading");
+        assert_eq!(title, "Untitled Problem");
+    }
 }
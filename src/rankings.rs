@@ -0,0 +1,216 @@
+//! Cross-contest rankings: per-contest results combined by a season's aggregation rule
+
+/// How a season combines each user's contest results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    BestN,
+    Average,
+    Placement,
+}
+
+impl Aggregation {
+    pub const ALL: [Aggregation; 4] = [
+        Aggregation::Sum,
+        Aggregation::BestN,
+        Aggregation::Average,
+        Aggregation::Placement,
+    ];
+
+    /// Look up a rule by its stored ID, falling back to `Sum`
+    pub fn parse(id: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.id() == id)
+            .unwrap_or(Aggregation::Sum)
+    }
+
+    /// Identifier stored in `seasons.aggregation`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Aggregation::Sum => "sum",
+            Aggregation::BestN => "best_n",
+            Aggregation::Average => "average",
+            Aggregation::Placement => "placement",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Sum => "Total points",
+            Aggregation::BestN => "Best N contests",
+            Aggregation::Average => "Average points",
+            Aggregation::Placement => "Placement points",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Aggregation::Sum => "Contest points added up over every contest in the season.",
+            Aggregation::BestN => {
+                "Contest points added up over each user's best N contests, so missing a week costs nothing."
+            }
+            Aggregation::Average => "Mean contest points over the contests each user took part in.",
+            Aggregation::Placement => {
+                "Each contest awards (ranked participants − rank + 1) points, so contests with different scoring systems weigh the same."
+            }
+        }
+    }
+}
+
+/// One user's ranked finish in one contest
+#[derive(Debug, Clone)]
+pub struct ContestResult {
+    pub username: String,
+    pub rank: usize,
+    pub score: i32,
+    pub field_size: usize, // Ranked entries in the contest
+}
+
+#[derive(Debug, Clone)]
+pub struct RankingEntry {
+    pub rank: usize,
+    pub username: String,
+    pub points: i64,
+    pub contests: usize, // Contests taken part in, counted or not
+    pub wins: usize,
+    pub best_rank: usize,
+}
+
+/// Combine contest results into a ranking, best first
+pub fn aggregate(
+    rule: Aggregation,
+    best_of: usize,
+    results: &[ContestResult],
+) -> Vec<RankingEntry> {
+    let mut by_user: Vec<(&str, Vec<&ContestResult>)> = Vec::new();
+    for result in results {
+        match by_user.iter_mut().find(|(u, _)| *u == result.username) {
+            Some((_, list)) => list.push(result),
+            None => by_user.push((&result.username, vec![result])),
+        }
+    }
+
+    let mut entries: Vec<RankingEntry> = by_user
+        .into_iter()
+        .map(|(username, list)| {
+            let mut points: Vec<i64> = list
+                .iter()
+                .map(|r| match rule {
+                    Aggregation::Placement => (r.field_size + 1).saturating_sub(r.rank) as i64,
+                    _ => r.score as i64,
+                })
+                .collect();
+            points.sort_unstable_by(|a, b| b.cmp(a));
+
+            let total = match rule {
+                Aggregation::BestN if best_of > 0 => points.iter().take(best_of).sum(),
+                Aggregation::Average => points.iter().sum::<i64>() / points.len() as i64,
+                _ => points.iter().sum(),
+            };
+
+            RankingEntry {
+                rank: 0,
+                username: username.to_string(),
+                points: total,
+                contests: list.len(),
+                wins: list.iter().filter(|r| r.rank == 1).count(),
+                best_rank: list.iter().map(|r| r.rank).min().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then(b.wins.cmp(&a.wins))
+            .then(a.username.cmp(&b.username))
+    });
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.rank = i + 1;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(username: &str, rank: usize, score: i32) -> ContestResult {
+        ContestResult {
+            username: username.to_string(),
+            rank,
+            score,
+            field_size: 3,
+        }
+    }
+
+    fn results() -> Vec<ContestResult> {
+        vec![
+            result("alice", 1, 20000),
+            result("bob", 2, 15000),
+            result("carol", 3, 5000),
+            result("bob", 1, 18000),
+            result("carol", 2, 9000),
+            result("carol", 1, 10000),
+        ]
+    }
+
+    fn summary(entries: &[RankingEntry]) -> Vec<(&str, i64)> {
+        entries
+            .iter()
+            .map(|e| (e.username.as_str(), e.points))
+            .collect()
+    }
+
+    #[test]
+    fn test_sum() {
+        let entries = aggregate(Aggregation::Sum, 0, &results());
+        assert_eq!(
+            summary(&entries),
+            vec![("bob", 33000), ("carol", 24000), ("alice", 20000)]
+        );
+        assert_eq!(entries[0].rank, 1);
+        assert_eq!((entries[1].contests, entries[1].wins), (3, 1));
+        assert_eq!(entries[1].best_rank, 1);
+    }
+
+    #[test]
+    fn test_best_n_and_average() {
+        let entries = aggregate(Aggregation::BestN, 1, &results());
+        assert_eq!(
+            summary(&entries),
+            vec![("alice", 20000), ("bob", 18000), ("carol", 10000)]
+        );
+
+        let entries = aggregate(Aggregation::Average, 0, &results());
+        assert_eq!(
+            summary(&entries),
+            vec![("alice", 20000), ("bob", 16500), ("carol", 8000)]
+        );
+    }
+
+    #[test]
+    fn test_placement_ties_broken_by_wins() {
+        // Placement: alice 3, bob 2 + 3, carol 1 + 2 + 3
+        let entries = aggregate(Aggregation::Placement, 0, &results());
+        assert_eq!(
+            summary(&entries),
+            vec![("carol", 6), ("bob", 5), ("alice", 3)]
+        );
+
+        let tied = vec![
+            result("alice", 2, 0),
+            result("bob", 1, 0),
+            result("alice", 3, 0),
+        ];
+        let entries = aggregate(Aggregation::Placement, 0, &tied);
+        assert_eq!(summary(&entries), vec![("bob", 3), ("alice", 3)]);
+    }
+
+    #[test]
+    fn test_parse_falls_back_to_sum() {
+        assert_eq!(Aggregation::parse("best_n"), Aggregation::BestN);
+        assert_eq!(Aggregation::parse("unknown"), Aggregation::Sum);
+    }
+}
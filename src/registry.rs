@@ -0,0 +1,277 @@
+//! In-memory registry of problem packages
+//! Every problem is loaded at startup and served from memory. A background task polls
+//! the problem directories and reloads a package once its files have stopped changing;
+//! a package that fails to load is logged and its last good version keeps being served
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use crate::{
+    checker::CHECKER_FILES,
+    problems::{self, EDITABLE_FILES, METADATA_FILE, Package},
+};
+
+/// Modification time and size of each package file, to spot changes without reading them
+type Fingerprint = Vec<Option<(SystemTime, u64)>>;
+
+fn fingerprint(dir: &Path) -> Fingerprint {
+    EDITABLE_FILES
+        .iter()
+        .chain([&METADATA_FILE])
+        .chain(CHECKER_FILES.iter())
+        .chain(problems::GENERATOR_FILES.iter())
+        .map(|name| {
+            let meta = fs::metadata(dir.join(name)).ok()?;
+            Some((meta.modified().ok()?, meta.len()))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Entry {
+    package: Option<Arc<Package>>, // Last version that loaded cleanly
+    loaded: Fingerprint,           // Files as of the last load attempt
+    seen: Fingerprint,             // Files as of the last poll, so edits can settle first
+    error: Option<String>,         // Why the current files failed to load
+}
+
+#[derive(Default)]
+pub struct ProblemRegistry {
+    entries: RwLock<HashMap<String, Entry>>,
+    root: Option<PathBuf>, // Serve only the problems in this directory, for tests
+}
+
+impl ProblemRegistry {
+    /// Load every problem now
+    pub fn load() -> Self {
+        let registry = Self::default();
+        match registry.problem_ids() {
+            Ok(problem_ids) => {
+                for problem_id in problem_ids {
+                    let _ = registry.reload(&problem_id);
+                }
+            }
+            Err(e) => eprintln!("Could not list problems: {e}"),
+        }
+        registry
+    }
+
+    fn problem_dir(&self, problem_id: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(problem_id),
+            None => problems::problem_dir(problem_id),
+        }
+    }
+
+    fn problem_ids(&self) -> std::io::Result<Vec<String>> {
+        let Some(root) = &self.root else {
+            return problems::list_problem_dirs();
+        };
+        Ok(fs::read_dir(root)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect())
+    }
+
+    /// Current version of a problem
+    pub fn get(&self, problem_id: &str) -> Option<Arc<Package>> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(problem_id)
+            .and_then(|e| e.package.clone())
+    }
+
+    /// Title of a problem, or a placeholder if it does not exist
+    pub fn title(&self, problem_id: &str) -> String {
+        self.get(problem_id)
+            .map(|p| p.problem.title.clone())
+            .unwrap_or_else(|| format!("Problem {problem_id}"))
+    }
+
+    /// Every loadable problem, sorted by ID
+    pub fn list(&self) -> Vec<Arc<Package>> {
+        let mut packages: Vec<_> = self
+            .entries
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|e| e.package.clone())
+            .collect();
+        packages.sort_by(|a, b| problems::compare_ids(&a.problem.id, &b.problem.id));
+        packages
+    }
+
+    /// Problems whose files currently fail to load, with the error
+    pub fn errors(&self) -> Vec<(String, String)> {
+        let mut errors: Vec<_> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, e)| Some((id.clone(), e.error.clone()?)))
+            .collect();
+        errors.sort_by(|a, b| problems::compare_ids(&a.0, &b.0));
+        errors
+    }
+
+    /// Load a problem from disk right away, e.g. after saving it from the admin UI
+    pub fn reload(&self, problem_id: &str) -> Result<(), String> {
+        let dir = self.problem_dir(problem_id);
+        let fingerprint = fingerprint(&dir);
+        let result = problems::load_package(problem_id, &dir).map_err(|e| e.to_string());
+
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries.entry(problem_id.to_string()).or_default();
+        entry.loaded = fingerprint.clone();
+        entry.seen = fingerprint;
+        match result {
+            Ok(package) => {
+                entry.package = Some(Arc::new(package));
+                entry.error = None;
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!(
+                    "Problem {problem_id} failed to load, keeping the last good version: {e}"
+                );
+                entry.error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    /// Reload problems whose files changed and have been stable since the last poll,
+    /// returning the IDs of problems that were added, updated or removed
+    pub fn poll(&self) -> Vec<String> {
+        // A listing that fails says nothing about which problems exist, so none are dropped
+        let mut problem_ids = match self.problem_ids() {
+            Ok(problem_ids) => problem_ids,
+            Err(e) => {
+                eprintln!("Could not list problems, keeping the loaded ones: {e}");
+                return Vec::new();
+            }
+        };
+        let known: Vec<String> = self.entries.read().unwrap().keys().cloned().collect();
+        let removed: Vec<String> = known
+            .into_iter()
+            .filter(|id| !problem_ids.contains(id))
+            .collect();
+
+        let mut changed = Vec::new();
+        if !removed.is_empty() {
+            let mut entries = self.entries.write().unwrap();
+            for problem_id in removed {
+                entries.remove(&problem_id);
+                changed.push(problem_id);
+            }
+        }
+
+        problem_ids.retain(|problem_id| {
+            let current = fingerprint(&self.problem_dir(problem_id));
+            let mut entries = self.entries.write().unwrap();
+            let entry = entries.entry(problem_id.clone()).or_default();
+            if current == entry.loaded {
+                return false;
+            }
+            // Wait for a poll with no further changes, so a half-copied package is not loaded
+            let settled = current == entry.seen;
+            entry.seen = current;
+            settled
+        });
+        for problem_id in problem_ids {
+            if self.reload(&problem_id).is_ok() {
+                changed.push(problem_id);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_problem(root: &Path, problem_id: &str, title: &str) {
+        let dir = root.join(problem_id);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in [
+            ("statement.md", format!("# {title}\n\nPrint the input.\n")),
+            ("input.txt", "1\n".to_string()),
+            ("output.txt", "1\n".to_string()),
+            ("grader.py", "print(solution(input()))\n".to_string()),
+        ] {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reload_waits_for_changes_to_settle_and_keeps_last_good_version() {
+        let root = std::env::temp_dir().join(format!("golf-registry-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        write_problem(&root, "0", "First");
+
+        let registry = ProblemRegistry {
+            root: Some(root.clone()),
+            ..Default::default()
+        };
+        assert!(registry.poll().is_empty());
+        assert_eq!(registry.poll(), vec!["0".to_string()]);
+        assert_eq!(registry.title("0"), "First");
+
+        // An edit is only picked up once a poll sees no further change
+        write_problem(&root, "0", "Second title");
+        assert!(registry.poll().is_empty());
+        assert_eq!(registry.title("0"), "First");
+        assert_eq!(registry.poll(), vec!["0".to_string()]);
+        assert_eq!(registry.title("0"), "Second title");
+
+        // A broken package is reported, and the last good version is still served
+        fs::write(root.join("0").join(METADATA_FILE), "{ not json").unwrap();
+        registry.poll();
+        assert!(registry.poll().is_empty());
+        assert_eq!(registry.title("0"), "Second title");
+        assert_eq!(registry.errors().len(), 1);
+
+        fs::remove_file(root.join("0").join(METADATA_FILE)).unwrap();
+        registry.poll();
+        assert_eq!(registry.poll(), vec!["0".to_string()]);
+        assert!(registry.errors().is_empty());
+
+        fs::remove_dir_all(root.join("0")).unwrap();
+        assert_eq!(registry.poll(), vec!["0".to_string()]);
+        assert!(registry.get("0").is_none());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_listing_failure_keeps_loaded_problems() {
+        let root = std::env::temp_dir().join(format!("golf-registry-list-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        write_problem(&root, "0", "First");
+
+        let registry = ProblemRegistry {
+            root: Some(root.clone()),
+            ..Default::default()
+        };
+        registry.reload("0").unwrap();
+
+        // The problem directory cannot be listed, e.g. while a volume is remounted
+        let moved = root.with_extension("moved");
+        fs::rename(&root, &moved).unwrap();
+        assert!(registry.poll().is_empty());
+        assert_eq!(registry.title("0"), "First");
+        assert_eq!(registry.list().len(), 1);
+
+        fs::rename(&moved, &root).unwrap();
+        assert!(registry.poll().is_empty());
+        assert_eq!(registry.title("0"), "First");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,111 @@
+use askama::Template;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    bundle::{self, BundleError},
+    session,
+    state::AppState,
+};
+
+/// Largest bundle accepted by the upload page
+pub const MAX_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct BundleQuery {
+    #[serde(default)]
+    submissions: bool,
+}
+
+pub async fn admin_export_bundle(
+    Path(contest_id): Path<i32>,
+    Query(query): Query<BundleQuery>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match bundle::export_contest(&state.db, contest_id, query.submissions).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/gzip".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"contest-{contest_id}.golf.tar.gz\""),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(BundleError::NotFound) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/import.html")]
+struct ImportTemplate {
+    max_upload_mb: usize,
+}
+
+pub async fn admin_import_page(session: Session) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let template = ImportTemplate {
+        max_upload_mb: MAX_UPLOAD_BYTES / (1024 * 1024),
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Import an uploaded bundle, sent as the raw request body
+pub async fn admin_import(
+    State(state): State<AppState>,
+    session: Session,
+    body: Bytes,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match bundle::import_contest(&state.db, &body).await {
+        Ok(contest_id) => {
+            // Serve newly installed problems now rather than after the next poll
+            let problem_ids: Vec<String> =
+                sqlx::query_scalar("SELECT problem_id FROM contest_problems WHERE contest_id = $1")
+                    .bind(contest_id)
+                    .fetch_all(&state.db)
+                    .await
+                    .unwrap_or_default();
+            for problem_id in problem_ids {
+                if state.problems.get(&problem_id).is_none() {
+                    let _ = state.problem_changed(&problem_id);
+                }
+            }
+            Redirect::to(&format!("/admin/contests/{contest_id}")).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,266 @@
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tower_sessions::Session;
+
+use crate::{
+    export,
+    leaderboard::{LeaderboardEntry, Snapshot},
+    markdown,
+    models::Contest,
+    session,
+    state::AppState,
+};
+
+/// Final standings of an ended contest, or the response to send instead
+async fn final_standings(
+    state: &AppState,
+    session: &Session,
+    contest_id: i32,
+) -> Result<(Contest, Snapshot), Response> {
+    // Check admin
+    if !session::get_user(session).await.is_some_and(|u| u.is_admin) {
+        return Err(StatusCode::UNAUTHORIZED.into_response());
+    }
+
+    let standings = match state.standings(contest_id).await {
+        Some(s) if s.contest.status == "ended" => s,
+        Some(_) => {
+            return Err(
+                (StatusCode::CONFLICT, "Only ended contests can be exported").into_response(),
+            );
+        }
+        None => return Err(StatusCode::NOT_FOUND.into_response()),
+    };
+    Ok((standings.contest.clone(), standings.view(false, None)))
+}
+
+fn attachment(content_type: &str, filename: String, body: impl IntoResponse) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Best accepted submission of every entry (user or team) on every problem
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+struct BestSolution {
+    entity: String,
+    author: String,
+    problem_id: String,
+    code_length: i32,
+    code: String,
+    created_at: i64,
+}
+
+async fn best_solutions(
+    state: &AppState,
+    snapshot: &Snapshot,
+    contest_id: i32,
+) -> Vec<BestSolution> {
+    let solutions = sqlx::query_as::<_, BestSolution>(
+        "SELECT DISTINCT ON (COALESCE(t.name, s.username), s.problem_id)
+                COALESCE(t.name, s.username) AS entity, s.username AS author,
+                s.problem_id, s.code_length, s.code, s.created_at
+         FROM submissions s
+         LEFT JOIN teams t ON t.id = s.team_id
+         WHERE s.contest_id = $1 AND s.verdict = 'AC' AND s.invalidated_reason IS NULL
+         ORDER BY COALESCE(t.name, s.username), s.problem_id, s.code_length, s.created_at",
+    )
+    .bind(contest_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    // Disqualified entries are left out of the standings, so leave their code out too
+    solutions
+        .into_iter()
+        .filter(|s| snapshot.entries.iter().any(|e| e.username == s.entity))
+        .collect()
+}
+
+pub async fn export_standings_csv(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> Response {
+    let (_, snapshot) = match final_standings(&state, &session, contest_id).await {
+        Ok(s) => s,
+        Err(response) => return response,
+    };
+
+    attachment(
+        "text/csv; charset=utf-8",
+        format!("contest-{contest_id}-standings.csv"),
+        export::standings_csv(&snapshot),
+    )
+}
+
+#[derive(Serialize)]
+struct ExportProblem {
+    id: String,
+    label: String,
+    title: String,
+    order: usize,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+struct ExportParticipant {
+    username: String,
+    joined_at: i64,
+    standing: String,
+    team: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ContestDump {
+    contest: Contest,
+    problems: Vec<ExportProblem>,
+    participants: Vec<ExportParticipant>,
+    standings: Vec<LeaderboardEntry>,
+    best_submissions: Vec<BestSolution>,
+}
+
+pub async fn export_contest_json(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> Response {
+    let (contest, snapshot) = match final_standings(&state, &session, contest_id).await {
+        Ok(s) => s,
+        Err(response) => return response,
+    };
+
+    let participants = sqlx::query_as::<_, ExportParticipant>(
+        "SELECT cp.username, cp.joined_at::BIGINT AS joined_at, cp.standing, t.name AS team
+         FROM contest_participants cp
+         LEFT JOIN team_members m ON m.contest_id = cp.contest_id AND m.username = cp.username
+         LEFT JOIN teams t ON t.id = m.team_id
+         WHERE cp.contest_id = $1
+         ORDER BY cp.joined_at, cp.username",
+    )
+    .bind(contest_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let best_submissions = best_solutions(&state, &snapshot, contest_id).await;
+    let problems = snapshot
+        .problem_ids
+        .iter()
+        .zip(&snapshot.problem_titles)
+        .zip(&snapshot.problem_labels)
+        .enumerate()
+        .map(|(order, ((id, title), label))| ExportProblem {
+            id: id.clone(),
+            label: label.clone(),
+            title: title.clone(),
+            order,
+        })
+        .collect();
+
+    attachment(
+        "application/json",
+        format!("contest-{contest_id}.json"),
+        Json(ContestDump {
+            contest,
+            problems,
+            participants,
+            standings: snapshot.entries,
+            best_submissions,
+        }),
+    )
+}
+
+struct ArchiveProblem {
+    label: String,
+    title: String,
+    statement: String,            // Rendered markdown
+    solutions: Vec<BestSolution>, // Shortest first
+}
+
+#[derive(Template)]
+#[template(path = "export/archive.html")]
+struct ArchiveTemplate {
+    contest: Contest,
+    started: Option<String>, // Formatted here since the archive has no scripts
+    snapshot: Snapshot,
+    problems: Vec<ArchiveProblem>,
+    solutions_public: bool,
+}
+
+pub async fn export_archive_html(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> Response {
+    let (contest, snapshot) = match final_standings(&state, &session, contest_id).await {
+        Ok(s) => s,
+        Err(response) => return response,
+    };
+
+    // Solutions are only archived when the contest's reveal policy makes them public
+    let now = chrono::Utc::now().timestamp();
+    let solutions_public = contest.solutions_public(now);
+    let mut solutions = if solutions_public {
+        best_solutions(&state, &snapshot, contest_id).await
+    } else {
+        Vec::new()
+    };
+    solutions.sort_by(|a, b| {
+        a.code_length
+            .cmp(&b.code_length)
+            .then(a.created_at.cmp(&b.created_at))
+    });
+
+    let problems = snapshot
+        .problem_ids
+        .iter()
+        .zip(&snapshot.problem_titles)
+        .zip(&snapshot.problem_labels)
+        .map(|((id, title), label)| ArchiveProblem {
+            label: label.clone(),
+            title: title.clone(),
+            statement: state
+                .problems
+                .get(id)
+                .map(|p| {
+                    markdown::render_standalone_statement(&p.problem.statement, id, &p.examples)
+                })
+                .unwrap_or_default(),
+            solutions: solutions
+                .iter()
+                .filter(|s| &s.problem_id == id)
+                .cloned()
+                .collect(),
+        })
+        .collect();
+
+    let template = ArchiveTemplate {
+        started: contest
+            .start_time
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string()),
+        contest,
+        snapshot,
+        problems,
+        solutions_public,
+    };
+    attachment(
+        "text/html; charset=utf-8",
+        format!("contest-{contest_id}.html"),
+        template.render().unwrap(),
+    )
+}
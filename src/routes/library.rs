@@ -0,0 +1,83 @@
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+};
+use tower_sessions::Session;
+
+use crate::{
+    library::{self, Entry, Filter},
+    markdown,
+    routes::problem_editor::Preview,
+    session,
+    state::AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/problems.html")]
+struct LibraryTemplate {
+    problems: Vec<Entry>, // Those matching the filter
+    total: usize,
+    filter: Filter,
+    tags: Vec<String>, // Every value in the library, for the filter menus
+    difficulties: Vec<String>,
+    authors: Vec<String>,
+    errors: Vec<(String, String)>, // Packages that fail to load: ID, error
+}
+
+pub async fn admin_problem_library(
+    State(state): State<AppState>,
+    session: Session,
+    Query(filter): Query<Filter>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let entries = library::entries(&state.db, &state.problems).await;
+    let template = LibraryTemplate {
+        total: entries.len(),
+        tags: library::values(&entries, |e| e.tags.iter().map(String::as_str).collect()),
+        difficulties: library::values(&entries, |e| e.difficulty.as_deref().into_iter().collect()),
+        authors: library::values(&entries, |e| e.author.as_deref().into_iter().collect()),
+        problems: entries.into_iter().filter(|e| filter.matches(e)).collect(),
+        filter,
+        errors: state.problems.errors(),
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Render a problem's current statement, for previews in the library
+pub async fn api_problem_preview(
+    Path(problem_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match state.problems.get(&problem_id) {
+        Some(package) => Json(Preview {
+            title: package.problem.title.clone(),
+            html: markdown::render_statement(
+                &package.problem.statement,
+                &problem_id,
+                &package.examples,
+            ),
+        })
+        .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
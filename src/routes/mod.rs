@@ -2,35 +2,47 @@ use askama::Template;
 use axum::{
     Form,
     body::Bytes,
-    extract::{Path, Query, State},
-    response::{Html, IntoResponse, Redirect},
+    extract::{Path, Query, RawQuery, State},
+    http::{StatusCode, header},
+    response::{
+        Html, IntoResponse, Redirect,
+        sse::{KeepAlive, Sse},
+    },
 };
 use serde::Deserialize;
 use tower_sessions::Session;
 
 use crate::{
+    access,
+    cache::{CacheStats, CachedStandings},
+    counting::{self, ByteCount},
+    languages::Language,
+    leaderboard::{self, LeaderboardEntry},
     markdown,
-    models::{Contest, Problem},
+    models::{Contest, ContestProblem, Problem, Submission, SubmissionView},
     problems,
     runner::{CodeRunner, get_free_box_id},
+    scoring::{self, ScoringSystem},
     session,
     state::AppState,
+    usernames::{self, UsernameError},
+    versions,
 };
 
-// Type aliases for complex types
-type SubmissionRawTuple = (String, String, String, String, i32, i32, String, i64);
-type UserDataMap = std::collections::HashMap<
-    String,
-    (
-        i32,
-        i64,
-        i64,
-        i32,
-        i32,
-        std::collections::HashMap<String, UserProblemResult>,
-    ),
->;
+pub mod bundle;
+pub mod export;
+pub mod library;
+pub mod plagiarism;
+pub mod problem_editor;
+pub mod profiles;
+pub mod rankings;
+pub mod reproduce;
+pub mod reveal;
+pub mod stats;
+pub mod teams;
+pub mod users;
 
+// Type aliases for complex types
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
@@ -86,6 +98,7 @@ pub async fn login_page(Query(query): Query<LoginQuery>, session: Session) -> im
 #[derive(Deserialize)]
 pub struct LoginForm {
     username: String,
+    email: Option<String>,
 }
 
 pub async fn login_post(
@@ -95,21 +108,28 @@ pub async fn login_post(
 ) -> impl IntoResponse {
     let username = form.username.trim();
 
-    if username.is_empty() {
+    if let Err(e) = check_new_username(&state, username).await {
         let template = LoginTemplate {
-            error: Some("Username is required".to_string()),
+            error: Some(e.to_string()),
         };
         return Html(template.render().unwrap()).into_response();
     }
 
     let now = chrono::Utc::now().timestamp();
 
-    // Insert or get existing user
+    // Insert or get existing user. The email is only kept for new accounts, so logging in
+    // as someone else cannot replace theirs
+    let email = form
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
     let _ = sqlx::query(
-        "INSERT INTO users (username, is_admin, created_at) VALUES ($1, FALSE, $2) ON CONFLICT (username) DO NOTHING"
+        "INSERT INTO users (username, is_admin, created_at, email) VALUES ($1, FALSE, $2, $3) ON CONFLICT (username) DO NOTHING"
     )
     .bind(username)
     .bind(now)
+    .bind(email)
     .execute(&state.db)
     .await;
 
@@ -129,6 +149,34 @@ pub async fn login_post(
     Redirect::to(&redirect).into_response()
 }
 
+/// Validate a username for login: existing users may always log back in (unless the
+/// name is reserved), new names must pass validation and not look like an existing name
+async fn check_new_username(state: &AppState, username: &str) -> Result<(), UsernameError> {
+    if username.is_empty() {
+        return Err(UsernameError::Empty);
+    }
+    if usernames::is_reserved(username) {
+        return Err(UsernameError::Reserved);
+    }
+
+    let existing: Vec<String> = sqlx::query_scalar("SELECT username FROM users")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+
+    if existing.iter().any(|name| name == username) {
+        return Ok(());
+    }
+
+    usernames::validate(username)?;
+
+    if let Some(other) = usernames::find_confusable(username, existing.iter().map(String::as_str)) {
+        return Err(UsernameError::Confusable(other.to_string()));
+    }
+
+    Ok(())
+}
+
 // Admin routes
 
 pub async fn admin_auth(
@@ -159,6 +207,7 @@ struct ContestWithCount {
 #[template(path = "admin/dashboard.html")]
 struct AdminDashboardTemplate {
     contests: Vec<ContestWithCount>,
+    cache_stats: CacheStats,
 }
 
 pub async fn admin_dashboard(State(state): State<AppState>, session: Session) -> impl IntoResponse {
@@ -186,20 +235,27 @@ pub async fn admin_dashboard(State(state): State<AppState>, session: Session) ->
     .await
     .unwrap_or_default();
 
-    let template = AdminDashboardTemplate { contests };
+    let template = AdminDashboardTemplate {
+        contests,
+        cache_stats: state.standings_cache.stats(),
+    };
     Html(template.render().unwrap()).into_response()
 }
 
 #[derive(Template)]
 #[template(path = "admin/create_contest.html")]
 struct CreateContestTemplate {
-    problems: Vec<Problem>,
+    problems: Vec<crate::library::Entry>, // Problems picked in the library first, in their order
+    selected: Vec<String>,
+    scoring_systems: &'static [&'static dyn ScoringSystem],
+    byte_counts: [ByteCount; 4],
     error: Option<String>,
 }
 
 pub async fn admin_create_contest_page(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     session: Session,
+    RawQuery(query): RawQuery,
 ) -> impl IntoResponse {
     // Check admin
     if let Some(user) = session::get_user(&session).await {
@@ -210,17 +266,30 @@ pub async fn admin_create_contest_page(
         return Redirect::to("/login").into_response();
     }
 
-    // Load problems from filesystem
-    let problem_ids = problems::list_problems().unwrap_or_default();
-    let mut problems = Vec::new();
-    for id in problem_ids {
-        if let Ok(problem) = problems::load_problem(&id) {
-            problems.push(problem);
+    // The library links here with its selection as repeated `problems` parameters
+    let mut selected: Vec<String> = Vec::new();
+    for pair in query.as_deref().unwrap_or_default().split('&') {
+        if let Some(("problems", value)) = pair.split_once('=')
+            && let Ok(id) = urlencoding::decode(&value.replace('+', " "))
+            && !selected.contains(&id.to_string())
+        {
+            selected.push(id.into_owned());
         }
     }
 
+    let mut problems = crate::library::entries(&state.db, &state.problems).await;
+    problems.sort_by_key(|p| {
+        selected
+            .iter()
+            .position(|id| *id == p.id)
+            .unwrap_or(selected.len())
+    });
+
     let template = CreateContestTemplate {
         problems,
+        selected,
+        scoring_systems: scoring::all(),
+        byte_counts: ByteCount::ALL,
         error: None,
     };
     Html(template.render().unwrap()).into_response()
@@ -244,6 +313,10 @@ pub async fn admin_create_contest(
     let form_str = String::from_utf8_lossy(&body);
     let mut name = String::new();
     let mut duration = 0i32;
+    let mut team_size = 1i32;
+    let mut scoring_id = String::new();
+    let mut freeze_minutes = 0i32;
+    let mut byte_count = ByteCount::Raw;
     let mut problems = Vec::new();
 
     for pair in form_str.split('&') {
@@ -257,6 +330,10 @@ pub async fn admin_create_contest(
             match key.as_ref() {
                 "name" => name = value.to_string(),
                 "duration" => duration = value.parse().unwrap_or(60),
+                "team_size" => team_size = value.parse().unwrap_or(1),
+                "scoring" => scoring_id = value.to_string(),
+                "freeze_minutes" => freeze_minutes = value.parse().unwrap_or(0),
+                "byte_count" => byte_count = ByteCount::parse(&value),
                 "problems" => problems.push(value.to_string()),
                 _ => {}
             }
@@ -268,11 +345,15 @@ pub async fn admin_create_contest(
 
     // Insert contest
     let contest_id: i32 = sqlx::query_scalar(
-        "INSERT INTO contests (name, duration, status, created_at) VALUES ($1, $2, 'pending', $3) RETURNING id"
+        "INSERT INTO contests (name, duration, status, created_at, team_size, scoring, freeze_minutes, byte_count) VALUES ($1, $2, 'pending', $3, $4, $5, $6, $7) RETURNING id"
     )
     .bind(&name)
     .bind(duration_seconds)
     .bind(now)
+    .bind(team_size.max(1))
+    .bind(scoring::get(&scoring_id).id())
+    .bind(freeze_minutes.max(0))
+    .bind(byte_count.id())
     .fetch_one(&state.db)
     .await
     .unwrap();
@@ -328,6 +409,32 @@ pub async fn admin_end_contest(
     Redirect::to("/admin")
 }
 
+#[derive(Deserialize)]
+pub struct ExtendForm {
+    minutes: i32,
+}
+
+pub async fn admin_extend_contest(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<ExtendForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    if form.minutes > 0 {
+        let _ = state.extend_contest(contest_id, form.minutes).await;
+    }
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
 pub async fn admin_delete_contest(
     Path(contest_id): Path<i32>,
     State(state): State<AppState>,
@@ -351,23 +458,37 @@ pub async fn admin_delete_contest(
             .bind(contest_id)
             .execute(&state.db)
             .await;
+        state.standings_cache.invalidate(contest_id);
     }
 
     Redirect::to("/admin")
 }
 
 #[derive(serde::Serialize)]
-struct ProblemWithOrder {
-    id: String,
+struct ProblemWithSettings {
+    settings: ContestProblem,
     title: String,
-    order: i32,
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct ParticipantView {
+    username: String,
+    joined_at: i64,
+    team_name: Option<String>,
+    standing: String,
 }
 
 #[derive(Template)]
 #[template(path = "admin/manage_contest.html")]
 struct ManageContestTemplate {
     contest: Contest,
-    problems: Vec<ProblemWithOrder>,
+    problems: Vec<ProblemWithSettings>,
+    participants: Vec<ParticipantView>,
+    allowlist: String, // One entry per line
+    scoring_systems: &'static [&'static dyn ScoringSystem],
+    byte_counts: [ByteCount; 4],
+    drift: Vec<versions::Drift>, // Problems changed since the contest started
+    error: Option<String>,
 }
 
 pub async fn admin_manage_contest(
@@ -384,48 +505,389 @@ pub async fn admin_manage_contest(
         return Redirect::to("/login").into_response();
     }
 
+    manage_contest_page(&state, contest_id, None).await
+}
+
+async fn manage_contest_page(
+    state: &AppState,
+    contest_id: i32,
+    error: Option<String>,
+) -> axum::response::Response {
     let contest = state.get_contest(contest_id).await.ok().flatten();
     if contest.is_none() {
         return Redirect::to("/admin").into_response();
     }
     let contest = contest.unwrap();
 
-    // Get problem IDs and orders from contest_problems
-    let contest_problems: Vec<(String, i32)> = sqlx::query_as(
-        "SELECT problem_id, problem_order FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order"
+    let contest_problems = sqlx::query_as::<_, ContestProblem>(
+        "SELECT * FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
     )
     .bind(contest_id)
     .fetch_all(&state.db)
     .await
     .unwrap_or_default();
 
-    // Load problem data from filesystem and join with contest_problems
+    // Join with the problem registry
     let mut problems = Vec::new();
-    for (problem_id, order) in contest_problems {
-        if let Ok(problem) = problems::load_problem(&problem_id) {
-            problems.push(ProblemWithOrder {
-                id: problem.id,
-                title: problem.title,
-                order,
+    for settings in contest_problems {
+        if let Some(package) = state.problems.get(&settings.problem_id) {
+            problems.push(ProblemWithSettings {
+                title: package.problem.title.clone(),
+                settings,
             });
         }
     }
 
-    let template = ManageContestTemplate { contest, problems };
+    let participants = sqlx::query_as::<_, ParticipantView>(
+        r#"
+        SELECT p.username, p.joined_at::BIGINT AS joined_at, t.name AS team_name, p.standing
+        FROM contest_participants p
+        LEFT JOIN team_members m ON m.contest_id = p.contest_id AND m.username = p.username
+        LEFT JOIN teams t ON t.id = m.team_id
+        WHERE p.contest_id = $1
+        ORDER BY p.joined_at
+        "#,
+    )
+    .bind(contest_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let allowlist: Vec<String> = sqlx::query_scalar(
+        "SELECT entry FROM contest_allowlist WHERE contest_id = $1 ORDER BY entry",
+    )
+    .bind(contest_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let template = ManageContestTemplate {
+        contest,
+        problems,
+        participants,
+        allowlist: allowlist.join("\n"),
+        scoring_systems: scoring::all(),
+        byte_counts: ByteCount::ALL,
+        drift: versions::contest_drift(&state.db, &state.problems, contest_id).await,
+        error,
+    };
     Html(template.render().unwrap()).into_response()
 }
 
-#[derive(serde::Serialize, sqlx::FromRow)]
-struct SubmissionView {
-    id: String,
+#[derive(Deserialize)]
+pub struct SettingsForm {
+    scoring: String,
+    freeze_minutes: i32,
+    reveal_policy: String,
+    byte_count: String,
+}
+
+pub async fn admin_update_settings(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<SettingsForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let reveal_policy = match form.reveal_policy.as_str() {
+        "never" => "never",
+        _ => "after_end",
+    };
+
+    let byte_count = ByteCount::parse(&form.byte_count);
+    let previous: Option<String> =
+        sqlx::query_scalar("SELECT byte_count FROM contests WHERE id = $1")
+            .bind(contest_id)
+            .fetch_optional(&state.db)
+            .await
+            .unwrap_or_default();
+
+    let _ = sqlx::query(
+        "UPDATE contests SET scoring = $2, freeze_minutes = $3, reveal_policy = $4, byte_count = $5 WHERE id = $1",
+    )
+    .bind(contest_id)
+    .bind(scoring::get(&form.scoring).id())
+    .bind(form.freeze_minutes.max(0))
+    .bind(reveal_policy)
+    .bind(byte_count.id())
+    .execute(&state.db)
+    .await;
+
+    // Existing submissions are re-measured so the leaderboard uses one rule throughout
+    if previous.as_deref() != Some(byte_count.id()) {
+        let _ = counting::recount(&state.db, &state.problems, contest_id, None).await;
+    }
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
+/// Re-measure every submission with the current counting rules, after a problem's rule
+/// changed while the contest was running or finished
+pub async fn admin_recount_contest(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let _ = counting::recount(&state.db, &state.problems, contest_id, None).await;
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
+/// Longest alias a contest problem can have
+const MAX_ALIAS_LENGTH: usize = 16;
+
+#[derive(Deserialize)]
+pub struct ContestProblemForm {
+    alias: String,
+    weight: String,
+    open_minute: String,  // Empty = at the start
+    close_minute: String, // Empty = at the end
+}
+
+pub async fn admin_update_contest_problem(
+    Path((contest_id, problem_id)): Path<(i32, String)>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<ContestProblemForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let alias: String = form.alias.trim().chars().take(MAX_ALIAS_LENGTH).collect();
+    let alias = Some(alias).filter(|a| !a.is_empty());
+    let weight = form
+        .weight
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|w| w.is_finite() && *w > 0.0)
+        .map_or(1.0, |w| w.min(scoring::MAX_WEIGHT));
+    let minute = |text: &str| text.trim().parse::<i32>().ok().filter(|&m| m >= 0);
+    let open_minute = minute(&form.open_minute).filter(|&m| m > 0);
+    let close_minute = minute(&form.close_minute);
+    if close_minute.is_some_and(|close| close <= open_minute.unwrap_or(0)) {
+        let error = format!("{problem_id} must close after it opens");
+        return manage_contest_page(&state, contest_id, Some(error)).await;
+    }
+
+    let _ = sqlx::query(
+        "UPDATE contest_problems SET alias = $3, weight = $4, open_minute = $5, close_minute = $6
+         WHERE contest_id = $1 AND problem_id = $2",
+    )
+    .bind(contest_id)
+    .bind(&problem_id)
+    .bind(alias)
+    .bind(weight)
+    .bind(open_minute)
+    .bind(close_minute)
+    .execute(&state.db)
+    .await;
+    // Open pages reload to pick up new labels and windows
+    state.contest_changed(contest_id).await;
+
+    Redirect::to(&format!("/admin/contests/{contest_id}")).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct AccessForm {
+    access_mode: String,
+    join_code: String,
+    allowlist: String,
+}
+
+pub async fn admin_update_access(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<AccessForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let access_mode = access::MODES
+        .into_iter()
+        .find(|m| *m == form.access_mode)
+        .unwrap_or("public");
+    let join_code = Some(form.join_code.trim()).filter(|c| !c.is_empty());
+
+    let _ = sqlx::query("UPDATE contests SET access_mode = $2, join_code = $3 WHERE id = $1")
+        .bind(contest_id)
+        .bind(access_mode)
+        .bind(join_code)
+        .execute(&state.db)
+        .await;
+
+    // Replace the allow-list with the submitted entries (one username per line)
+    let entries = access::parse_allowlist(&form.allowlist);
+
+    let _ = sqlx::query("DELETE FROM contest_allowlist WHERE contest_id = $1")
+        .bind(contest_id)
+        .execute(&state.db)
+        .await;
+    for entry in entries {
+        let _ = sqlx::query(
+            "INSERT INTO contest_allowlist (contest_id, entry) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(contest_id)
+        .bind(entry)
+        .execute(&state.db)
+        .await;
+    }
+    state.standings_cache.invalidate(contest_id);
+
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
+#[derive(Deserialize)]
+pub struct ParticipantForm {
     username: String,
-    problem_id: String,
-    problem_title: String,
-    verdict: String,
-    code_length: i32,
-    time: i32,
-    code: String,
-    created_at: i64,
+}
+
+pub async fn admin_remove_participant(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<ParticipantForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    // Submissions are kept; the user just has to be let back in to continue
+    let _ = sqlx::query("DELETE FROM contest_participants WHERE contest_id = $1 AND username = $2")
+        .bind(contest_id)
+        .bind(&form.username)
+        .execute(&state.db)
+        .await;
+    let _ = sqlx::query("DELETE FROM team_members WHERE contest_id = $1 AND username = $2")
+        .bind(contest_id)
+        .bind(&form.username)
+        .execute(&state.db)
+        .await;
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
+#[derive(Deserialize)]
+pub struct StandingForm {
+    username: String,
+    standing: String,
+}
+
+pub async fn admin_set_standing(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<StandingForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let standing = match form.standing.as_str() {
+        "disqualified" | "hors_concours" => form.standing.as_str(),
+        _ => "ok",
+    };
+
+    // Users who submitted without joining get a participant row so they can be moderated
+    let now = chrono::Utc::now().timestamp();
+    let _ = sqlx::query(
+        "INSERT INTO contest_participants (contest_id, username, joined_at, standing) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (contest_id, username) DO UPDATE SET standing = EXCLUDED.standing",
+    )
+    .bind(contest_id)
+    .bind(form.username.trim())
+    .bind(now)
+    .bind(standing)
+    .execute(&state.db)
+    .await;
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/admin/contests/{contest_id}"))
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateForm {
+    reason: String,
+}
+
+/// Invalidate a submission with a reason, or restore it when the reason is empty
+pub async fn admin_invalidate_submission(
+    Path(submission_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<InvalidateForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let reason = Some(form.reason.trim()).filter(|r| !r.is_empty());
+
+    let contest_id: Option<i32> = sqlx::query_scalar(
+        "UPDATE submissions SET invalidated_reason = $2 WHERE id = $1 RETURNING contest_id",
+    )
+    .bind(&submission_id)
+    .bind(reason)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+
+    match contest_id {
+        Some(id) => {
+            state.standings_changed(id).await;
+            Redirect::to(&format!("/admin/contests/{id}/submissions"))
+        }
+        None => Redirect::to("/admin"),
+    }
 }
 
 #[derive(Template)]
@@ -443,6 +905,37 @@ pub struct SubmissionsQuery {
     verdict: Option<String>,
 }
 
+/// A contest's submissions for admins, newest first, optionally of one user or verdict
+async fn contest_submissions(
+    state: &AppState,
+    contest_id: i32,
+    query: &SubmissionsQuery,
+) -> Vec<SubmissionView> {
+    let mut submissions: Vec<SubmissionView> = sqlx::query_as(
+        "SELECT s.id, s.username, s.problem_id, s.verdict, s.code_length, s.time, s.code,
+                s.created_at, s.invalidated_reason, ps.version AS problem_version, s.seed
+         FROM submissions s
+         LEFT JOIN problem_snapshots ps ON ps.problem_id = s.problem_id AND ps.hash = s.problem_hash
+         WHERE s.contest_id = $1 AND ($2 = '' OR s.username = $2) AND ($3 = '' OR s.verdict = $3)
+         ORDER BY s.created_at DESC",
+    )
+    .bind(contest_id)
+    .bind(query.username.as_deref().unwrap_or_default())
+    .bind(query.verdict.as_deref().unwrap_or_default())
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    for submission in &mut submissions {
+        submission.problem_title = state
+            .problems
+            .get(&submission.problem_id)
+            .map(|p| p.problem.title.clone())
+            .unwrap_or_else(|| submission.problem_id.clone());
+    }
+    submissions
+}
+
 pub async fn admin_submissions(
     Path(contest_id): Path<i32>,
     Query(query): Query<SubmissionsQuery>,
@@ -464,97 +957,55 @@ pub async fn admin_submissions(
     }
     let contest = contest.unwrap();
 
-    let filter_username = query.username.clone().unwrap_or_default();
-    let filter_verdict = query.verdict.clone().unwrap_or_default();
-
-    // Build query with optional filters
-    let mut query_str = String::from(
-        "SELECT s.id, s.username, s.problem_id, s.verdict, s.code_length, s.time, s.code, s.created_at FROM submissions s WHERE s.contest_id = $1",
-    );
-
-    if !filter_username.is_empty() {
-        query_str.push_str(" AND s.username = $2");
-    }
-    if !filter_verdict.is_empty() {
-        if filter_username.is_empty() {
-            query_str.push_str(" AND s.verdict = $2");
-        } else {
-            query_str.push_str(" AND s.verdict = $3");
-        }
-    }
-
-    query_str.push_str(" ORDER BY s.created_at DESC");
-
-    // Execute query with appropriate bindings
-    let submissions_raw: Vec<SubmissionRawTuple> =
-        if !filter_username.is_empty() && !filter_verdict.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_username)
-                .bind(&filter_verdict)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else if !filter_username.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_username)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else if !filter_verdict.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_verdict)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        };
-
-    // Load problem titles from filesystem
-    let mut submissions = Vec::new();
-    for (id, username, problem_id, verdict, code_length, time, code, created_at) in submissions_raw
-    {
-        let problem_title = problems::load_problem(&problem_id)
-            .ok()
-            .map(|p| p.title)
-            .unwrap_or_else(|| problem_id.clone());
-
-        submissions.push(SubmissionView {
-            id,
-            username,
-            problem_id,
-            problem_title,
-            verdict,
-            code_length,
-            time,
-            code,
-            created_at,
-        });
-    }
+    let submissions = contest_submissions(&state, contest_id, &query).await;
 
     let template = SubmissionsTemplate {
         contest,
         submissions,
-        filter_username: filter_username.clone(),
-        filter_verdict: filter_verdict.clone(),
+        filter_username: query.username.unwrap_or_default(),
+        filter_verdict: query.verdict.unwrap_or_default(),
     };
     Html(template.render().unwrap()).into_response()
 }
 
-// Contest routes
-
-pub async fn contest_join(
+// Contest routes
+
+#[derive(Template)]
+#[template(path = "contest/join.html")]
+struct JoinTemplate {
+    contest: Contest,
+    username: String,
+    error: Option<String>,
+}
+
+pub async fn contest_join(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    join_contest(contest_id, state, session, None).await
+}
+
+#[derive(Deserialize)]
+pub struct JoinForm {
+    code: String,
+}
+
+pub async fn contest_join_with_code(
     Path(contest_id): Path<i32>,
     State(state): State<AppState>,
     session: Session,
+    Form(form): Form<JoinForm>,
 ) -> impl IntoResponse {
+    join_contest(contest_id, state, session, Some(form.code)).await
+}
+
+async fn join_contest(
+    contest_id: i32,
+    state: AppState,
+    session: Session,
+    code: Option<String>,
+) -> axum::response::Response {
     // Get contest first to check status
     let contest = match state.get_contest(contest_id).await {
         Ok(Some(c)) => c,
@@ -575,16 +1026,51 @@ pub async fn contest_join(
         }
     };
 
-    // Record participation (idempotent)
-    let now = chrono::Utc::now().timestamp();
-    let _ = sqlx::query(
-        "INSERT INTO contest_participants (contest_id, username, joined_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
-    )
-    .bind(contest_id)
-    .bind(&user.username)
-    .bind(now)
-    .execute(&state.db)
-    .await;
+    let registered = state
+        .is_participant(contest_id, &user.username)
+        .await
+        .unwrap_or(false);
+
+    if !registered {
+        // Enforce the contest's access mode before registering
+        let allowlisted = contest.access_mode == "allowlist"
+            && state
+                .is_allowlisted(contest_id, &user.username)
+                .await
+                .unwrap_or(false);
+        let decision = access::decide(&contest, code.as_deref(), allowlisted);
+        if decision != access::Decision::Join {
+            let error = match decision {
+                access::Decision::Refuse(reason) => Some(reason.to_string()),
+                _ => None, // Show the form for the join code
+            };
+            let template = JoinTemplate {
+                contest,
+                username: user.username,
+                error,
+            };
+            return Html(template.render().unwrap()).into_response();
+        }
+
+        // Record participation (idempotent)
+        let now = chrono::Utc::now().timestamp();
+        let _ = sqlx::query(
+            "INSERT INTO contest_participants (contest_id, username, joined_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
+        )
+        .bind(contest_id)
+        .bind(&user.username)
+        .bind(now)
+        .execute(&state.db)
+        .await;
+        state.standings_changed(contest_id).await;
+    }
+
+    // Team contests need a team before anything else
+    if contest.is_team_contest()
+        && let Ok(None) = state.get_user_team(contest_id, &user.username).await
+    {
+        return Redirect::to(&format!("/contest/{contest_id}/team")).into_response();
+    }
 
     // Redirect based on contest status
     if contest.status == "pending" {
@@ -622,6 +1108,10 @@ pub async fn contest_waiting(
         _ => return Redirect::to("/").into_response(),
     };
 
+    if !state.can_access_contest(&contest, &user.username).await {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
     // If contest is active, redirect to problems
     if contest.status == "active" {
         return Redirect::to(&format!("/contest/{contest_id}/problems")).into_response();
@@ -646,9 +1136,11 @@ pub async fn contest_waiting(
 #[derive(serde::Serialize)]
 struct ProblemListItem {
     id: String,
+    label: String,
     title: String,
-    order: i32,
     verdict: Option<String>,
+    closes_in: Option<i64>, // Seconds until it stops taking submissions, if before the end
+    closed: bool,
 }
 
 #[derive(Template)]
@@ -657,6 +1149,7 @@ struct ProblemsTemplate {
     contest: Contest,
     username: String,
     problems: Vec<ProblemListItem>,
+    upcoming: Vec<(String, i64)>, // Label and seconds until opening of problems released later
     time_remaining: Option<i64>,
 }
 
@@ -680,6 +1173,10 @@ pub async fn contest_problems(
         _ => return Redirect::to("/").into_response(),
     };
 
+    if !state.can_access_contest(&contest, &user.username).await {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
     // If contest is not active, redirect appropriately
     if contest.status == "pending" {
         return Redirect::to(&format!("/contest/{contest_id}/waiting")).into_response();
@@ -687,24 +1184,42 @@ pub async fn contest_problems(
         return Redirect::to(&format!("/contest/{contest_id}/leaderboard")).into_response();
     }
 
-    // Get problem IDs and orders from contest_problems
-    let contest_problems: Vec<(String, i32)> = sqlx::query_as(
-        "SELECT problem_id, problem_order FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order"
+    // In team contests verdicts are shared by the whole team
+    let team = if contest.is_team_contest() {
+        match state.get_user_team(contest_id, &user.username).await {
+            Ok(Some(t)) => Some(t),
+            _ => return Redirect::to(&format!("/contest/{contest_id}/team")).into_response(),
+        }
+    } else {
+        None
+    };
+
+    let contest_problems = sqlx::query_as::<_, ContestProblem>(
+        "SELECT * FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
     )
     .bind(contest_id)
     .fetch_all(&state.db)
     .await
     .unwrap_or_default();
+    let now = chrono::Utc::now().timestamp();
 
-    // Load problem data from filesystem and join with user submissions
+    // Join with the problem registry and user submissions
     let mut problems = Vec::new();
-    for (problem_id, order) in contest_problems {
-        if let Ok(problem) = problems::load_problem(&problem_id) {
+    let mut upcoming = Vec::new();
+    for contest_problem in contest_problems {
+        if !contest_problem.is_released(&contest, now) {
+            let opens_in = contest_problem.opens_at(&contest).unwrap_or(now) - now;
+            upcoming.push((contest_problem.label(), opens_in));
+            continue;
+        }
+        let problem_id = contest_problem.problem_id.clone();
+        if let Some(package) = state.problems.get(&problem_id) {
+            let problem = &package.problem;
             // Get user's best submission verdict for this problem
             let verdict: Option<String> = sqlx::query_scalar(
                 r#"
                 SELECT verdict FROM submissions
-                WHERE username = $1 AND contest_id = $2 AND problem_id = $3
+                WHERE (username = $1 OR team_id = $4) AND contest_id = $2 AND problem_id = $3
                 ORDER BY
                     CASE WHEN verdict = 'AC' THEN 0 ELSE 1 END,
                     code_length ASC,
@@ -715,16 +1230,22 @@ pub async fn contest_problems(
             .bind(&user.username)
             .bind(contest_id)
             .bind(&problem_id)
+            .bind(team.as_ref().map(|t| t.id))
             .fetch_optional(&state.db)
             .await
             .ok()
             .flatten();
 
             problems.push(ProblemListItem {
-                id: problem.id,
-                title: problem.title,
-                order,
+                id: problem_id,
+                label: contest_problem.label(),
+                title: problem.title.clone(),
                 verdict,
+                closes_in: contest_problem
+                    .closes_at(&contest)
+                    .map(|t| t - now)
+                    .filter(|&t| t > 0),
+                closed: !contest_problem.is_open(&contest, now),
             });
         }
     }
@@ -735,6 +1256,7 @@ pub async fn contest_problems(
         contest,
         username: user.username,
         problems,
+        upcoming,
         time_remaining,
     };
     Html(template.render().unwrap()).into_response()
@@ -747,8 +1269,11 @@ struct ProblemPageTemplate {
     problem: Problem,
     username: String,
     statement: String,
+    byte_count: ByteCount, // Rule the editor's counter mirrors
+    language: &'static str,
     time_remaining: Option<i64>,
     contest_ended: bool,
+    closed: bool, // Its own window has closed before the end of the contest
 }
 
 pub async fn contest_problem(
@@ -773,19 +1298,34 @@ pub async fn contest_problem(
         _ => return Redirect::to("/").into_response(),
     };
 
+    if !state.can_access_contest(&contest, &user.username).await {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
     // If contest not active, redirect
     if contest.status != "active" {
         return Redirect::to(&format!("/contest/{contest_id}/problems")).into_response();
     }
 
-    // Load problem from filesystem
-    let problem = match problems::load_problem(&problem_id) {
-        Ok(p) => p,
-        Err(_) => return Redirect::to(&format!("/contest/{contest_id}/problems")).into_response(),
+    // Problems released later in the contest stay hidden until they open
+    let now = chrono::Utc::now().timestamp();
+    let closed = match state.get_contest_problem(contest_id, &problem_id).await {
+        Ok(Some(cp)) if cp.is_released(&contest, now) => !cp.is_open(&contest, now),
+        _ => return Redirect::to(&format!("/contest/{contest_id}/problems")).into_response(),
+    };
+
+    let package = match state.problems.get(&problem_id) {
+        Some(p) => p,
+        None => return Redirect::to(&format!("/contest/{contest_id}/problems")).into_response(),
     };
+    let problem = package.problem.clone();
+    let byte_count =
+        ByteCount::effective(&contest.byte_count, package.metadata.byte_count.as_deref());
+    let kind = package.kind();
 
     // Render markdown statement with sanitization
-    let statement_html = markdown::render_markdown(&problem.statement);
+    let statement_html =
+        markdown::render_statement(&problem.statement, &problem.id, &package.examples);
 
     let time_remaining = state.get_time_remaining(&contest);
     let contest_ended = state.is_contest_ended(&contest);
@@ -795,12 +1335,73 @@ pub async fn contest_problem(
         problem,
         username: user.username,
         statement: statement_html,
+        byte_count,
+        language: Language::get(kind.language_id()).map_or("", |l| l.name),
         time_remaining,
         contest_ended,
+        closed,
     };
     Html(template.render().unwrap()).into_response()
 }
 
+/// An image from a problem's directory, shown in its statement. Images of problems that no
+/// contest has released yet stay hidden from everyone but admins.
+pub async fn problem_image(
+    Path((problem_id, file)): Path<(String, String)>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    let Some(user) = session::get_user(&session).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let content_type = match problems::image_type(&file) {
+        Some(t) if problems::is_valid_problem_id(&problem_id) => t,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    if !user.is_admin {
+        // Visible once the problem is released in any contest
+        let settings = sqlx::query_as::<_, ContestProblem>(
+            "SELECT * FROM contest_problems WHERE problem_id = $1",
+        )
+        .bind(&problem_id)
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+        let now = chrono::Utc::now().timestamp();
+        let mut released = false;
+        for settings in settings {
+            if let Ok(Some(contest)) = state.get_contest(settings.contest_id).await
+                && settings.is_released(&contest, now)
+            {
+                released = true;
+                break;
+            }
+        }
+        if !released {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+
+    match std::fs::read(problems::problem_dir(&problem_id).join(&file)) {
+        Ok(contents) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+                // SVGs can carry scripts, which must not run if the image is opened directly
+                (
+                    header::CONTENT_SECURITY_POLICY,
+                    "default-src 'none'; style-src 'unsafe-inline'; sandbox",
+                ),
+                (header::CACHE_CONTROL, "private, max-age=60"),
+            ],
+            contents,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SubmitForm {
     code: String,
@@ -859,6 +1460,51 @@ pub async fn contest_submit(
         .into_response();
     }
 
+    if !state.can_access_contest(&contest, &user.username).await {
+        return axum::Json(SubmitResponse {
+            verdict: "ERROR".to_string(),
+            code_length: 0,
+            time: 0,
+            output: "You are not registered for this contest".to_string(),
+        })
+        .into_response();
+    }
+
+    // Team contests attribute the submission to the submitter's team
+    let team = if contest.is_team_contest() {
+        match state.get_user_team(contest_id, &user.username).await {
+            Ok(Some(t)) => Some(t),
+            _ => {
+                return axum::Json(SubmitResponse {
+                    verdict: "ERROR".to_string(),
+                    code_length: 0,
+                    time: 0,
+                    output: "Join a team before submitting".to_string(),
+                })
+                .into_response();
+            }
+        }
+    } else {
+        None
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let window_open = state
+        .get_contest_problem(contest_id, &problem_id)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|cp| cp.is_open(&contest, now));
+    if !window_open {
+        return axum::Json(SubmitResponse {
+            verdict: "ERROR".to_string(),
+            code_length: 0,
+            time: 0,
+            output: "This problem is not open for submissions".to_string(),
+        })
+        .into_response();
+    }
+
     let code = &form.code;
 
     // Check code length limit (10KB max)
@@ -877,15 +1523,13 @@ pub async fn contest_submit(
         .into_response();
     }
 
-    let code_length = code.len() as i32;
-
-    // Load problem from filesystem
-    let problem = match problems::load_problem(&problem_id) {
-        Ok(p) => p,
-        Err(_) => {
+    // Problem, grader and version come from one registry entry, so an edit cannot mix versions
+    let package = match state.problems.get(&problem_id) {
+        Some(p) => p,
+        None => {
             return axum::Json(SubmitResponse {
                 verdict: "ERROR".to_string(),
-                code_length,
+                code_length: 0,
                 time: 0,
                 output: "Problem not found".to_string(),
             })
@@ -893,6 +1537,11 @@ pub async fn contest_submit(
         }
     };
 
+    let problem = &package.problem;
+    let code_length =
+        ByteCount::effective(&contest.byte_count, package.metadata.byte_count.as_deref())
+            .count(code);
+
     // Check that test data exists
     if problem.test_input.is_empty() || problem.test_output.is_empty() {
         return axum::Json(SubmitResponse {
@@ -908,39 +1557,9 @@ pub async fn contest_submit(
     let box_id = get_free_box_id().await;
     let runner = CodeRunner::new(box_id);
 
-    // For this contest, only Python 3.11 is allowed
-    let language_id = "python3.11_function_f";
-
-    // Get time and memory limits
-    let time_limit = problems::get_time_limit();
-    let memory_limit = problems::get_memory_limit();
-
-    // Load custom grader for this problem
-    let custom_grader = match problems::load_custom_grader(&problem_id) {
-        Ok(grader) => grader,
-        Err(_) => {
-            return axum::Json(SubmitResponse {
-                verdict: "ERROR".to_string(),
-                code_length,
-                time: 0,
-                output: "Grader not found for this problem".to_string(),
-            })
-            .into_response();
-        }
-    };
-
-    let result = match runner
-        .judge(
-            code,
-            language_id,
-            &problem.test_input,
-            &problem.test_output,
-            time_limit,
-            memory_limit,
-            &custom_grader,
-        )
-        .await
-    {
+    // Random tests are generated from a fresh seed, which is kept to reproduce them
+    let seed = rand::random::<u32>() as i64;
+    let (result, seed) = match runner.judge_package(&package, code, seed).await {
         Ok(r) => r,
         Err(e) => {
             return axum::Json(SubmitResponse {
@@ -957,13 +1576,14 @@ pub async fn contest_submit(
     let time_ms = result.time_ms;
     let output = result.output;
 
-    // Save submission
+    // Save submission, keeping the version it was judged against
     let now = chrono::Utc::now().timestamp();
-    let submission_id = generate_submission_id();
+    let submission_id = Submission::generate_id();
+    let _ = versions::record(&state.db, &package).await;
 
-    let _ = sqlx::query(
-        "INSERT INTO submissions (id, username, contest_id, problem_id, verdict, code_length, time, code, created_at)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+    let saved = sqlx::query(
+        "INSERT INTO submissions (id, username, contest_id, problem_id, verdict, code_length, time, code, created_at, team_id, problem_hash, seed)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"
     )
     .bind(&submission_id)
     .bind(&user.username)
@@ -974,8 +1594,34 @@ pub async fn contest_submit(
     .bind(time_ms)
     .bind(code)
     .bind(now)
+    .bind(team.as_ref().map(|t| t.id))
+    .bind(&package.hash)
+    .bind(seed)
     .execute(&state.db)
     .await;
+    if let Err(e) = saved {
+        return axum::Json(SubmitResponse {
+            verdict: "ERROR".to_string(),
+            code_length,
+            time: time_ms,
+            output: format!("Failed to save submission: {e}"),
+        })
+        .into_response();
+    }
+
+    // Standings only change when an accepted submission beats the entry's previous best
+    if verdict.as_str() == "AC" {
+        let entity = team.as_ref().map_or(&user.username, |t| &t.name);
+        let unchanged = state
+            .standings_cache
+            .peek(contest_id)
+            .and_then(|s| s.best_length(entity, &problem_id))
+            .is_some_and(|best| best <= code_length);
+        if !unchanged {
+            let state = state.clone();
+            tokio::spawn(async move { state.standings_changed(contest_id).await });
+        }
+    }
 
     axum::Json(SubmitResponse {
         verdict: verdict.to_string(),
@@ -986,37 +1632,36 @@ pub async fn contest_submit(
     .into_response()
 }
 
-fn generate_submission_id() -> String {
-    use base64::Engine;
-    use rand::Rng;
-    let bytes: [u8; 16] = rand::rng().random();
-    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
-}
-
 // Leaderboard
 
-#[derive(serde::Serialize, Clone)]
-struct UserProblemResult {
-    code_length: i32,
-    medal: String, // "diamond", "gold", or "none"
-}
+/// Standings as `user` may see them, served from the standings cache
+async fn visible_standings(
+    state: &AppState,
+    standings: &CachedStandings,
+    user: Option<&session::SessionUser>,
+) -> leaderboard::Snapshot {
+    let contest = &standings.contest;
+    let now = chrono::Utc::now().timestamp();
+    let is_admin = user.is_some_and(|u| u.is_admin);
+    let frozen = contest.is_frozen(now) && !is_admin;
 
-#[derive(serde::Serialize, Clone)]
-struct LeaderboardEntry {
-    username: String,
-    total_score: i32,
-    problems_solved: i64,
-    total_bytes: i64,
-    diamonds: i32,
-    golds: i32,
-    problem_results: Vec<Option<UserProblemResult>>, // One per problem in order
-}
+    // Participants keep seeing their own row (or their team's) update during a freeze
+    let viewer = match user {
+        Some(u) if frozen && contest.is_team_contest() => state
+            .get_user_team(contest.id, &u.username)
+            .await
+            .ok()
+            .flatten()
+            .map(|t| t.name),
+        Some(u) if frozen => Some(u.username.clone()),
+        _ => None,
+    };
 
-#[derive(serde::Serialize, sqlx::FromRow)]
-struct ProblemScore {
-    problem_id: String,
-    username: String,
-    code_length: i32,
+    let mut snapshot = standings.view(frozen, viewer.as_deref());
+    if !is_admin {
+        standings.hide_unreleased(&mut snapshot, now);
+    }
+    snapshot
 }
 
 #[derive(Template)]
@@ -1026,7 +1671,12 @@ struct LeaderboardTemplate {
     username: Option<String>,
     entries: Vec<LeaderboardEntry>,
     problem_ids: Vec<String>,
-    problem_titles: Vec<String>,
+    problem_labels: Vec<String>,
+    problem_titles: Vec<String>, // Empty for problems not released yet
+    team_mode: bool,
+    scoring: &'static dyn ScoringSystem,
+    frozen: bool,       // Viewer sees the board as of the freeze
+    admin_frozen: bool, // Viewer is an admin and participants see a frozen board
 }
 
 pub async fn contest_leaderboard(
@@ -1036,382 +1686,60 @@ pub async fn contest_leaderboard(
 ) -> impl IntoResponse {
     let user = session::get_user(&session).await;
 
-    // Get contest
-    let contest = match state.get_contest(contest_id).await {
-        Ok(Some(c)) => c,
-        _ => return Redirect::to("/").into_response(),
+    // Get contest with its standings
+    let standings = match state.standings(contest_id).await {
+        Some(s) => s,
+        None => return Redirect::to("/").into_response(),
     };
-
-    // Get problem IDs for this contest
-    let problem_ids: Vec<String> = sqlx::query_scalar(
-        "SELECT problem_id FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
-    )
-    .bind(contest_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
-
-    // Load problem titles
-    let problem_titles: Vec<String> = problem_ids
-        .iter()
-        .map(|pid| {
-            problems::load_problem(pid)
-                .map(|p| p.title)
-                .unwrap_or_else(|_| format!("Problem {}", pid))
-        })
-        .collect();
-
-    // For each problem, find the best (shortest) accepted solution and count how many have it
-    let mut best_solutions: std::collections::HashMap<String, i32> =
-        std::collections::HashMap::new();
-    let mut best_solution_counts: std::collections::HashMap<String, i32> =
-        std::collections::HashMap::new();
-
-    for pid in &problem_ids {
-        let best: Option<i32> = sqlx::query_scalar(
-            "SELECT MIN(code_length) FROM submissions
-             WHERE contest_id = $1 AND problem_id = $2 AND verdict = 'AC'",
-        )
-        .bind(contest_id)
-        .bind(pid)
-        .fetch_optional(&state.db)
-        .await
-        .ok()
-        .flatten();
-
-        if let Some(best_len) = best {
-            best_solutions.insert(pid.clone(), best_len);
-
-            // Count how many users have this best solution
-            let count: i64 = sqlx::query_scalar(
-                r#"SELECT COUNT(DISTINCT username) FROM (
-                    SELECT DISTINCT ON (username) username, code_length
-                    FROM submissions
-                    WHERE contest_id = $1 AND problem_id = $2 AND verdict = 'AC'
-                    ORDER BY username, code_length ASC, created_at ASC
-                ) AS best_per_user WHERE code_length = $3"#,
-            )
-            .bind(contest_id)
-            .bind(pid)
-            .bind(best_len)
-            .fetch_one(&state.db)
-            .await
-            .unwrap_or(0);
-
-            best_solution_counts.insert(pid.clone(), count as i32);
-        }
-    }
-
-    // Get all participants for this contest
-    let participants: Vec<String> =
-        sqlx::query_scalar("SELECT username FROM contest_participants WHERE contest_id = $1")
-            .bind(contest_id)
-            .fetch_all(&state.db)
-            .await
-            .unwrap_or_default();
-
-    // Get all users' best submissions for each problem
-    let user_scores: Vec<ProblemScore> = sqlx::query_as::<_, ProblemScore>(
-        r#"
-        SELECT DISTINCT ON (problem_id, username)
-               problem_id, username, code_length
-        FROM submissions
-        WHERE contest_id = $1 AND verdict = 'AC'
-        ORDER BY problem_id, username, code_length ASC, created_at ASC
-        "#,
-    )
-    .bind(contest_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
-
-    // Build user data with medals
-    let mut user_data: UserDataMap = std::collections::HashMap::new();
-
-    // Initialize all participants with zero scores
-    for username in participants {
-        user_data
-            .entry(username)
-            .or_insert((0, 0, 0, 0, 0, std::collections::HashMap::new()));
-    }
-
-    for score in user_scores {
-        let entry = user_data.entry(score.username.clone()).or_insert((
-            0,
-            0,
-            0,
-            0,
-            0,
-            std::collections::HashMap::new(),
-        ));
-
-        // Determine medal type
-        let (points, medal) = if let Some(&best_len) = best_solutions.get(&score.problem_id) {
-            if score.code_length == best_len {
-                let count = best_solution_counts
-                    .get(&score.problem_id)
-                    .copied()
-                    .unwrap_or(0);
-                if count == 1 {
-                    // Diamond: unique best solution
-                    entry.3 += 1; // diamonds count
-                    (10000, "diamond".to_string())
-                } else {
-                    // Gold: shared best solution
-                    entry.4 += 1; // golds count
-                    (10000, "gold".to_string())
-                }
-            } else {
-                // Bronze: solved but not best
-                (
-                    (10000 * best_len / score.code_length).max(1),
-                    "none".to_string(),
-                )
-            }
-        } else {
-            (0, "none".to_string())
-        };
-
-        entry.0 += points;
-        entry.1 += 1;
-        entry.2 += score.code_length as i64;
-        entry.5.insert(
-            score.problem_id.clone(),
-            UserProblemResult {
-                code_length: score.code_length,
-                medal,
-            },
-        );
-    }
-
-    // Convert to sorted entries with problem results in order
-    let mut entries: Vec<LeaderboardEntry> = user_data
-        .into_iter()
-        .map(
-            |(username, (score, solved, bytes, diamonds, golds, results_map))| {
-                let problem_results: Vec<Option<UserProblemResult>> = problem_ids
-                    .iter()
-                    .map(|pid| results_map.get(pid).cloned())
-                    .collect();
-
-                LeaderboardEntry {
-                    username,
-                    total_score: score,
-                    problems_solved: solved,
-                    total_bytes: bytes,
-                    diamonds,
-                    golds,
-                    problem_results,
-                }
-            },
-        )
-        .collect();
-
-    // Sort by problems solved (descending), then by total bytes (ascending), then by username (ascending)
-    entries.sort_by(|a, b| {
-        b.problems_solved
-            .cmp(&a.problems_solved)
-            .then(a.total_bytes.cmp(&b.total_bytes))
-            .then(a.username.cmp(&b.username))
-    });
+    let snapshot = visible_standings(&state, &standings, user.as_ref()).await;
+    let contest = standings.contest.clone();
+    let now = chrono::Utc::now().timestamp();
 
     let template = LeaderboardTemplate {
+        team_mode: contest.is_team_contest(),
+        scoring: scoring::get(&contest.scoring),
+        admin_frozen: !snapshot.frozen && contest.is_frozen(now),
+        frozen: snapshot.frozen,
         contest,
         username: user.map(|u| u.username),
-        entries,
-        problem_ids,
-        problem_titles,
+        entries: snapshot.entries,
+        problem_ids: snapshot.problem_ids,
+        problem_labels: snapshot.problem_labels,
+        problem_titles: snapshot.problem_titles,
     };
     Html(template.render().unwrap()).into_response()
 }
 
 // API endpoints for JSON data
 
-#[derive(serde::Serialize)]
-struct LeaderboardApiResponse {
-    entries: Vec<LeaderboardEntry>,
-    problem_ids: Vec<String>,
-    problem_titles: Vec<String>,
-}
-
 pub async fn api_contest_leaderboard(
     Path(contest_id): Path<i32>,
     State(state): State<AppState>,
+    session: Session,
 ) -> impl IntoResponse {
-    // Get problem IDs for this contest
-    let problem_ids: Vec<String> = sqlx::query_scalar(
-        "SELECT problem_id FROM contest_problems WHERE contest_id = $1 ORDER BY problem_order",
-    )
-    .bind(contest_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
-
-    // Load problem titles
-    let problem_titles: Vec<String> = problem_ids
-        .iter()
-        .map(|pid| {
-            problems::load_problem(pid)
-                .map(|p| p.title)
-                .unwrap_or_else(|_| format!("Problem {}", pid))
-        })
-        .collect();
-
-    // For each problem, find the best (shortest) accepted solution and count how many have it
-    let mut best_solutions: std::collections::HashMap<String, i32> =
-        std::collections::HashMap::new();
-    let mut best_solution_counts: std::collections::HashMap<String, i32> =
-        std::collections::HashMap::new();
-
-    for pid in &problem_ids {
-        let best: Option<i32> = sqlx::query_scalar(
-            "SELECT MIN(code_length) FROM submissions
-             WHERE contest_id = $1 AND problem_id = $2 AND verdict = 'AC'",
-        )
-        .bind(contest_id)
-        .bind(pid)
-        .fetch_optional(&state.db)
-        .await
-        .ok()
-        .flatten();
-
-        if let Some(best_len) = best {
-            best_solutions.insert(pid.clone(), best_len);
-
-            let count: i64 = sqlx::query_scalar(
-                r#"SELECT COUNT(DISTINCT username) FROM (
-                    SELECT DISTINCT ON (username) username, code_length
-                    FROM submissions
-                    WHERE contest_id = $1 AND problem_id = $2 AND verdict = 'AC'
-                    ORDER BY username, code_length ASC, created_at ASC
-                ) AS best_per_user WHERE code_length = $3"#,
-            )
-            .bind(contest_id)
-            .bind(pid)
-            .bind(best_len)
-            .fetch_one(&state.db)
-            .await
-            .unwrap_or(0);
-
-            best_solution_counts.insert(pid.clone(), count as i32);
-        }
-    }
-
-    // Get all participants for this contest
-    let participants: Vec<String> =
-        sqlx::query_scalar("SELECT username FROM contest_participants WHERE contest_id = $1")
-            .bind(contest_id)
-            .fetch_all(&state.db)
-            .await
-            .unwrap_or_default();
-
-    // Get all users' best submissions for each problem
-    let user_scores: Vec<ProblemScore> = sqlx::query_as::<_, ProblemScore>(
-        r#"
-        SELECT DISTINCT ON (problem_id, username)
-               problem_id, username, code_length
-        FROM submissions
-        WHERE contest_id = $1 AND verdict = 'AC'
-        ORDER BY problem_id, username, code_length ASC, created_at ASC
-        "#,
-    )
-    .bind(contest_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
-
-    // Build user data with medals
-    let mut user_data: UserDataMap = std::collections::HashMap::new();
-
-    // Initialize all participants with zero scores
-    for username in participants {
-        user_data
-            .entry(username)
-            .or_insert((0, 0, 0, 0, 0, std::collections::HashMap::new()));
-    }
+    let user = session::get_user(&session).await;
 
-    for score in user_scores {
-        let entry = user_data.entry(score.username.clone()).or_insert((
-            0,
-            0,
-            0,
-            0,
-            0,
-            std::collections::HashMap::new(),
-        ));
-
-        // Determine medal type
-        let (points, medal) = if let Some(&best_len) = best_solutions.get(&score.problem_id) {
-            if score.code_length == best_len {
-                let count = best_solution_counts
-                    .get(&score.problem_id)
-                    .copied()
-                    .unwrap_or(0);
-                if count == 1 {
-                    entry.3 += 1;
-                    (10000, "diamond".to_string())
-                } else {
-                    entry.4 += 1;
-                    (10000, "gold".to_string())
-                }
-            } else {
-                (
-                    (10000 * best_len / score.code_length).max(1),
-                    "none".to_string(),
-                )
-            }
-        } else {
-            (0, "none".to_string())
-        };
+    let standings = match state.standings(contest_id).await {
+        Some(s) => s,
+        None => return axum::http::StatusCode::NOT_FOUND.into_response(),
+    };
 
-        entry.0 += points;
-        entry.1 += 1;
-        entry.2 += score.code_length as i64;
-        entry.5.insert(
-            score.problem_id.clone(),
-            UserProblemResult {
-                code_length: score.code_length,
-                medal,
-            },
-        );
-    }
+    axum::Json(visible_standings(&state, &standings, user.as_ref()).await).into_response()
+}
 
-    // Convert to sorted entries
-    let mut entries: Vec<LeaderboardEntry> = user_data
-        .into_iter()
-        .map(
-            |(username, (score, solved, bytes, diamonds, golds, results_map))| {
-                let problem_results: Vec<Option<UserProblemResult>> = problem_ids
-                    .iter()
-                    .map(|pid| results_map.get(pid).cloned())
-                    .collect();
-
-                LeaderboardEntry {
-                    username,
-                    total_score: score,
-                    problems_solved: solved,
-                    total_bytes: bytes,
-                    diamonds,
-                    golds,
-                    problem_results,
-                }
-            },
-        )
-        .collect();
+/// Server-sent events for one contest: standings snapshots and status changes
+pub async fn api_contest_events(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    use tokio_stream::StreamExt;
 
-    entries.sort_by(|a, b| {
-        b.problems_solved
-            .cmp(&a.problems_solved)
-            .then(a.total_bytes.cmp(&b.total_bytes))
-            .then(a.username.cmp(&b.username))
-    });
+    let stream = state
+        .events
+        .contest_events(contest_id)
+        .map(|e| Ok::<_, std::convert::Infallible>(e.to_sse()));
 
-    axum::Json(LeaderboardApiResponse {
-        entries,
-        problem_ids,
-        problem_titles,
-    })
-    .into_response()
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 pub async fn api_admin_submissions(
@@ -1437,80 +1765,6 @@ pub async fn api_admin_submissions(
             .into_response();
     }
 
-    let filter_username = query.username.clone().unwrap_or_default();
-    let filter_verdict = query.verdict.clone().unwrap_or_default();
-
-    // Build query with optional filters
-    let mut query_str = String::from(
-        "SELECT s.id, s.username, s.problem_id, s.verdict, s.code_length, s.time, s.code, s.created_at FROM submissions s WHERE s.contest_id = $1",
-    );
-
-    if !filter_username.is_empty() {
-        query_str.push_str(" AND s.username = $2");
-    }
-    if !filter_verdict.is_empty() {
-        if filter_username.is_empty() {
-            query_str.push_str(" AND s.verdict = $2");
-        } else {
-            query_str.push_str(" AND s.verdict = $3");
-        }
-    }
-
-    query_str.push_str(" ORDER BY s.created_at DESC");
-
-    // Execute query with appropriate bindings
-    let submissions_raw: Vec<SubmissionRawTuple> =
-        if !filter_username.is_empty() && !filter_verdict.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_username)
-                .bind(&filter_verdict)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else if !filter_username.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_username)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else if !filter_verdict.is_empty() {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .bind(&filter_verdict)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        } else {
-            sqlx::query_as(&query_str)
-                .bind(contest_id)
-                .fetch_all(&state.db)
-                .await
-                .unwrap_or_default()
-        };
-
-    // Load problem titles from filesystem
-    let mut submissions = Vec::new();
-    for (id, username, problem_id, verdict, code_length, time, code, created_at) in submissions_raw
-    {
-        let problem_title = problems::load_problem(&problem_id)
-            .ok()
-            .map(|p| p.title)
-            .unwrap_or_else(|| problem_id.clone());
-
-        submissions.push(SubmissionView {
-            id,
-            username,
-            problem_id,
-            problem_title,
-            verdict,
-            code_length,
-            time,
-            code,
-            created_at,
-        });
-    }
-
+    let submissions = contest_submissions(&state, contest_id, &query).await;
     axum::Json(submissions).into_response()
 }
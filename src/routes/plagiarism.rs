@@ -0,0 +1,154 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    models::Contest,
+    plagiarism::{self, Candidate, MAX_CANDIDATES, Report, SuspiciousPair},
+    session,
+    state::AppState,
+};
+
+const DEFAULT_THRESHOLD_PERCENT: i32 = 85;
+
+#[derive(Template)]
+#[template(path = "admin/plagiarism.html")]
+struct PlagiarismTemplate {
+    contest: Contest,
+    pairs: Vec<SuspiciousPair>,
+    problem_titles: std::collections::HashMap<String, String>,
+    threshold_percent: i32,
+    compared: usize,
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PlagiarismQuery {
+    threshold: Option<i32>, // Percent
+}
+
+#[derive(sqlx::FromRow)]
+struct CandidateRow {
+    id: String,
+    owner: String,
+    username: String,
+    problem_id: String,
+    code: String,
+    code_length: i32,
+    created_at: i64,
+}
+
+pub async fn admin_plagiarism(
+    Path(contest_id): Path<i32>,
+    Query(query): Query<PlagiarismQuery>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let Some(standings) = state.standings(contest_id).await else {
+        return Redirect::to("/admin").into_response();
+    };
+
+    let threshold_percent = query
+        .threshold
+        .map(|t| t.clamp(1, 100))
+        .unwrap_or(DEFAULT_THRESHOLD_PERCENT);
+
+    let report = match standings.plagiarism_report(threshold_percent) {
+        Some(report) => report,
+        None => {
+            let report = check(&state, contest_id, threshold_percent).await;
+            standings.store_plagiarism_report(threshold_percent, report)
+        }
+    };
+
+    let mut problem_titles = std::collections::HashMap::new();
+    for pair in &report.pairs {
+        problem_titles
+            .entry(pair.problem_id.clone())
+            .or_insert_with(|| {
+                state
+                    .problems
+                    .get(&pair.problem_id)
+                    .map(|p| p.problem.title.clone())
+                    .unwrap_or_else(|| pair.problem_id.clone())
+            });
+    }
+
+    let template = PlagiarismTemplate {
+        contest: standings.contest.clone(),
+        pairs: report.pairs.clone(),
+        problem_titles,
+        threshold_percent,
+        compared: report.compared,
+        truncated: report.truncated,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Compare each entry's best accepted solution per problem with everyone else's
+async fn check(state: &AppState, contest_id: i32, threshold_percent: i32) -> Report {
+    // Teammates share solutions legitimately, so compare by team in team contests
+    let mut rows = sqlx::query_as::<_, CandidateRow>(
+        r#"
+        SELECT * FROM (
+            SELECT DISTINCT ON (problem_id, owner) * FROM (
+                SELECT id, COALESCE('team:' || team_id, 'user:' || username) AS owner,
+                       username, problem_id, code, code_length, created_at
+                FROM submissions
+                WHERE contest_id = $1 AND verdict = 'AC' AND invalidated_reason IS NULL
+            ) s
+            ORDER BY problem_id, owner, code_length, created_at
+        ) best
+        ORDER BY created_at
+        LIMIT $2
+        "#,
+    )
+    .bind(contest_id)
+    .bind(MAX_CANDIDATES as i64 + 1)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    let truncated = rows.len() > MAX_CANDIDATES;
+    rows.truncate(MAX_CANDIDATES);
+
+    let candidates: Vec<Candidate> = rows
+        .into_iter()
+        .map(|r| Candidate {
+            submission_id: r.id,
+            owner: r.owner,
+            username: r.username,
+            problem_id: r.problem_id,
+            code: r.code,
+            code_length: r.code_length,
+            created_at: r.created_at,
+        })
+        .collect();
+
+    // Comparing is quadratic in the number of submissions, keep it off the async runtime
+    let compared = candidates.len();
+    let threshold = threshold_percent as f64 / 100.0;
+    let pairs = tokio::task::spawn_blocking(move || {
+        plagiarism::find_suspicious_pairs(&candidates, threshold)
+    })
+    .await
+    .unwrap_or_default();
+
+    Report {
+        pairs,
+        compared,
+        truncated,
+    }
+}
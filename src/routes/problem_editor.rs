@@ -0,0 +1,625 @@
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Multipart, Path, Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+
+use crate::{
+    checker::{self, CHECKER_FILES},
+    counting::ByteCount,
+    examples::{self, Examples},
+    library, markdown,
+    problems::{self, GENERATOR_FILES, ProblemKind},
+    session,
+    state::AppState,
+    versions::{self, ProblemVersion},
+};
+
+/// Largest problem upload (statement, tests and grader together)
+pub const MAX_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+/// Files the editor accepts besides the statement, in the order the form shows them
+const UPLOADABLE_FILES: [&str; 7] = [
+    "input.txt",
+    "output.txt",
+    "grader.py",
+    CHECKER_FILES[0],
+    CHECKER_FILES[1],
+    GENERATOR_FILES[0],
+    GENERATOR_FILES[1],
+];
+
+#[derive(Template)]
+#[template(path = "admin/edit_problem.html")]
+struct EditProblemTemplate {
+    problem_id: Option<String>, // None when creating
+    statement: String,          // Raw markdown, including the title heading
+    preview: Preview,
+    present: Vec<&'static str>, // Optional and test files that exist, for download links
+    images: Vec<String>,        // Statement images in the problem's directory
+    uploaded: bool,
+    byte_count: String, // Rule overriding the contest's, empty for none
+    byte_counts: [ByteCount; 4],
+    kind: ProblemKind,
+    kinds: [ProblemKind; 2],
+    checker: String, // Checker setting, empty for the default
+    float_tolerance: String,
+    checkers: [(&'static str, &'static str); 5],
+    tags: String,     // Comma-separated
+    examples: String, // Comma-separated test case numbers
+    difficulty: String,
+    author: String,
+    contests: Vec<(i32, String, String)>, // Contests using the problem: id, name, status
+    error: Option<String>,
+}
+
+impl EditProblemTemplate {
+    fn blank() -> Self {
+        let statement = "# Title\n\nDescribe the task here.\n".to_string();
+        Self {
+            problem_id: None,
+            preview: preview(&statement, None),
+            statement,
+            present: Vec::new(),
+            images: Vec::new(),
+            uploaded: false,
+            byte_count: String::new(),
+            byte_counts: ByteCount::ALL,
+            kind: ProblemKind::Function,
+            kinds: ProblemKind::ALL,
+            checker: String::new(),
+            float_tolerance: String::new(),
+            checkers: checker::BUILT_IN,
+            tags: String::new(),
+            examples: String::new(),
+            difficulty: String::new(),
+            author: String::new(),
+            contests: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+pub async fn admin_new_problem_page(session: Session) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let template = EditProblemTemplate::blank();
+    Html(template.render().unwrap()).into_response()
+}
+
+async fn edit_page(state: &AppState, problem_id: &str, error: Option<String>) -> Response {
+    let statement =
+        match std::fs::read_to_string(problems::problem_dir(problem_id).join("statement.md")) {
+            Ok(s) if problems::is_valid_problem_id(problem_id) => s,
+            _ => return (StatusCode::NOT_FOUND, "Problem not found").into_response(),
+        };
+
+    let contests: Vec<(i32, String, String)> = sqlx::query_as(
+        "SELECT c.id, c.name, c.status FROM contests c
+         JOIN contest_problems cp ON cp.contest_id = c.id
+         WHERE cp.problem_id = $1
+         ORDER BY c.created_at DESC",
+    )
+    .bind(problem_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let metadata = problems::read_metadata(problem_id).unwrap_or_default();
+    let template = EditProblemTemplate {
+        problem_id: Some(problem_id.to_string()),
+        preview: preview(
+            &statement,
+            Some((problem_id, &current_examples(state, problem_id, &statement))),
+        ),
+        statement,
+        images: problems::list_images(problem_id),
+        present: UPLOADABLE_FILES
+            .into_iter()
+            .filter(|f| problems::problem_dir(problem_id).join(f).is_file())
+            .collect(),
+        uploaded: problems::is_uploaded(problem_id),
+        byte_count: metadata.byte_count.unwrap_or_default(),
+        byte_counts: ByteCount::ALL,
+        kind: ProblemKind::parse(metadata.kind.as_deref().unwrap_or_default()),
+        kinds: ProblemKind::ALL,
+        checker: metadata.checker.unwrap_or_default(),
+        float_tolerance: metadata
+            .float_tolerance
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+        checkers: checker::BUILT_IN,
+        tags: metadata.tags.unwrap_or_default().join(", "),
+        examples: metadata
+            .examples
+            .unwrap_or_default()
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        difficulty: metadata.difficulty.unwrap_or_default(),
+        author: metadata.author.unwrap_or_default(),
+        contests,
+        error,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn admin_edit_problem_page(
+    Path(problem_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    edit_page(&state, &problem_id, None).await
+}
+
+/// Fields of the editor form. Empty file inputs mean "keep the current file".
+#[derive(Default)]
+struct ProblemUpload {
+    id: String,
+    statement: String,
+    byte_count: Option<String>, // None to use the contest's rule
+    kind: Option<String>,       // None for function problems, the default
+    checker: Option<String>,    // None for the kind's default or the checker file
+    float_tolerance: Option<f64>,
+    tags: Option<Vec<String>>, // None when there are no tags
+    difficulty: Option<String>,
+    author: Option<String>,
+    examples: Option<Vec<usize>>, // Test cases shown by {{examples}}, None for the first ones
+    files: Vec<(&'static str, Vec<u8>)>,
+    images: Vec<(String, Vec<u8>)>, // Statement images, added or replaced by name
+}
+
+async fn read_upload(mut multipart: Multipart) -> Result<ProblemUpload, String> {
+    let mut upload = ProblemUpload::default();
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        let name = field.name().unwrap_or_default().to_string();
+        let is_file = field.file_name().is_some();
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let bytes = field.bytes().await.map_err(|e| e.to_string())?;
+        match name.as_str() {
+            "images" if !bytes.is_empty() => {
+                if problems::image_type(&file_name).is_none() {
+                    return Err(format!(
+                        "{file_name} is not an image the statement can show: use a PNG, JPEG, GIF, WebP or SVG file named with letters, digits, '-', '_' and '.'"
+                    ));
+                }
+                upload.images.push((file_name, bytes.to_vec()));
+            }
+            "id" => upload.id = String::from_utf8_lossy(&bytes).trim().to_string(),
+            "byte_count" => {
+                let rule = String::from_utf8_lossy(&bytes);
+                upload.byte_count = Some(rule)
+                    .filter(|r| !r.is_empty())
+                    .map(|r| ByteCount::parse(&r).id().to_string())
+            }
+            "kind" => {
+                let kind = ProblemKind::parse(&String::from_utf8_lossy(&bytes));
+                upload.kind = Some(kind)
+                    .filter(|k| *k != ProblemKind::Function)
+                    .map(|k| k.id().to_string())
+            }
+            // The checker setting shares its name with the compiled checker's file input
+            "checker" if !is_file => {
+                let id = String::from_utf8_lossy(&bytes);
+                upload.checker = checker::BUILT_IN
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .chain(["custom"])
+                    .find(|c| *c == id)
+                    .map(str::to_string)
+            }
+            "float_tolerance" => {
+                let tolerance = String::from_utf8_lossy(&bytes);
+                if !tolerance.trim().is_empty() {
+                    upload.float_tolerance = Some(
+                        tolerance
+                            .trim()
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|t| t.is_finite() && *t >= 0.0)
+                            .ok_or_else(|| "The float tolerance must be a number".to_string())?,
+                    );
+                }
+            }
+            "tags" => {
+                let tags = library::parse_tags(&String::from_utf8_lossy(&bytes));
+                upload.tags = Some(tags).filter(|t| !t.is_empty())
+            }
+            "difficulty" => upload.difficulty = optional_text(&bytes),
+            "author" => upload.author = optional_text(&bytes),
+            "examples" => {
+                let numbers = String::from_utf8_lossy(&bytes);
+                let numbers: Result<Vec<usize>, _> = numbers
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(|n| n.parse::<usize>().ok().filter(|n| *n > 0).ok_or(n))
+                    .collect();
+                upload.examples = match numbers {
+                    Ok(numbers) => Some(numbers).filter(|n| !n.is_empty()),
+                    Err(n) => return Err(format!("Example test case {n} is not a number from 1")),
+                };
+            }
+            "statement" => {
+                upload.statement = String::from_utf8(bytes.to_vec())
+                    .map_err(|_| "Statement must be UTF-8".to_string())?
+            }
+            _ => {
+                if let Some(file) = UPLOADABLE_FILES.into_iter().find(|f| *f == name)
+                    && !bytes.is_empty()
+                {
+                    upload.files.push((file, bytes.to_vec()));
+                }
+            }
+        }
+    }
+
+    if upload.statement.trim().is_empty() {
+        return Err("The statement cannot be empty".to_string());
+    }
+    // The compiled checker is the one file that is not text
+    for (name, contents) in &upload.files {
+        if *name != CHECKER_FILES[1] && std::str::from_utf8(contents).is_err() {
+            return Err(format!("{name} must be a UTF-8 text file"));
+        }
+    }
+    Ok(upload)
+}
+
+fn optional_text(bytes: &[u8]) -> Option<String> {
+    Some(String::from_utf8_lossy(bytes).trim().to_string()).filter(|t| !t.is_empty())
+}
+
+async fn save(state: &AppState, problem_id: &str, upload: ProblemUpload) -> Result<(), String> {
+    let mut files = upload.files;
+    // Browsers submit textarea line breaks as CRLF
+    files.push((
+        "statement.md",
+        upload.statement.replace("\r\n", "\n").into_bytes(),
+    ));
+
+    // problem.json is only written when a setting changes, so other problems never gain one
+    let metadata = problems::read_metadata(problem_id).map_err(|e| e.to_string())?;
+    let rule_changed = metadata.byte_count != upload.byte_count;
+    let updated = problems::Metadata {
+        byte_count: upload.byte_count,
+        kind: upload.kind,
+        checker: upload.checker,
+        float_tolerance: upload.float_tolerance,
+        tags: upload.tags,
+        difficulty: upload.difficulty,
+        author: upload.author,
+        examples: upload.examples,
+    };
+    if updated != metadata {
+        files.push((problems::METADATA_FILE, problems::metadata_json(&updated)));
+    }
+
+    let files: Vec<(&str, Vec<u8>)> = files
+        .into_iter()
+        .chain(
+            upload
+                .images
+                .iter()
+                .map(|(name, contents)| (name.as_str(), contents.clone())),
+        )
+        .collect();
+    problems::check_problem_files(problem_id, &files)
+        .map_err(|e| format!("Not saved, the problem would not load: {e}"))?;
+    problems::save_problem_files(problem_id, &files)
+        .map_err(|e| format!("Could not save problem: {e}"))?;
+
+    state
+        .problem_changed(problem_id)
+        .map_err(|e| format!("Saved, but the problem does not load: {e}"))?;
+
+    if rule_changed {
+        state.recount_problem(problem_id).await;
+    }
+    Ok(())
+}
+
+pub async fn admin_create_problem(
+    State(state): State<AppState>,
+    session: Session,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let render_error = |statement: String, error: String| {
+        let mut template = EditProblemTemplate::blank();
+        template.preview = preview(&statement, None);
+        template.statement = statement;
+        template.error = Some(error);
+        Html(template.render().unwrap()).into_response()
+    };
+
+    let upload = match read_upload(multipart).await {
+        Ok(upload) => upload,
+        Err(e) => return render_error(String::new(), e),
+    };
+    let problem_id = upload.id.clone();
+    if !problems::is_valid_problem_id(&problem_id) {
+        return render_error(
+            upload.statement,
+            "Problem IDs may only contain letters, digits, '-' and '_'".to_string(),
+        );
+    }
+    if problems::problem_dir(&problem_id).exists() {
+        return render_error(
+            upload.statement,
+            format!("Problem {problem_id} already exists"),
+        );
+    }
+    // New problems need every file up front, or they would not be listed
+    let has = |name| upload.files.iter().any(|(f, _)| *f == name);
+    let needs_grader = upload.kind.is_none();
+    if !has("input.txt") || !has("output.txt") || (needs_grader && !has("grader.py")) {
+        return render_error(
+            upload.statement,
+            if needs_grader {
+                "Upload the test input, expected output and grader".to_string()
+            } else {
+                "Upload the test input and expected output".to_string()
+            },
+        );
+    }
+
+    match save(&state, &problem_id, upload).await {
+        Ok(()) => Redirect::to(&format!("/admin/problems/{problem_id}")).into_response(),
+        Err(e) => render_error(String::new(), e),
+    }
+}
+
+pub async fn admin_update_problem(
+    Path(problem_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    if !problems::is_valid_problem_id(&problem_id) || !problems::problem_dir(&problem_id).exists() {
+        return (StatusCode::NOT_FOUND, "Problem not found").into_response();
+    }
+
+    let result = match read_upload(multipart).await {
+        Ok(upload) => save(&state, &problem_id, upload).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => Redirect::to(&format!("/admin/problems/{problem_id}")).into_response(),
+        Err(e) => edit_page(&state, &problem_id, Some(e)).await,
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/problem_versions.html")]
+struct ProblemVersionsTemplate {
+    problem_id: String,
+    current_hash: Option<String>,
+    versions: Vec<(ProblemVersion, i64)>, // With the number of submissions judged against each
+    selected: Option<ProblemVersion>,
+    selected_statement: String, // Rendered markdown
+}
+
+async fn versions_page(state: &AppState, problem_id: &str, selected: Option<i32>) -> Response {
+    // Make sure the version being served right now is listed
+    let current_hash = match state.problems.get(problem_id) {
+        Some(package) => {
+            let _ = versions::record(&state.db, &package).await;
+            Some(package.hash.clone())
+        }
+        None => None,
+    };
+
+    let versions = versions::list(&state.db, problem_id).await;
+    if versions.is_empty() {
+        return (StatusCode::NOT_FOUND, "Problem not found").into_response();
+    }
+    let selected = match selected {
+        Some(version) => match versions::get(&state.db, problem_id, version).await {
+            Some(v) => Some(v),
+            None => return (StatusCode::NOT_FOUND, "Version not found").into_response(),
+        },
+        None => None,
+    };
+
+    let template = ProblemVersionsTemplate {
+        problem_id: problem_id.to_string(),
+        current_hash,
+        selected_statement: selected
+            .as_ref()
+            .map(|v| {
+                // Examples come from the current tests, so past versions only name the directive
+                let examples = Err("they are only shown for the current version".to_string());
+                markdown::render_statement(&v.statement, problem_id, &examples)
+            })
+            .unwrap_or_default(),
+        versions,
+        selected,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn admin_problem_versions(
+    Path(problem_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    versions_page(&state, &problem_id, None).await
+}
+
+pub async fn admin_problem_version(
+    Path((problem_id, version)): Path<(String, i32)>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    versions_page(&state, &problem_id, Some(version)).await
+}
+
+/// Download one of a problem's current files
+pub async fn admin_problem_file(
+    Path((problem_id, file)): Path<(String, String)>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let downloadable = file == "statement.md" || UPLOADABLE_FILES.contains(&&*file);
+    if !problems::is_valid_problem_id(&problem_id) || !downloadable {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let content_type = if file == CHECKER_FILES[1] {
+        "application/octet-stream"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    match std::fs::read(problems::problem_dir(&problem_id).join(&file)) {
+        Ok(contents) => (
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{problem_id}-{file}\""),
+                ),
+            ],
+            contents,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct Preview {
+    pub title: String,
+    pub html: String,
+}
+
+/// Render a statement, with examples and images once the problem has been saved
+fn preview(statement: &str, problem: Option<(&str, &Examples)>) -> Preview {
+    let (title, statement) = problems::extract_and_remove_title(statement);
+    Preview {
+        title,
+        html: match problem {
+            Some((id, examples)) => markdown::render_statement(&statement, id, examples),
+            None => markdown::render_markdown(&examples::expand(
+                &statement,
+                &Err("they are taken from the tests once the problem is saved".to_string()),
+            )),
+        },
+    }
+}
+
+/// Examples from a saved problem's tests. They are only worked out on load when the saved
+/// statement asks for them, so a preview adding the directive needs them first.
+fn current_examples(state: &AppState, problem_id: &str, statement: &str) -> Examples {
+    let Some(package) = state.problems.get(problem_id) else {
+        return Err("the problem does not load".to_string());
+    };
+    if !statement.contains(examples::DIRECTIVE)
+        || package.problem.statement.contains(examples::DIRECTIVE)
+    {
+        return package.examples.clone();
+    }
+    let problem = &package.problem;
+    examples::collect(
+        package.kind(),
+        &package.grader,
+        &problem.test_input,
+        &problem.test_output,
+        package.metadata.examples.as_deref(),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    id: Option<String>, // Problem being edited, None when creating
+}
+
+/// Render a statement as contestants will see it, for the editor's live preview
+pub async fn api_preview_statement(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<PreviewQuery>,
+    body: String,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let problem = query
+        .id
+        .filter(|id| problems::is_valid_problem_id(id))
+        .map(|id| {
+            let examples = current_examples(&state, &id, &body);
+            (id, examples)
+        });
+    let problem = problem
+        .as_ref()
+        .map(|(id, examples)| (id.as_str(), examples));
+    Json(preview(&body, problem)).into_response()
+}
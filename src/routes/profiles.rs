@@ -0,0 +1,131 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use tower_sessions::Session;
+
+use crate::{
+    leaderboard::LeaderboardEntry,
+    models::{Contest, Submission, User},
+    routes::rankings::public_snapshot,
+    session,
+    state::AppState,
+};
+
+/// How a user did in one contest
+struct ContestHistory {
+    contest: Contest,
+    team: Option<String>,
+    entry: Option<LeaderboardEntry>, // None while the user has no visible result
+    field_size: usize,
+    solutions: Vec<Solution>,
+}
+
+struct Solution {
+    problem_title: String,
+    submission: Submission,
+}
+
+#[derive(Template)]
+#[template(path = "profile.html")]
+struct ProfileTemplate {
+    profile: User,
+    history: Vec<ContestHistory>,
+    contests_entered: usize,
+    wins: usize,
+    diamonds: i32,
+    golds: i32,
+    username: Option<String>,
+}
+
+pub async fn user_profile(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    let profile = match sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
+        .bind(&name)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(Some(u)) => u,
+        _ => return Redirect::to("/").into_response(),
+    };
+
+    // Contests the user joined or submitted to, once they have started
+    let contests = sqlx::query_as::<_, Contest>(
+        "SELECT c.* FROM contests c
+         WHERE c.status <> 'pending'
+         AND (EXISTS(SELECT 1 FROM contest_participants cp WHERE cp.contest_id = c.id AND cp.username = $1)
+              OR EXISTS(SELECT 1 FROM submissions s WHERE s.contest_id = c.id AND s.username = $1))
+         ORDER BY c.start_time DESC",
+    )
+    .bind(&name)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut history = Vec::new();
+    for contest in contests {
+        let team = state
+            .get_user_team(contest.id, &name)
+            .await
+            .ok()
+            .flatten()
+            .map(|t| t.name);
+        let entity = team.as_deref().unwrap_or(&name);
+
+        let Some((contest, snapshot)) = public_snapshot(&state, contest.id).await else {
+            continue;
+        };
+        let field_size = snapshot.entries.iter().filter(|e| e.rank.is_some()).count();
+        let entry = snapshot.entries.into_iter().find(|e| e.username == entity);
+
+        // The user's own shortest accepted solution to each problem, once the contest allows it
+        let mut solutions = Vec::new();
+        if contest.solutions_public(now) {
+            let submissions = sqlx::query_as::<_, Submission>(
+                "SELECT DISTINCT ON (problem_id) * FROM submissions
+                 WHERE contest_id = $1 AND username = $2
+                 AND verdict = 'AC' AND invalidated_reason IS NULL
+                 ORDER BY problem_id, code_length, created_at",
+            )
+            .bind(contest.id)
+            .bind(&name)
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+
+            for (pid, title) in snapshot.problem_ids.iter().zip(&snapshot.problem_titles) {
+                if let Some(submission) = submissions.iter().find(|s| &s.problem_id == pid) {
+                    solutions.push(Solution {
+                        problem_title: title.clone(),
+                        submission: submission.clone(),
+                    });
+                }
+            }
+        }
+
+        history.push(ContestHistory {
+            contest,
+            team,
+            entry,
+            field_size,
+            solutions,
+        });
+    }
+
+    let entries = || history.iter().filter_map(|h| h.entry.as_ref());
+    let template = ProfileTemplate {
+        contests_entered: history.len(),
+        wins: entries().filter(|e| e.rank == Some(1)).count(),
+        diamonds: entries().map(|e| e.diamonds).sum(),
+        golds: entries().map(|e| e.golds).sum(),
+        profile,
+        history,
+        username: session::get_user(&session).await.map(|u| u.username),
+    };
+    Html(template.render().unwrap()).into_response()
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use askama::Template;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    leaderboard::Snapshot,
+    models::{Contest, Season},
+    rankings::{self, Aggregation, ContestResult, RankingEntry},
+    session,
+    state::AppState,
+};
+
+/// A contest's standings as everyone sees them: the frozen board until final standings are revealed
+pub async fn public_snapshot(state: &AppState, contest_id: i32) -> Option<(Contest, Snapshot)> {
+    let standings = state.standings(contest_id).await?;
+    let now = chrono::Utc::now().timestamp();
+    let snapshot = standings.view(standings.contest.is_frozen(now), None);
+    Some((standings.contest.clone(), snapshot))
+}
+
+/// Ranked finishes in the given contests, with team results credited to every member
+async fn contest_results(state: &AppState, contests: &[Contest]) -> Vec<ContestResult> {
+    let mut results = Vec::new();
+    for contest in contests {
+        let Some((contest, snapshot)) = public_snapshot(state, contest.id).await else {
+            continue;
+        };
+
+        let members: HashMap<String, Vec<String>> = if contest.is_team_contest() {
+            let rows: Vec<(String, String)> = sqlx::query_as(
+                "SELECT t.name, m.username FROM teams t
+                 JOIN team_members m ON m.team_id = t.id
+                 WHERE t.contest_id = $1",
+            )
+            .bind(contest.id)
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+            rows.into_iter()
+                .fold(HashMap::new(), |mut map, (team, user)| {
+                    map.entry(team).or_default().push(user);
+                    map
+                })
+        } else {
+            HashMap::new()
+        };
+
+        let field_size = snapshot.entries.iter().filter(|e| e.rank.is_some()).count();
+        for entry in &snapshot.entries {
+            let Some(rank) = entry.rank else {
+                continue;
+            };
+            let usernames = match members.get(&entry.username) {
+                Some(users) => users.clone(),
+                None => vec![entry.username.clone()],
+            };
+            for username in usernames {
+                results.push(ContestResult {
+                    username,
+                    rank,
+                    score: entry.total_score,
+                    field_size,
+                });
+            }
+        }
+    }
+    results
+}
+
+#[derive(Template)]
+#[template(path = "rankings.html")]
+struct RankingsTemplate {
+    title: String,
+    rule: Aggregation,
+    best_of: i32,
+    contests: Vec<Contest>,
+    entries: Vec<RankingEntry>,
+    seasons: Vec<Season>,
+    season_id: Option<i32>,
+    username: Option<String>,
+}
+
+async fn render_rankings(
+    state: &AppState,
+    session: &Session,
+    title: String,
+    season: Option<&Season>,
+    rule: Aggregation,
+    best_of: i32,
+    contests: Vec<Contest>,
+) -> axum::response::Response {
+    let results = contest_results(state, &contests).await;
+    let entries = rankings::aggregate(rule, best_of.max(0) as usize, &results);
+
+    let seasons = sqlx::query_as::<_, Season>("SELECT * FROM seasons ORDER BY created_at DESC")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+
+    let template = RankingsTemplate {
+        title,
+        rule,
+        best_of,
+        contests,
+        entries,
+        seasons,
+        season_id: season.map(|s| s.id),
+        username: session::get_user(session).await.map(|u| u.username),
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct RankingsQuery {
+    rule: Option<String>,
+    best_of: Option<i32>,
+}
+
+/// All-time ranking over every ended contest
+pub async fn all_time_rankings(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<RankingsQuery>,
+) -> impl IntoResponse {
+    let contests = sqlx::query_as::<_, Contest>(
+        "SELECT * FROM contests WHERE status = 'ended' ORDER BY start_time",
+    )
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let rule = Aggregation::parse(query.rule.as_deref().unwrap_or_default());
+    render_rankings(
+        &state,
+        &session,
+        "All-Time Rankings".to_string(),
+        None,
+        rule,
+        query.best_of.unwrap_or(5),
+        contests,
+    )
+    .await
+}
+
+pub async fn season_rankings(
+    Path(season_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    let season = match sqlx::query_as::<_, Season>("SELECT * FROM seasons WHERE id = $1")
+        .bind(season_id)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(Some(s)) => s,
+        _ => return Redirect::to("/rankings").into_response(),
+    };
+
+    let contests = sqlx::query_as::<_, Contest>(
+        "SELECT c.* FROM contests c
+         JOIN season_contests sc ON sc.contest_id = c.id
+         WHERE sc.season_id = $1 AND c.status = 'ended'
+         ORDER BY c.start_time",
+    )
+    .bind(season_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    render_rankings(
+        &state,
+        &session,
+        season.name.clone(),
+        Some(&season),
+        Aggregation::parse(&season.aggregation),
+        season.best_of,
+        contests,
+    )
+    .await
+}
+
+struct SeasonWithContests {
+    season: Season,
+    contest_ids: Vec<i32>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/seasons.html")]
+struct AdminSeasonsTemplate {
+    seasons: Vec<SeasonWithContests>,
+    contests: Vec<Contest>,
+    rules: [Aggregation; 4],
+}
+
+pub async fn admin_seasons(State(state): State<AppState>, session: Session) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let seasons = sqlx::query_as::<_, Season>("SELECT * FROM seasons ORDER BY created_at DESC")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+    let links: Vec<(i32, i32)> =
+        sqlx::query_as("SELECT season_id, contest_id FROM season_contests")
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+    let contests = sqlx::query_as::<_, Contest>("SELECT * FROM contests ORDER BY created_at DESC")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+
+    let seasons = seasons
+        .into_iter()
+        .map(|season| SeasonWithContests {
+            contest_ids: links
+                .iter()
+                .filter(|(s, _)| *s == season.id)
+                .map(|(_, c)| *c)
+                .collect(),
+            season,
+        })
+        .collect();
+
+    let template = AdminSeasonsTemplate {
+        seasons,
+        contests,
+        rules: Aggregation::ALL,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+struct SeasonForm {
+    name: String,
+    aggregation: String,
+    best_of: i32,
+    contests: Vec<i32>,
+}
+
+/// Parse form data manually to handle the repeated `contests` field
+fn parse_season_form(body: &[u8]) -> SeasonForm {
+    let form_str = String::from_utf8_lossy(body);
+    let mut form = SeasonForm {
+        name: String::new(),
+        aggregation: String::new(),
+        best_of: 0,
+        contests: Vec::new(),
+    };
+
+    for pair in form_str.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            let key_replaced = key.replace('+', " ");
+            let value_replaced = value.replace('+', " ");
+            let key = urlencoding::decode(&key_replaced).unwrap_or_default();
+            let value = urlencoding::decode(&value_replaced).unwrap_or_default();
+
+            match key.as_ref() {
+                "name" => form.name = value.trim().to_string(),
+                "aggregation" => form.aggregation = value.to_string(),
+                "best_of" => form.best_of = value.parse().unwrap_or(0),
+                "contests" => form.contests.extend(value.parse::<i32>().ok()),
+                _ => {}
+            }
+        }
+    }
+    form
+}
+
+async fn save_season(state: &AppState, season_id: Option<i32>, form: SeasonForm) {
+    let aggregation = Aggregation::parse(&form.aggregation).id();
+    let season_id = match season_id {
+        Some(id) => {
+            let _ = sqlx::query(
+                "UPDATE seasons SET name = $2, aggregation = $3, best_of = $4 WHERE id = $1",
+            )
+            .bind(id)
+            .bind(&form.name)
+            .bind(aggregation)
+            .bind(form.best_of.max(0))
+            .execute(&state.db)
+            .await;
+            id
+        }
+        None => {
+            let now = chrono::Utc::now().timestamp();
+            match sqlx::query_scalar(
+                "INSERT INTO seasons (name, aggregation, best_of, created_at) VALUES ($1, $2, $3, $4) RETURNING id",
+            )
+            .bind(&form.name)
+            .bind(aggregation)
+            .bind(form.best_of.max(0))
+            .bind(now)
+            .fetch_one(&state.db)
+            .await
+            {
+                Ok(id) => id,
+                Err(_) => return,
+            }
+        }
+    };
+
+    // Replace the season's contests with the selected ones
+    let _ = sqlx::query("DELETE FROM season_contests WHERE season_id = $1")
+        .bind(season_id)
+        .execute(&state.db)
+        .await;
+    for contest_id in form.contests {
+        let _ = sqlx::query(
+            "INSERT INTO season_contests (season_id, contest_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(season_id)
+        .bind(contest_id)
+        .execute(&state.db)
+        .await;
+    }
+}
+
+pub async fn admin_create_season(
+    State(state): State<AppState>,
+    session: Session,
+    body: Bytes,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let form = parse_season_form(&body);
+    if !form.name.is_empty() {
+        save_season(&state, None, form).await;
+    }
+    Redirect::to("/admin/seasons")
+}
+
+pub async fn admin_update_season(
+    Path(season_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    body: Bytes,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let form = parse_season_form(&body);
+    if !form.name.is_empty() {
+        save_season(&state, Some(season_id), form).await;
+    }
+    Redirect::to("/admin/seasons")
+}
+
+pub async fn admin_delete_season(
+    Path(season_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/");
+        }
+    } else {
+        return Redirect::to("/login");
+    }
+
+    let _ = sqlx::query("DELETE FROM seasons WHERE id = $1")
+        .bind(season_id)
+        .execute(&state.db)
+        .await;
+    Redirect::to("/admin/seasons")
+}
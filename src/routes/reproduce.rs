@@ -0,0 +1,156 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use std::sync::Arc;
+use tower_sessions::Session;
+
+use crate::{
+    runner::{CodeRunner, RunResult, get_free_box_id},
+    session,
+    state::AppState,
+    versions,
+};
+
+/// Longest generated input or expected output shown in full
+const PREVIEW_CHARS: usize = 20_000;
+
+#[derive(sqlx::FromRow)]
+struct ReproducedSubmission {
+    contest_id: i32,
+    username: String,
+    problem_id: String,
+    verdict: String,
+    code: String,
+    seed: Option<i64>,
+    problem_hash: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/reproduce.html")]
+struct ReproduceTemplate {
+    submission_id: String,
+    contest_id: i32,
+    username: String,
+    problem_id: String,
+    verdict: String,
+    seed: i64,
+    judged_version: Option<i32>,     // None if judged before versioning
+    reproduced_version: Option<i32>, // None if the version used was never stored
+    changed: bool, // Reproduced with a different version than judged, since that one was not stored
+    input: String,
+    output: String,
+    result: Option<RunResult>,
+    error: Option<String>,
+}
+
+/// Regenerate a submission's random tests from its seed and judge it on them again
+pub async fn admin_reproduce_submission(
+    Path(submission_id): Path<String>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let row: Option<ReproducedSubmission> = sqlx::query_as(
+        "SELECT contest_id, username, problem_id, verdict, code, seed, problem_hash
+         FROM submissions WHERE id = $1",
+    )
+    .bind(&submission_id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let Some(ReproducedSubmission {
+        contest_id,
+        username,
+        problem_id,
+        verdict,
+        code,
+        seed: Some(seed),
+        problem_hash,
+    }) = row
+    else {
+        return Redirect::to("/admin").into_response();
+    };
+
+    let judged_version = match &problem_hash {
+        Some(hash) => versions::find(&state.db, &problem_id, hash).await,
+        None => None,
+    };
+
+    let mut template = ReproduceTemplate {
+        submission_id,
+        contest_id,
+        username,
+        problem_id,
+        verdict,
+        seed,
+        judged_version,
+        reproduced_version: None,
+        changed: false,
+        input: String::new(),
+        output: String::new(),
+        result: None,
+        error: None,
+    };
+
+    // The version judged against is rebuilt from its snapshot once the problem has moved
+    // on. Submissions judged before versioning can only be rerun with the current one.
+    let current = state.problems.get(&template.problem_id);
+    let package = match (&problem_hash, judged_version) {
+        (Some(hash), Some(version)) if current.as_ref().is_none_or(|p| p.hash != *hash) => {
+            match versions::get(&state.db, &template.problem_id, version).await {
+                Some(snapshot) => snapshot
+                    .package(&template.problem_id)
+                    .map(Arc::new)
+                    .map_err(|e| format!("Version {version} of the problem no longer loads: {e}")),
+                None => Err(format!("Version {version} of the problem is missing")),
+            }
+        }
+        _ => current.ok_or_else(|| "The problem no longer loads".to_string()),
+    };
+
+    match package {
+        Ok(package) => {
+            template.reproduced_version =
+                versions::find(&state.db, &package.problem.id, &package.hash).await;
+            template.changed = problem_hash.as_ref().is_some_and(|h| *h != package.hash);
+
+            let runner = CodeRunner::new(get_free_box_id().await);
+            let rerun = match runner.generate_tests(&package, seed).await {
+                Ok(tests) => {
+                    let result = runner.judge_tests(&package, &code, &tests).await;
+                    let (input, output) = tests.to_text();
+                    template.input = truncate(input);
+                    template.output = truncate(output);
+                    result
+                }
+                Err(e) => Err(e),
+            };
+            match rerun {
+                Ok(result) => template.result = Some(result),
+                Err(e) => template.error = Some(e),
+            }
+        }
+        Err(e) => template.error = Some(e),
+    }
+
+    Html(template.render().unwrap()).into_response()
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((end, _)) = text.char_indices().nth(PREVIEW_CHARS) {
+        text.truncate(end);
+        text.push_str("\n[truncated]");
+    }
+    text
+}
@@ -0,0 +1,130 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use tower_sessions::Session;
+
+use crate::{
+    leaderboard,
+    models::{Contest, ContestProblem},
+    session,
+    state::AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/reveal.html")]
+struct RevealTemplate {
+    contest: Contest,
+    problems: Vec<(String, String)>, // Label and title of each column
+    error: Option<String>,
+}
+
+pub async fn admin_reveal_page(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    reveal_page(&state, contest_id, None).await
+}
+
+async fn reveal_page(state: &AppState, contest_id: i32, error: Option<String>) -> Response {
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) if c.freeze_time().is_some() => c,
+        _ => return Redirect::to(&format!("/admin/contests/{contest_id}")).into_response(),
+    };
+
+    let (contest_problems, problem_titles) =
+        leaderboard::contest_problems(&state.db, &state.problems, contest_id).await;
+    let problems = contest_problems
+        .iter()
+        .map(ContestProblem::label)
+        .zip(problem_titles)
+        .collect();
+
+    let template = RevealTemplate {
+        contest,
+        problems,
+        error,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Standings after revealing 0, 1, 2... problems, for the reveal animation
+pub async fn api_admin_reveal_steps(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if !session::get_user(&session)
+        .await
+        .is_some_and(|u| u.is_admin)
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) => c,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let (contest_problems, _) =
+        leaderboard::contest_problems(&state.db, &state.problems, contest_id).await;
+    let frozen_rows = leaderboard::fetch_rows(&state.db, &contest, contest.freeze_time())
+        .await
+        .unwrap_or_default();
+    let final_rows = leaderboard::fetch_rows(&state.db, &contest, None)
+        .await
+        .unwrap_or_default();
+
+    axum::Json(leaderboard::reveal_steps(
+        &contest,
+        &contest_problems,
+        &frozen_rows,
+        &final_rows,
+    ))
+    .into_response()
+}
+
+/// Publish the final standings to everyone
+pub async fn admin_reveal(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    // Revealing early would publish standings the freeze is still hiding
+    let revealed =
+        sqlx::query("UPDATE contests SET revealed = TRUE WHERE id = $1 AND status = 'ended'")
+            .bind(contest_id)
+            .execute(&state.db)
+            .await
+            .map(|r| r.rows_affected())
+            .unwrap_or_default();
+    if revealed == 0 {
+        let error = "Final standings can only be published once the contest has ended";
+        return reveal_page(&state, contest_id, Some(error.to_string())).await;
+    }
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/contest/{contest_id}/leaderboard")).into_response()
+}
@@ -0,0 +1,93 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use tower_sessions::Session;
+
+use crate::{
+    leaderboard,
+    models::Contest,
+    session,
+    state::AppState,
+    stats::{self, Attempt, ProblemStats},
+};
+
+/// Size of the record timeline chart, in SVG units
+const CHART_WIDTH: i32 = 600;
+const CHART_HEIGHT: i32 = 160;
+
+#[derive(Template)]
+#[template(path = "contest/stats.html")]
+struct ProblemStatsTemplate {
+    contest: Contest,
+    problem_title: String,
+    stats: ProblemStats,
+    record_path: String,
+    chart_width: i32,
+    chart_height: i32,
+    username: Option<String>,
+}
+
+pub async fn problem_stats(
+    Path((contest_id, problem_id)): Path<(i32, String)>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) => c,
+        _ => return Redirect::to("/").into_response(),
+    };
+
+    // Public once the contest's reveal policy allows it, admins can always look
+    let user = session::get_user(&session).await;
+    let now = chrono::Utc::now().timestamp();
+    if !contest.solutions_public(now) && !user.as_ref().is_some_and(|u| u.is_admin) {
+        return Redirect::to(&format!("/contest/{contest_id}/leaderboard")).into_response();
+    }
+
+    let (contest_problems, problem_titles) =
+        leaderboard::contest_problems(&state.db, &state.problems, contest_id).await;
+    let Some(problem_title) = contest_problems
+        .iter()
+        .position(|p| p.problem_id == problem_id)
+        .map(|i| format!("{}: {}", contest_problems[i].label(), problem_titles[i]))
+    else {
+        return Redirect::to(&format!("/contest/{contest_id}/leaderboard")).into_response();
+    };
+
+    // Disqualified entries are left out like on the leaderboard: a user by their own
+    // standing, a team's submissions if any member is disqualified
+    let attempts = sqlx::query_as::<_, Attempt>(
+        "SELECT s.username, s.verdict, s.code_length, s.created_at FROM submissions s
+         LEFT JOIN contest_participants p
+           ON p.contest_id = s.contest_id AND p.username = s.username
+         WHERE s.contest_id = $1 AND s.problem_id = $2 AND s.invalidated_reason IS NULL
+         AND COALESCE(p.standing, 'ok') <> 'disqualified'
+         AND NOT EXISTS (
+             SELECT 1 FROM team_members m
+             JOIN contest_participants q ON q.contest_id = m.contest_id AND q.username = m.username
+             WHERE m.team_id = s.team_id AND q.standing = 'disqualified'
+         )
+         ORDER BY s.created_at, s.id",
+    )
+    .bind(contest_id)
+    .bind(&problem_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let stats = stats::compute(&attempts, contest.start_time.unwrap_or_default());
+    let record_path = stats.record_path(contest.duration as i64, CHART_WIDTH, CHART_HEIGHT);
+
+    let template = ProblemStatsTemplate {
+        contest,
+        problem_title,
+        stats,
+        record_path,
+        chart_width: CHART_WIDTH,
+        chart_height: CHART_HEIGHT,
+        username: user.map(|u| u.username),
+    };
+    Html(template.render().unwrap()).into_response()
+}
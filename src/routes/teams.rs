@@ -0,0 +1,269 @@
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    models::{Contest, Team},
+    session,
+    state::AppState,
+    teams,
+};
+
+#[derive(Template)]
+#[template(path = "contest/team.html")]
+struct TeamTemplate {
+    contest: Contest,
+    username: String,
+    team: Option<Team>,
+    members: Vec<String>,
+    error: Option<String>,
+}
+
+async fn render_team_page(
+    state: &AppState,
+    contest: Contest,
+    username: String,
+    error: Option<String>,
+) -> axum::response::Response {
+    let team = state
+        .get_user_team(contest.id, &username)
+        .await
+        .ok()
+        .flatten();
+
+    let members: Vec<String> = match &team {
+        Some(t) => sqlx::query_scalar(
+            "SELECT username FROM team_members WHERE team_id = $1 ORDER BY joined_at",
+        )
+        .bind(t.id)
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let template = TeamTemplate {
+        contest,
+        username,
+        team,
+        members,
+        error,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn contest_team(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+) -> impl IntoResponse {
+    // Check login
+    let user = match session::get_user(&session).await {
+        Some(u) => u,
+        None => {
+            return Redirect::to(&format!("/login?next=/contest/{contest_id}/join"))
+                .into_response();
+        }
+    };
+
+    // Get contest
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) => c,
+        _ => return Redirect::to("/").into_response(),
+    };
+
+    if !contest.is_team_contest() || !state.can_access_contest(&contest, &user.username).await {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
+    render_team_page(&state, contest, user.username, None).await
+}
+
+#[derive(Deserialize)]
+pub struct CreateTeamForm {
+    name: String,
+}
+
+pub async fn contest_create_team(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<CreateTeamForm>,
+) -> impl IntoResponse {
+    // Check login
+    let user = match session::get_user(&session).await {
+        Some(u) => u,
+        None => {
+            return Redirect::to(&format!("/login?next=/contest/{contest_id}/join"))
+                .into_response();
+        }
+    };
+
+    // Get contest
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) => c,
+        _ => return Redirect::to("/").into_response(),
+    };
+
+    if !contest.is_team_contest()
+        || contest.status == "ended"
+        || !state.can_access_contest(&contest, &user.username).await
+    {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
+    if let Ok(Some(_)) = state.get_user_team(contest_id, &user.username).await {
+        return render_team_page(
+            &state,
+            contest,
+            user.username,
+            Some("You are already in a team".to_string()),
+        )
+        .await;
+    }
+
+    let name = form.name.trim();
+    let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM teams WHERE contest_id = $1")
+        .bind(contest_id)
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+    if let Err(error) = teams::check_name(name, &existing) {
+        return render_team_page(&state, contest, user.username, Some(error)).await;
+    }
+
+    if let Err(e) = create_team(&state, contest_id, name, &user.username).await {
+        return render_team_page(
+            &state,
+            contest,
+            user.username,
+            Some(format!("Failed to create team: {e}")),
+        )
+        .await;
+    }
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/contest/{contest_id}/team")).into_response()
+}
+
+async fn create_team(
+    state: &AppState,
+    contest_id: i32,
+    name: &str,
+    username: &str,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp();
+    let mut tx = state.db.begin().await?;
+
+    let team_id: i32 = sqlx::query_scalar(
+        "INSERT INTO teams (contest_id, name, invite_code, created_at) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(contest_id)
+    .bind(name)
+    .bind(teams::generate_invite_code())
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO team_members (team_id, contest_id, username, joined_at) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(team_id)
+    .bind(contest_id)
+    .bind(username)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+#[derive(Deserialize)]
+pub struct JoinTeamForm {
+    invite_code: String,
+}
+
+pub async fn contest_join_team(
+    Path(contest_id): Path<i32>,
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<JoinTeamForm>,
+) -> impl IntoResponse {
+    // Check login
+    let user = match session::get_user(&session).await {
+        Some(u) => u,
+        None => {
+            return Redirect::to(&format!("/login?next=/contest/{contest_id}/join"))
+                .into_response();
+        }
+    };
+
+    // Get contest
+    let contest = match state.get_contest(contest_id).await {
+        Ok(Some(c)) => c,
+        _ => return Redirect::to("/").into_response(),
+    };
+
+    if !contest.is_team_contest()
+        || contest.status == "ended"
+        || !state.can_access_contest(&contest, &user.username).await
+    {
+        return Redirect::to(&format!("/contest/{contest_id}/join")).into_response();
+    }
+
+    if let Ok(Some(_)) = state.get_user_team(contest_id, &user.username).await {
+        return render_team_page(
+            &state,
+            contest,
+            user.username,
+            Some("You are already in a team".to_string()),
+        )
+        .await;
+    }
+
+    let code = teams::normalize_invite_code(&form.invite_code);
+    let team =
+        sqlx::query_as::<_, Team>("SELECT * FROM teams WHERE contest_id = $1 AND invite_code = $2")
+            .bind(contest_id)
+            .bind(&code)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+
+    let team = match team {
+        Some(t) => t,
+        None => {
+            return render_team_page(
+                &state,
+                contest,
+                user.username,
+                Some("Invalid invite code".to_string()),
+            )
+            .await;
+        }
+    };
+
+    let joined = teams::join(
+        &state.db,
+        team.id,
+        contest_id,
+        &user.username,
+        contest.team_size,
+    )
+    .await
+    .unwrap_or(false);
+
+    if !joined {
+        let error = format!("Team \"{}\" is full", team.name);
+        return render_team_page(&state, contest, user.username, Some(error)).await;
+    }
+    state.standings_changed(contest_id).await;
+
+    Redirect::to(&format!("/contest/{contest_id}/team")).into_response()
+}
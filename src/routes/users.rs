@@ -0,0 +1,332 @@
+use askama::Template;
+use axum::{
+    Form,
+    extract::State,
+    response::{Html, IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    leaderboard, session,
+    state::AppState,
+    usernames::{self, UsernameError},
+};
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct UserWithCounts {
+    username: String,
+    created_at: i64,
+    submission_count: i64,
+    contest_count: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/users.html")]
+struct UsersTemplate {
+    users: Vec<UserWithCounts>,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+async fn render_users_page(
+    state: &AppState,
+    error: Option<String>,
+    message: Option<String>,
+) -> axum::response::Response {
+    let users = sqlx::query_as::<_, UserWithCounts>(
+        r#"
+        SELECT u.username, u.created_at,
+               (SELECT COUNT(*) FROM submissions s WHERE s.username = u.username) AS submission_count,
+               (SELECT COUNT(*) FROM contest_participants cp WHERE cp.username = u.username) AS contest_count
+        FROM users u
+        ORDER BY u.username
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    let template = UsersTemplate {
+        users,
+        error,
+        message,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn admin_users(State(state): State<AppState>, session: Session) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    render_users_page(&state, None, None).await
+}
+
+#[derive(Deserialize)]
+pub struct RenameForm {
+    from: String,
+    to: String,
+}
+
+pub async fn admin_rename_user(
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<RenameForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let from = form.from.trim();
+    let to = form.to.trim();
+
+    if let Err(e) = usernames::validate(to) {
+        return render_users_page(&state, Some(e.to_string()), None).await;
+    }
+
+    // The new name may only look like the user being renamed
+    let existing: Vec<String> =
+        sqlx::query_scalar("SELECT username FROM users WHERE username <> $1")
+            .bind(from)
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+    if existing.iter().any(|name| name == to) {
+        return render_users_page(
+            &state,
+            Some(format!("User \"{to}\" already exists, merge instead")),
+            None,
+        )
+        .await;
+    }
+    if let Some(other) = usernames::find_confusable(to, existing.iter().map(String::as_str)) {
+        let e = UsernameError::Confusable(other.to_string());
+        return render_users_page(&state, Some(e.to_string()), None).await;
+    }
+
+    match rename_user(&state, from, to).await {
+        Ok(true) => {
+            render_users_page(
+                &state,
+                None,
+                Some(format!("Renamed \"{from}\" to \"{to}\"")),
+            )
+            .await
+        }
+        Ok(false) => {
+            render_users_page(&state, Some(format!("User \"{from}\" not found")), None).await
+        }
+        Err(e) => render_users_page(&state, Some(format!("Rename failed: {e}")), None).await,
+    }
+}
+
+/// Rename a user, carrying over their submissions and contest participation
+async fn rename_user(state: &AppState, from: &str, to: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO users (username, is_admin, created_at, email)
+         SELECT $2, is_admin, created_at, email FROM users WHERE username = $1",
+    )
+    .bind(from)
+    .bind(to)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE submissions SET username = $2 WHERE username = $1")
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE contest_participants SET username = $2 WHERE username = $1")
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE team_members SET username = $2 WHERE username = $1")
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+    move_allowlist_entries(&mut tx, from, to).await?;
+    sqlx::query("DELETE FROM users WHERE username = $1")
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    state.standings_cache.invalidate_all();
+    state.users_changed();
+    Ok(true)
+}
+
+#[derive(Deserialize)]
+pub struct MergeForm {
+    from: String,
+    into: String,
+}
+
+pub async fn admin_merge_users(
+    State(state): State<AppState>,
+    session: Session,
+    Form(form): Form<MergeForm>,
+) -> impl IntoResponse {
+    // Check admin
+    if let Some(user) = session::get_user(&session).await {
+        if !user.is_admin {
+            return Redirect::to("/").into_response();
+        }
+    } else {
+        return Redirect::to("/login").into_response();
+    }
+
+    let from = form.from.trim();
+    let into = form.into.trim();
+
+    if from == into {
+        return render_users_page(
+            &state,
+            Some("Cannot merge a user into itself".to_string()),
+            None,
+        )
+        .await;
+    }
+
+    match merge_users(&state, from, into).await {
+        Ok(true) => {
+            render_users_page(
+                &state,
+                None,
+                Some(format!("Merged \"{from}\" into \"{into}\"")),
+            )
+            .await
+        }
+        Ok(false) => {
+            render_users_page(&state, Some("Both users must exist".to_string()), None).await
+        }
+        Err(e) => render_users_page(&state, Some(format!("Merge failed: {e}")), None).await,
+    }
+}
+
+/// Move all of `from`'s submissions and participation onto `into`, then delete `from`
+async fn merge_users(state: &AppState, from: &str, into: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    let found: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username IN ($1, $2)")
+        .bind(from)
+        .bind(into)
+        .fetch_one(&mut *tx)
+        .await?;
+    if found != 2 {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE submissions SET username = $2 WHERE username = $1")
+        .bind(from)
+        .bind(into)
+        .execute(&mut *tx)
+        .await?;
+
+    // A disqualification or hors concours mark on either account stays after the merge
+    let standings: Vec<(i32, String, Option<String>)> = sqlx::query_as(
+        "SELECT f.contest_id, f.standing, i.standing FROM contest_participants f
+         LEFT JOIN contest_participants i ON i.contest_id = f.contest_id AND i.username = $2
+         WHERE f.username = $1",
+    )
+    .bind(from)
+    .bind(into)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Keep the earliest join time when both users joined the same contest
+    sqlx::query(
+        "INSERT INTO contest_participants (contest_id, username, joined_at)
+         SELECT contest_id, $2, joined_at FROM contest_participants WHERE username = $1
+         ON CONFLICT (contest_id, username)
+         DO UPDATE SET joined_at = LEAST(contest_participants.joined_at, EXCLUDED.joined_at)",
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM contest_participants WHERE username = $1")
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+    for (contest_id, from_standing, into_standing) in &standings {
+        let into_standing = into_standing.as_deref().unwrap_or("ok");
+        sqlx::query(
+            "UPDATE contest_participants SET standing = $3 WHERE contest_id = $1 AND username = $2",
+        )
+        .bind(contest_id)
+        .bind(into)
+        .bind(leaderboard::stricter_standing(into_standing, from_standing))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Team memberships move over unless `into` is already in a team for that contest
+    sqlx::query(
+        "UPDATE team_members SET username = $2
+         WHERE username = $1
+         AND NOT EXISTS (
+             SELECT 1 FROM team_members other
+             WHERE other.contest_id = team_members.contest_id AND other.username = $2
+         )",
+    )
+    .bind(from)
+    .bind(into)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM team_members WHERE username = $1")
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+    move_allowlist_entries(&mut tx, from, into).await?;
+    sqlx::query("DELETE FROM users WHERE username = $1")
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    state.standings_cache.invalidate_all();
+    state.users_changed();
+    Ok(true)
+}
+
+/// Point allow-list entries naming `from` at `to`, so access to restricted contests carries over
+async fn move_allowlist_entries(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    from: &str,
+    to: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO contest_allowlist (contest_id, entry)
+         SELECT contest_id, LOWER($2) FROM contest_allowlist WHERE entry = LOWER($1)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(from)
+    .bind(to)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("DELETE FROM contest_allowlist WHERE entry = LOWER($1)")
+        .bind(from)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -1,7 +1,15 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
 use tokio::fs;
 use tokio::process::Command;
 
+use crate::{
+    checker::{CHECKER_TIME_LIMIT_SECS, Checker, CustomChecker, Outcome},
+    languages::Language,
+    problems::{self, GENERATOR_FILES, Package, ProblemKind, TEST_SEPARATOR},
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum Verdict {
@@ -35,6 +43,94 @@ pub struct RunResult {
     pub output: String,
 }
 
+/// What one run of a program in the sandbox produced
+struct Execution {
+    status: String,         // isolate's status code, "OK" if the program exited normally
+    exit_code: Option<i32>, // None if the program did not exit by itself
+    time_ms: i32,
+    stdout: String,
+    stderr: String,
+}
+
+/// Time the generator and reference solution may each take
+const GENERATION_TIME_LIMIT_SECS: f64 = 10.0;
+
+/// Test cases in the shape a problem's submissions are judged on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tests {
+    /// Function problems: the grader reads all of the input and prints a line per test case
+    Grader { input: String, output: String },
+    /// Full programs: the input and expected output of each run
+    Cases(Vec<(String, String)>),
+}
+
+impl Tests {
+    /// A problem's own tests from `input.txt` and `output.txt`
+    pub fn fixed(package: &Package) -> Result<Self, String> {
+        let problem = &package.problem;
+        match package.kind() {
+            ProblemKind::Function => Ok(Tests::Grader {
+                input: problem.test_input.clone(),
+                output: problem.test_output.clone(),
+            }),
+            ProblemKind::Program => {
+                problems::split_tests(&problem.test_input, &problem.test_output).map(Tests::Cases)
+            }
+        }
+    }
+
+    /// Input and expected output in the format of the problem's files, for reading
+    pub fn to_text(&self) -> (String, String) {
+        match self {
+            Tests::Grader { input, output } => (input.clone(), output.clone()),
+            Tests::Cases(cases) => {
+                let separator = format!("{TEST_SEPARATOR}\n");
+                let (inputs, outputs): (Vec<&str>, Vec<&str>) = cases
+                    .iter()
+                    .map(|(input, output)| (input.as_str(), output.as_str()))
+                    .unzip();
+                (inputs.join(&separator), outputs.join(&separator))
+            }
+        }
+    }
+}
+
+/// Generated tests by package hash and seed, so reproducing or re-judging a submission
+/// does not run the generator and reference solution again
+#[derive(Default)]
+struct GeneratedCache {
+    entries: HashMap<(String, i64), (u64, Arc<Tests>)>, // With when each was last used
+    clock: u64,
+}
+
+impl GeneratedCache {
+    const CAPACITY: usize = 64;
+
+    fn get(&mut self, hash: &str, seed: i64) -> Option<Arc<Tests>> {
+        self.clock += 1;
+        let (used, tests) = self.entries.get_mut(&(hash.to_string(), seed))?;
+        *used = self.clock;
+        Some(tests.clone())
+    }
+
+    fn insert(&mut self, hash: &str, seed: i64, tests: Arc<Tests>) {
+        if self.entries.len() >= Self::CAPACITY
+            && let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone())
+        {
+            self.entries.remove(&oldest);
+        }
+        self.clock += 1;
+        self.entries
+            .insert((hash.to_string(), seed), (self.clock, tests));
+    }
+}
+
+static GENERATED: LazyLock<Mutex<GeneratedCache>> = LazyLock::new(Mutex::default);
+
 pub struct CodeRunner {
     box_id: u32,
 }
@@ -183,10 +279,11 @@ impl CodeRunner {
         time_limit_secs: f64,
         mem_limit_kb: u64,
         custom_grader: &str,
+        checker: &Checker,
     ) -> Result<RunResult, String> {
         // Get language definition
-        let language = crate::languages::Language::get(language_id)
-            .ok_or_else(|| format!("Unknown language: {language_id}"))?;
+        let language =
+            Language::get(language_id).ok_or_else(|| format!("Unknown language: {language_id}"))?;
 
         // Initialize sandbox
         let box_path = self.init().await?;
@@ -203,86 +300,23 @@ impl CodeRunner {
             .await
             .map_err(|e| format!("Failed to write grader: {e}"))?;
 
-        // Use provided test input/output
-        let input = test_input;
         let expected_output = test_output;
-
-        // Prepare meta file
-        let meta_file = format!("/tmp/isolate-meta-{}.txt", self.box_id);
-
-        // Build isolate command with language-specific run command
-        use tokio::io::AsyncWriteExt;
-        let mut cmd = Command::new("isolate");
-        cmd.args([
-            "--box-id",
-            &self.box_id.to_string(),
-            "--wall-time",
-            &format!("{:.1}", time_limit_secs * 2.0),
-            "--time",
-            &format!("{time_limit_secs:.1}"),
-            "--mem",
-            &mem_limit_kb.to_string(),
-            "--processes",
-            "--meta",
-            &meta_file,
-            // Directory bindings for Python
-            "--dir=/usr",
-            "--dir=/lib",
-            "--dir=/lib64",
-            "--dir=/bin",
-            "--run",
-            "--",
-            language.run_command.program,
-        ]);
-        cmd.args(language.run_command.args.iter());
-
-        let mut child = cmd
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn isolate: {e}"))?;
-
-        // Write input to stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(input.as_bytes())
-                .await
-                .map_err(|e| format!("Failed to write stdin: {e}"))?;
-            drop(stdin); // Close stdin
-        }
-
-        // Wait for completion
-        let output = child
-            .wait_with_output()
-            .await
-            .map_err(|e| format!("Failed to wait for isolate: {e}"))?;
-
-        // Read meta file
-        let meta = fs::read_to_string(&meta_file).await.unwrap_or_default();
-
-        // Parse meta file
-        let mut time_ms = 0;
-        let mut status = "OK";
-
-        for line in meta.lines() {
-            if let Some(value) = line.strip_prefix("time:") {
-                if let Ok(time_secs) = value.trim().parse::<f64>() {
-                    time_ms = (time_secs * 1000.0) as i32;
-                }
-            } else if let Some(value) = line.strip_prefix("status:") {
-                status = value.trim();
-            }
-        }
-
-        // Clean up
-        let _ = fs::remove_file(&meta_file).await;
+        let run = self
+            .execute(
+                language.run_command.program,
+                language.run_command.args,
+                test_input,
+                time_limit_secs,
+                mem_limit_kb,
+            )
+            .await?;
         self.cleanup().await?;
 
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let time_ms = run.time_ms;
+        let stderr = &run.stderr;
 
         // Determine verdict
-        match status {
+        match run.status.as_str() {
             "RE" | "SG" | "XX" => {
                 // Extract which test case failed from stderr
                 let mut test_case_info = String::new();
@@ -381,88 +415,527 @@ impl CodeRunner {
             _ => {}
         };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-
-        // Check if output matches expected
-        let actual = stdout.trim();
-        let expected = expected_output.trim();
+        // A custom checker sees all of the grader's output at once, one line per test case
+        if let Checker::Custom(custom) = checker {
+            let total_tests = expected_output.lines().count();
+            let outcome = self
+                .run_checker(
+                    custom,
+                    grader_checker_files(custom, test_input, expected_output, &run),
+                )
+                .await?;
+            return Ok(RunResult {
+                verdict: if outcome.accepted {
+                    Verdict::AC
+                } else {
+                    Verdict::WA
+                },
+                time_ms,
+                output: if outcome.accepted {
+                    format!("Passed {total_tests}/{total_tests} test cases")
+                } else {
+                    format!(
+                        "The problem's checker rejected the output{}",
+                        checker_note(&outcome.message)
+                    )
+                },
+            });
+        }
 
-        // Count total test cases and passing test cases
-        let actual_lines: Vec<&str> = actual.lines().collect();
-        let expected_lines: Vec<&str> = expected.lines().collect();
+        // Compare the grader's line for each test case using the problem's checker
+        let actual_lines: Vec<&str> = run.stdout.trim().lines().collect();
+        let expected_lines: Vec<&str> = expected_output.trim().lines().collect();
         let total_tests = expected_lines.len();
 
         let mut passing_tests = 0;
-        let mut failed_test_num = 0;
-        let mut expected_value = String::new();
-        let mut actual_value = String::new();
-
-        for (i, (exp, act)) in expected_lines.iter().zip(actual_lines.iter()).enumerate() {
-            if exp == act {
+        let mut failure = None; // Test number, expected, got, checker message
+        for (i, (exp, act)) in expected_lines.iter().zip(&actual_lines).enumerate() {
+            let outcome = checker
+                .compare(exp, act)
+                .ok_or_else(|| "Checker gave no decision".to_string())?;
+            if outcome.accepted {
                 passing_tests += 1;
-            } else if failed_test_num == 0 {
+            } else if failure.is_none() {
                 // Record the first failure
-                failed_test_num = i + 1;
-                expected_value = exp.to_string();
-                actual_value = act.to_string();
+                failure = Some((i + 1, exp.to_string(), act.to_string(), outcome.message));
             }
         }
 
         // Handle length mismatch
-        if actual_lines.len() != expected_lines.len() && failed_test_num == 0 {
+        if actual_lines.len() != expected_lines.len() && failure.is_none() {
             // No mismatch found yet, so the issue is length
-            if actual_lines.len() < expected_lines.len() {
-                failed_test_num = actual_lines.len() + 1;
-                expected_value = expected_lines
-                    .get(actual_lines.len())
-                    .unwrap_or(&"")
-                    .to_string();
-                actual_value = "(no output)".to_string();
+            failure = Some(if actual_lines.len() < expected_lines.len() {
+                (
+                    actual_lines.len() + 1,
+                    expected_lines[actual_lines.len()].to_string(),
+                    "(no output)".to_string(),
+                    String::new(),
+                )
             } else {
-                failed_test_num = expected_lines.len() + 1;
-                expected_value = "(no more output expected)".to_string();
-                actual_value = actual_lines
-                    .get(expected_lines.len())
-                    .unwrap_or(&"")
-                    .to_string();
-            }
+                (
+                    expected_lines.len() + 1,
+                    "(no more output expected)".to_string(),
+                    actual_lines[expected_lines.len()].to_string(),
+                    String::new(),
+                )
+            });
         }
 
-        if actual == expected {
-            Ok(RunResult {
+        match failure {
+            None => Ok(RunResult {
                 verdict: Verdict::AC,
                 time_ms,
                 output: format!("Passed {total_tests}/{total_tests} test cases"),
-            })
-        } else {
-            // Extract input info from stderr for the failed test case
-            let mut input_info = String::new();
-            if !stderr.is_empty() && failed_test_num > 0 {
-                let marker = format!("TESTCASE {failed_test_num}:");
-                for line in stderr.lines() {
-                    if line.starts_with(&marker) {
-                        if let Some(input_desc) = line.strip_prefix(&marker) {
-                            input_info = format!("Input: {}\n\n", input_desc.trim());
-                        }
-                        break;
-                    }
-                }
-            }
+            }),
+            Some((failed_test_num, expected_value, actual_value, message)) => {
+                let input = grader_test_input(stderr, failed_test_num);
+                let input_info = if input.is_empty() {
+                    String::new()
+                } else {
+                    format!("Input: {input}\n\n")
+                };
 
-            Ok(RunResult {
-                verdict: Verdict::WA,
-                time_ms,
-                output: format!(
-                    "Passed {passing_tests}/{total_tests} test cases
+                Ok(RunResult {
+                    verdict: Verdict::WA,
+                    time_ms,
+                    output: format!(
+                        "Passed {passing_tests}/{total_tests} test cases
 
 Failed on test case {failed_test_num}
 
 {input_info}Expected: {expected_value}
-Got: {actual_value}"
-                ),
-            })
+Got: {actual_value}{}",
+                        checker_note(&message)
+                    ),
+                })
+            }
         }
     }
+
+    /// Judge a submission against a problem's fixed tests and, once it passes them, against
+    /// tests generated from `seed` if the problem has a generator. Returns the seed when
+    /// generated tests were run, so the submission can be reproduced.
+    pub async fn judge_package(
+        &self,
+        package: &Package,
+        code: &str,
+        seed: i64,
+    ) -> Result<(RunResult, Option<i64>), String> {
+        let fixed = self
+            .judge_tests(package, code, &Tests::fixed(package)?)
+            .await?;
+        if fixed.verdict != Verdict::AC || package.generator.is_none() {
+            return Ok((fixed, None));
+        }
+
+        let tests = self.generate_tests(package, seed).await?;
+        let generated = self.judge_tests(package, code, &tests).await?;
+        let result = RunResult {
+            verdict: generated.verdict,
+            time_ms: fixed.time_ms + generated.time_ms,
+            output: format!(
+                "{}\n\nGenerated tests (seed {seed}): {}",
+                fixed.output, generated.output
+            ),
+        };
+        Ok((result, Some(seed)))
+    }
+
+    /// Judge a submission on some tests
+    pub async fn judge_tests(
+        &self,
+        package: &Package,
+        code: &str,
+        tests: &Tests,
+    ) -> Result<RunResult, String> {
+        // Only Python 3.11 is allowed, run the way the problem expects
+        let kind = package.kind();
+        let language_id = kind.language_id();
+
+        // Get time and memory limits
+        let time_limit = problems::get_time_limit();
+        let memory_limit = problems::get_memory_limit();
+
+        match tests {
+            Tests::Grader { input, output } => {
+                self.judge(
+                    code,
+                    language_id,
+                    input,
+                    output,
+                    time_limit,
+                    memory_limit,
+                    &package.grader,
+                    &package.checker,
+                )
+                .await
+            }
+            Tests::Cases(cases) => {
+                self.judge_program(
+                    code,
+                    language_id,
+                    cases,
+                    time_limit,
+                    memory_limit,
+                    &package.checker,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Generate a problem's random tests for a seed, with the reference solution's output
+    /// as the expected output
+    pub async fn generate_tests(&self, package: &Package, seed: i64) -> Result<Arc<Tests>, String> {
+        if let Some(tests) = GENERATED.lock().unwrap().get(&package.hash, seed) {
+            return Ok(tests);
+        }
+        let tests = Arc::new(self.generate_tests_uncached(package, seed).await?);
+        GENERATED
+            .lock()
+            .unwrap()
+            .insert(&package.hash, seed, tests.clone());
+        Ok(tests)
+    }
+
+    async fn generate_tests_uncached(&self, package: &Package, seed: i64) -> Result<Tests, String> {
+        let generator = package
+            .generator
+            .as_ref()
+            .ok_or_else(|| "The problem has no generator".to_string())?;
+        let kind = package.kind();
+        let language = Language::get(kind.language_id())
+            .ok_or_else(|| format!("Unknown language: {}", kind.language_id()))?;
+
+        let box_path = self.init().await?;
+        let files = [
+            (GENERATOR_FILES[0].to_string(), &generator.generator),
+            (language.submission_filename(), &generator.solution),
+            (language.grader_filename(), &package.grader),
+        ];
+        for (name, contents) in &files {
+            fs::write(box_path.join(name), contents)
+                .await
+                .map_err(|e| format!("Failed to write {name}: {e}"))?;
+        }
+
+        let result = self.run_generator(kind, language, seed).await;
+        self.cleanup().await?;
+        result
+    }
+
+    async fn run_generator(
+        &self,
+        kind: ProblemKind,
+        language: &Language,
+        seed: i64,
+    ) -> Result<Tests, String> {
+        let memory_limit = problems::get_memory_limit();
+        let seed = seed.to_string();
+        let generated = self
+            .execute(
+                "/usr/bin/python3",
+                &[GENERATOR_FILES[0], &seed],
+                "",
+                GENERATION_TIME_LIMIT_SECS,
+                memory_limit,
+            )
+            .await?;
+        succeeded(&generated, "Generator")?;
+        let input = generated.stdout;
+
+        let mut outputs = Vec::new();
+        let cases = match kind {
+            ProblemKind::Function => vec![input.clone()],
+            ProblemKind::Program => problems::split_cases(&input),
+        };
+        for case in &cases {
+            let solved = self
+                .execute(
+                    language.run_command.program,
+                    language.run_command.args,
+                    case,
+                    GENERATION_TIME_LIMIT_SECS,
+                    memory_limit,
+                )
+                .await?;
+            succeeded(&solved, "Reference solution")?;
+            let mut output = solved.stdout;
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            outputs.push(output);
+        }
+
+        Ok(match kind {
+            ProblemKind::Function => Tests::Grader {
+                input,
+                output: outputs.concat(),
+            },
+            ProblemKind::Program => Tests::Cases(cases.into_iter().zip(outputs).collect()),
+        })
+    }
+
+    /// Run a full program once per test case, checking each output with the problem's checker
+    pub async fn judge_program(
+        &self,
+        code: &str,
+        language_id: &str,
+        tests: &[(String, String)],
+        time_limit_secs: f64,
+        mem_limit_kb: u64,
+        checker: &Checker,
+    ) -> Result<RunResult, String> {
+        let language =
+            Language::get(language_id).ok_or_else(|| format!("Unknown language: {language_id}"))?;
+
+        let box_path = self.init().await?;
+        fs::write(box_path.join(language.submission_filename()), code)
+            .await
+            .map_err(|e| format!("Failed to write submission: {e}"))?;
+
+        let result = self
+            .run_tests(language, tests, time_limit_secs, mem_limit_kb, checker)
+            .await;
+        self.cleanup().await?;
+        result
+    }
+
+    async fn run_tests(
+        &self,
+        language: &Language,
+        tests: &[(String, String)],
+        time_limit_secs: f64,
+        mem_limit_kb: u64,
+        checker: &Checker,
+    ) -> Result<RunResult, String> {
+        let total_tests = tests.len();
+        let mut time_ms = 0;
+
+        for (i, (input, expected)) in tests.iter().enumerate() {
+            let run = self
+                .execute(
+                    language.run_command.program,
+                    language.run_command.args,
+                    input,
+                    time_limit_secs,
+                    mem_limit_kb,
+                )
+                .await?;
+            time_ms += run.time_ms;
+
+            let test_num = i + 1;
+            let failure = match run.status.as_str() {
+                "RE" | "SG" | "XX" => Some((
+                    Verdict::RE,
+                    format!(
+                        "Runtime error on test case {test_num}\n\nInput: {}\n\nError:\n{}",
+                        preview(input),
+                        run.stderr.trim()
+                    ),
+                )),
+                "TO" => Some((
+                    Verdict::TLE,
+                    format!("Time limit exceeded on test case {test_num}"),
+                )),
+                _ => {
+                    let outcome = self.check(checker, input, expected, &run.stdout).await?;
+                    (!outcome.accepted).then(|| {
+                        (
+                            Verdict::WA,
+                            format!(
+                                "Failed on test case {test_num}\n\nInput: {}\n\nExpected: {}\nGot: {}{}",
+                                preview(input),
+                                preview(expected),
+                                preview(&run.stdout),
+                                checker_note(&outcome.message)
+                            ),
+                        )
+                    })
+                }
+            };
+
+            if let Some((verdict, message)) = failure {
+                return Ok(RunResult {
+                    verdict,
+                    time_ms,
+                    output: format!("Passed {i}/{total_tests} test cases\n\n{message}"),
+                });
+            }
+        }
+
+        Ok(RunResult {
+            verdict: Verdict::AC,
+            time_ms,
+            output: format!("Passed {total_tests}/{total_tests} test cases"),
+        })
+    }
+
+    /// Compare the output for one test case with the expected output
+    async fn check(
+        &self,
+        checker: &Checker,
+        input: &str,
+        expected: &str,
+        actual: &str,
+    ) -> Result<Outcome, String> {
+        match (checker.compare(expected, actual), checker) {
+            (Some(outcome), _) => Ok(outcome),
+            (None, Checker::Custom(custom)) => {
+                self.run_checker(custom, custom.files(input, expected, actual))
+                    .await
+            }
+            (None, _) => Err("Checker gave no decision".to_string()),
+        }
+    }
+
+    /// Run a problem's own checker in a sandbox of its own, so nothing the submission
+    /// left behind can affect it
+    async fn run_checker(
+        &self,
+        custom: &CustomChecker,
+        files: [(&'static str, &[u8]); 4],
+    ) -> Result<Outcome, String> {
+        let sandbox = CodeRunner::new(get_free_box_id().await);
+        let box_path = sandbox.init().await?;
+        let result = sandbox.run_checker_in(&box_path, custom, files).await;
+        sandbox.cleanup().await?;
+        result
+    }
+
+    async fn run_checker_in(
+        &self,
+        box_path: &Path,
+        custom: &CustomChecker,
+        files: [(&'static str, &[u8]); 4],
+    ) -> Result<Outcome, String> {
+        for (name, contents) in files {
+            fs::write(box_path.join(name), contents)
+                .await
+                .map_err(|e| format!("Failed to write {name}: {e}"))?;
+        }
+        if custom.is_executable() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                box_path.join(custom.file),
+                std::fs::Permissions::from_mode(0o755),
+            )
+            .await
+            .map_err(|e| format!("Failed to make checker executable: {e}"))?;
+        }
+
+        let (program, args) = custom.command();
+        let run = self
+            .execute(
+                program,
+                &args,
+                "",
+                CHECKER_TIME_LIMIT_SECS,
+                problems::get_memory_limit(),
+            )
+            .await?;
+        if run.status == "TO" {
+            return Err("Checker timed out".to_string());
+        }
+        CustomChecker::outcome(run.exit_code, &run.stdout, &run.stderr)
+    }
+
+    /// Run a command in the initialised sandbox with the given stdin
+    async fn execute(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: &str,
+        time_limit_secs: f64,
+        mem_limit_kb: u64,
+    ) -> Result<Execution, String> {
+        // Prepare meta file
+        let meta_file = format!("/tmp/isolate-meta-{}.txt", self.box_id);
+
+        // Build isolate command with language-specific run command
+        use tokio::io::AsyncWriteExt;
+        let mut cmd = Command::new("isolate");
+        cmd.args([
+            "--box-id",
+            &self.box_id.to_string(),
+            "--wall-time",
+            &format!("{:.1}", time_limit_secs * 2.0),
+            "--time",
+            &format!("{time_limit_secs:.1}"),
+            "--mem",
+            &mem_limit_kb.to_string(),
+            "--processes",
+            "--meta",
+            &meta_file,
+            // Directory bindings for Python
+            "--dir=/usr",
+            "--dir=/lib",
+            "--dir=/lib64",
+            "--dir=/bin",
+            "--run",
+            "--",
+            program,
+        ]);
+        cmd.args(args);
+
+        let mut child = cmd
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn isolate: {e}"))?;
+
+        // Write input to stdin
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.as_bytes())
+                .await
+                .map_err(|e| format!("Failed to write stdin: {e}"))?;
+            drop(stdin); // Close stdin
+        }
+
+        // Wait for completion
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| format!("Failed to wait for isolate: {e}"))?;
+
+        // Read meta file
+        let meta = fs::read_to_string(&meta_file).await.unwrap_or_default();
+
+        // Parse meta file
+        let mut time_ms = 0;
+        let mut status = "OK";
+        let mut exit_code = None;
+
+        for line in meta.lines() {
+            if let Some(value) = line.strip_prefix("time:") {
+                if let Ok(time_secs) = value.trim().parse::<f64>() {
+                    time_ms = (time_secs * 1000.0) as i32;
+                }
+            } else if let Some(value) = line.strip_prefix("status:") {
+                status = value.trim();
+            } else if let Some(value) = line.strip_prefix("exitcode:") {
+                exit_code = value.trim().parse().ok();
+            }
+        }
+
+        let _ = fs::remove_file(&meta_file).await;
+
+        // isolate only reports the exit code when it is not 0
+        let exit_code = match status {
+            "OK" => Some(exit_code.unwrap_or(0)),
+            "RE" => exit_code,
+            _ => None,
+        };
+        Ok(Execution {
+            status: status.to_string(),
+            exit_code,
+            time_ms,
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
 }
 
 /// Get a free isolate box ID
@@ -471,3 +944,127 @@ pub async fn get_free_box_id() -> u32 {
     static NEXT_BOX_ID: AtomicU32 = AtomicU32::new(0);
     NEXT_BOX_ID.fetch_add(1, Ordering::SeqCst) % 100 // Cycle through 0-99
 }
+
+/// Fail with the program's error output unless it exited normally
+fn succeeded(run: &Execution, program: &str) -> Result<(), String> {
+    if run.status == "OK" {
+        Ok(())
+    } else {
+        Err(format!(
+            "{program} failed ({}): {}",
+            run.status,
+            run.stderr.trim()
+        ))
+    }
+}
+
+/// The files a custom checker is given for a function problem's run. The input is the
+/// problem's own: the grader's TESTCASE lines share stderr with the submission, which could
+/// write its own to change what the checker sees.
+fn grader_checker_files<'a>(
+    custom: &'a CustomChecker,
+    test_input: &'a str,
+    expected_output: &'a str,
+    run: &'a Execution,
+) -> [(&'static str, &'a [u8]); 4] {
+    custom.files(test_input, expected_output, &run.stdout)
+}
+
+/// What the grader reported as the input of a test case, from its TESTCASE lines on stderr.
+/// Only for showing to the submitter, since the submission can write to stderr too.
+fn grader_test_input(stderr: &str, test_num: usize) -> String {
+    let marker = format!("TESTCASE {test_num}:");
+    stderr
+        .lines()
+        .find_map(|line| line.strip_prefix(&marker))
+        .map(|input| input.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The checker's explanation, on its own line after Expected/Got
+fn checker_note(message: &str) -> String {
+    if message.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", preview(message))
+    }
+}
+
+/// Shorten text for a verdict message
+fn preview(text: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    let text = text.trim();
+    if text.is_empty() {
+        "(no output)".to_string()
+    } else if text.chars().count() > MAX_CHARS {
+        format!("{}...", text.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_output_is_kept_per_case() {
+        // A reference solution may print the separator line itself
+        let tests = Tests::Cases(vec![
+            ("1\n".to_string(), "---\n".to_string()),
+            ("2\n".to_string(), "4\n".to_string()),
+        ]);
+        let (input, output) = tests.to_text();
+        assert_eq!(input, "1\n---\n2\n");
+        assert_eq!(output, "---\n---\n4\n");
+        let Tests::Cases(cases) = tests else {
+            unreachable!()
+        };
+        assert_eq!(cases[0].1, "---\n");
+    }
+
+    #[test]
+    fn test_checker_ignores_testcase_lines_from_the_run() {
+        let custom = CustomChecker {
+            file: "checker.py",
+            contents: b"import sys".to_vec(),
+        };
+        let run = Execution {
+            status: "OK".to_string(),
+            exit_code: Some(0),
+            time_ms: 5,
+            stdout: "3\n".to_string(),
+            stderr: "TESTCASE 1: [[1, 2]] 3\nTESTCASE 1: [[0]] 3\n".to_string(),
+        };
+        let files = grader_checker_files(&custom, "1\n1 2 3\n1 2\n", "3\n", &run);
+        assert_eq!(files[0], ("input.txt", b"1\n1 2 3\n1 2\n".as_slice()));
+        assert_eq!(files[1], ("output.txt", b"3\n".as_slice()));
+        assert_eq!(files[2], ("answer.txt", b"3\n".as_slice()));
+    }
+
+    #[test]
+    fn test_generated_cache() {
+        let mut cache = GeneratedCache::default();
+        let tests = |n: i64| {
+            Arc::new(Tests::Grader {
+                input: n.to_string(),
+                output: String::new(),
+            })
+        };
+        assert!(cache.get("a", 1).is_none());
+        cache.insert("a", 1, tests(1));
+        assert_eq!(cache.get("a", 1), Some(tests(1)));
+        assert!(cache.get("a", 2).is_none());
+        assert!(cache.get("b", 1).is_none());
+
+        // The least recently used entry makes room
+        for seed in 2..=GeneratedCache::CAPACITY as i64 {
+            cache.insert("a", seed, tests(seed));
+        }
+        cache.get("a", 1);
+        cache.insert("a", 100, tests(100));
+        assert_eq!(cache.entries.len(), GeneratedCache::CAPACITY);
+        assert!(cache.get("a", 1).is_some());
+        assert!(cache.get("a", 2).is_none());
+    }
+}
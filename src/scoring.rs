@@ -0,0 +1,252 @@
+//! Leaderboard scoring systems, selectable per contest
+
+use std::cmp::Ordering;
+
+/// Maximum points for a single problem
+pub const MAX_POINTS: i32 = 10000;
+
+/// Largest per-contest point multiplier a problem can have
+pub const MAX_WEIGHT: f64 = 100.0;
+
+/// One participant's best accepted solution to one problem
+#[derive(Debug, Clone)]
+pub struct ProblemResult {
+    pub code_length: i32,
+    pub best_length: i32, // Shortest among ranked participants
+    pub rank: usize,      // 1-based, ties share a rank
+    pub solvers: usize,   // Ranked participants who solved the problem
+    pub elapsed: i64,     // Seconds from contest start to the submission
+    pub duration: i64,    // Contest duration in seconds
+}
+
+/// A participant's totals across all problems
+#[derive(Debug, Clone, Copy)]
+pub struct Totals {
+    pub score: i32,
+    pub solved: i64,
+    pub bytes: i64,
+}
+
+pub trait ScoringSystem: Send + Sync {
+    /// Identifier stored in `contests.scoring`
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+
+    /// Points for one solved problem
+    fn problem_points(&self, result: &ProblemResult) -> i32;
+
+    /// Ordering of participants, best first
+    fn compare(&self, a: &Totals, b: &Totals) -> Ordering {
+        b.score
+            .cmp(&a.score)
+            .then(b.solved.cmp(&a.solved))
+            .then(a.bytes.cmp(&b.bytes))
+    }
+}
+
+/// 10000 points for the best length, `10000 * best / length` otherwise
+pub struct Ratio;
+
+impl ScoringSystem for Ratio {
+    fn id(&self) -> &'static str {
+        "ratio"
+    }
+
+    fn name(&self) -> &'static str {
+        "Length ratio"
+    }
+
+    fn description(&self) -> &'static str {
+        "Each problem is worth 10000 × (shortest length / your length). Ranked by total points, then problems solved, then total bytes."
+    }
+
+    fn problem_points(&self, result: &ProblemResult) -> i32 {
+        ratio_points(result)
+    }
+}
+
+/// Most problems solved, then fewest total bytes
+pub struct TotalBytes;
+
+impl ScoringSystem for TotalBytes {
+    fn id(&self) -> &'static str {
+        "bytes"
+    }
+
+    fn name(&self) -> &'static str {
+        "Total bytes"
+    }
+
+    fn description(&self) -> &'static str {
+        "Ranked by most problems solved, then lowest total bytes across solved problems."
+    }
+
+    fn problem_points(&self, result: &ProblemResult) -> i32 {
+        ratio_points(result)
+    }
+
+    fn compare(&self, a: &Totals, b: &Totals) -> Ordering {
+        b.solved
+            .cmp(&a.solved)
+            .then(a.bytes.cmp(&b.bytes))
+            .then(b.score.cmp(&a.score))
+    }
+}
+
+/// Anarchy Golf style: points by rank among the solvers of each problem
+pub struct RankPoints;
+
+impl ScoringSystem for RankPoints {
+    fn id(&self) -> &'static str {
+        "anarchy"
+    }
+
+    fn name(&self) -> &'static str {
+        "Rank points"
+    }
+
+    fn description(&self) -> &'static str {
+        "Anarchy Golf style: on each problem with N solvers, rank r earns 10000 × (N - r + 1) / N points (ties share a rank). Ranked by total points."
+    }
+
+    fn problem_points(&self, result: &ProblemResult) -> i32 {
+        let solvers = result.solvers.max(result.rank).max(1) as i64;
+        let beaten_or_tied = solvers - result.rank as i64 + 1;
+        (MAX_POINTS as i64 * beaten_or_tied / solvers).max(1) as i32
+    }
+}
+
+/// Length ratio, decaying linearly to half value at the end of the contest
+pub struct TimeDecay;
+
+impl ScoringSystem for TimeDecay {
+    fn id(&self) -> &'static str {
+        "time_decay"
+    }
+
+    fn name(&self) -> &'static str {
+        "Time-decayed ratio"
+    }
+
+    fn description(&self) -> &'static str {
+        "Length ratio points, multiplied by a factor falling from 1 at the start to 0.5 at the end of the contest (based on when your best solution was submitted). Ranked by total points."
+    }
+
+    fn problem_points(&self, result: &ProblemResult) -> i32 {
+        let progress = if result.duration > 0 {
+            (result.elapsed as f64 / result.duration as f64).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let factor = 1.0 - 0.5 * progress;
+        ((ratio_points(result) as f64 * factor).round() as i32).max(1)
+    }
+}
+
+fn ratio_points(result: &ProblemResult) -> i32 {
+    if result.code_length <= 0 {
+        return MAX_POINTS;
+    }
+    (MAX_POINTS as i64 * result.best_length as i64 / result.code_length as i64)
+        .clamp(1, MAX_POINTS as i64) as i32
+}
+
+static SYSTEMS: &[&dyn ScoringSystem] = &[&Ratio, &TotalBytes, &RankPoints, &TimeDecay];
+
+/// Get a scoring system by ID, falling back to the length ratio
+pub fn get(id: &str) -> &'static dyn ScoringSystem {
+    SYSTEMS
+        .iter()
+        .copied()
+        .find(|s| s.id() == id)
+        .unwrap_or(&Ratio)
+}
+
+/// All available scoring systems
+pub fn all() -> &'static [&'static dyn ScoringSystem] {
+    SYSTEMS
+}
+
+/// A problem's points scaled by its weight in the contest
+pub fn weighted(points: i32, weight: f64) -> i32 {
+    (points as f64 * weight).round() as i32
+}
+
+/// 1-based rank of `length` among `lengths` (ties share the better rank)
+pub fn rank_of(length: i32, lengths: &[i32]) -> usize {
+    1 + lengths.iter().filter(|&&l| l < length).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(code_length: i32, best_length: i32, rank: usize, solvers: usize) -> ProblemResult {
+        ProblemResult {
+            code_length,
+            best_length,
+            rank,
+            solvers,
+            elapsed: 0,
+            duration: 3600,
+        }
+    }
+
+    #[test]
+    fn test_ratio_points() {
+        assert_eq!(Ratio.problem_points(&result(40, 40, 1, 3)), 10000);
+        assert_eq!(Ratio.problem_points(&result(80, 40, 2, 3)), 5000);
+    }
+
+    #[test]
+    fn test_rank_points() {
+        assert_eq!(RankPoints.problem_points(&result(40, 40, 1, 4)), 10000);
+        assert_eq!(RankPoints.problem_points(&result(50, 40, 2, 4)), 7500);
+        assert_eq!(RankPoints.problem_points(&result(90, 40, 4, 4)), 2500);
+        assert_eq!(rank_of(50, &[40, 50, 50, 60]), 2);
+        assert_eq!(rank_of(60, &[40, 50, 50, 60]), 4);
+    }
+
+    #[test]
+    fn test_time_decay() {
+        let mut r = result(40, 40, 1, 1);
+        assert_eq!(TimeDecay.problem_points(&r), 10000);
+        r.elapsed = 1800;
+        assert_eq!(TimeDecay.problem_points(&r), 7500);
+        r.elapsed = 7200;
+        assert_eq!(TimeDecay.problem_points(&r), 5000);
+    }
+
+    #[test]
+    fn test_weighted() {
+        assert_eq!(weighted(7500, 1.0), 7500);
+        assert_eq!(weighted(7500, 2.0), 15000);
+        assert_eq!(weighted(3333, 0.5), 1667);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let many_bytes = Totals {
+            score: 20000,
+            solved: 2,
+            bytes: 500,
+        };
+        let few_bytes = Totals {
+            score: 15000,
+            solved: 2,
+            bytes: 100,
+        };
+        assert_eq!(Ratio.compare(&many_bytes, &few_bytes), Ordering::Less);
+        assert_eq!(
+            TotalBytes.compare(&many_bytes, &few_bytes),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_unknown_system_falls_back_to_ratio() {
+        assert_eq!(get("nonsense").id(), "ratio");
+        assert_eq!(get("anarchy").id(), "anarchy");
+    }
+}
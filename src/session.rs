@@ -1,5 +1,12 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
 use tower_sessions::{Expiry, MemoryStore, Session, SessionManagerLayer};
 
+use crate::state::AppState;
+
 pub fn session_layer() -> SessionManagerLayer<MemoryStore> {
     let session_store = MemoryStore::default();
     SessionManagerLayer::new(session_store)
@@ -32,9 +39,41 @@ pub async fn set_user(
     Ok(())
 }
 
-#[allow(dead_code)]
 pub async fn clear_user(session: &Session) -> Result<(), tower_sessions::session::Error> {
     session.remove::<String>("username").await?;
     session.remove::<bool>("is_admin").await?;
+    session.remove::<u64>(CHECKED_GENERATION).await?;
     Ok(())
 }
+
+/// AppState's user generation when the session's user was last known to exist
+const CHECKED_GENERATION: &str = "checked_user_generation";
+
+/// Log out sessions whose user no longer exists, such as after an admin renamed or merged
+/// them, so nothing is saved under a name that is gone. Users only go away then, so a
+/// session checks once after each rename or merge rather than on every request.
+pub async fn drop_missing_user(
+    State(state): State<AppState>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    let generation = state.user_generation();
+    if let Some(user) = get_user(&session).await
+        && !user.is_admin
+        && session.get::<u64>(CHECKED_GENERATION).await.ok().flatten() != Some(generation)
+    {
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1)")
+                .bind(&user.username)
+                .fetch_one(&state.db)
+                .await
+                .unwrap_or(true);
+        if exists {
+            let _ = session.insert(CHECKED_GENERATION, generation).await;
+        } else {
+            let _ = clear_user(&session).await;
+        }
+    }
+    next.run(request).await
+}
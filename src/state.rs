@@ -1,15 +1,38 @@
-use crate::models::Contest;
+use crate::{
+    access,
+    cache::{CachedStandings, StandingsCache},
+    counting,
+    events::{ContestEvent, EventHub},
+    models::{Contest, ContestProblem, Team},
+    registry::ProblemRegistry,
+    versions,
+};
 use sqlx::PgPool;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub admin_token: String,
+    pub events: EventHub,
+    pub standings_cache: Arc<StandingsCache>,
+    pub problems: Arc<ProblemRegistry>,
+    user_generation: Arc<AtomicU64>, // Bumped whenever users are renamed or merged away
 }
 
 impl AppState {
     pub fn new(db: PgPool, admin_token: String) -> Self {
-        Self { db, admin_token }
+        Self {
+            db,
+            admin_token,
+            events: EventHub::new(),
+            standings_cache: Arc::new(StandingsCache::default()),
+            problems: Arc::new(ProblemRegistry::load()),
+            user_generation: Arc::new(AtomicU64::new(0)),
+        }
     }
 
     // Contest helper methods
@@ -20,6 +43,76 @@ impl AppState {
             .await
     }
 
+    /// Get the team a user belongs to in a contest
+    pub async fn get_user_team(
+        &self,
+        contest_id: i32,
+        username: &str,
+    ) -> Result<Option<Team>, sqlx::Error> {
+        sqlx::query_as::<_, Team>(
+            "SELECT t.* FROM teams t
+             JOIN team_members m ON m.team_id = t.id
+             WHERE m.contest_id = $1 AND m.username = $2",
+        )
+        .bind(contest_id)
+        .bind(username)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    /// Get a problem's settings in a contest, if the contest has it
+    pub async fn get_contest_problem(
+        &self,
+        contest_id: i32,
+        problem_id: &str,
+    ) -> Result<Option<ContestProblem>, sqlx::Error> {
+        sqlx::query_as::<_, ContestProblem>(
+            "SELECT * FROM contest_problems WHERE contest_id = $1 AND problem_id = $2",
+        )
+        .bind(contest_id)
+        .bind(problem_id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    pub async fn is_participant(
+        &self,
+        contest_id: i32,
+        username: &str,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM contest_participants WHERE contest_id = $1 AND username = $2)",
+        )
+        .bind(contest_id)
+        .bind(username)
+        .fetch_one(&self.db)
+        .await
+    }
+
+    /// Public contests are open to everyone, restricted ones only to registered participants
+    pub async fn can_access_contest(&self, contest: &Contest, username: &str) -> bool {
+        !contest.is_restricted()
+            || self
+                .is_participant(contest.id, username)
+                .await
+                .unwrap_or(false)
+    }
+
+    /// Check whether a user is on a contest's allow-list. Only usernames count: emails are
+    /// typed in at login and never verified, so anyone could claim an allowed address.
+    pub async fn is_allowlisted(
+        &self,
+        contest_id: i32,
+        username: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let entries: Vec<String> =
+            sqlx::query_scalar("SELECT entry FROM contest_allowlist WHERE contest_id = $1")
+                .bind(contest_id)
+                .fetch_all(&self.db)
+                .await?;
+        Ok(access::is_allowlisted(&entries, username))
+    }
+
     #[allow(dead_code)]
     pub fn is_contest_active(&self, contest: &Contest) -> bool {
         contest.status == "active" && !self.is_contest_ended(contest)
@@ -50,6 +143,8 @@ impl AppState {
             .bind(contest_id)
             .execute(&self.db)
             .await?;
+        versions::pin_contest(&self.db, &self.problems, contest_id).await?;
+        self.contest_changed(contest_id).await;
         Ok(())
     }
 
@@ -58,25 +153,167 @@ impl AppState {
             .bind(contest_id)
             .execute(&self.db)
             .await?;
+        self.contest_changed(contest_id).await;
+        Ok(())
+    }
+
+    /// Give an active contest extra time
+    pub async fn extend_contest(&self, contest_id: i32, minutes: i32) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE contests SET duration = duration + $2 WHERE id = $1 AND status = 'active'",
+        )
+        .bind(contest_id)
+        .bind(minutes * 60)
+        .execute(&self.db)
+        .await?;
+        self.contest_changed(contest_id).await;
         Ok(())
     }
 
+    /// Cached standings for a contest, computing them on a miss
+    pub async fn standings(&self, contest_id: i32) -> Option<Arc<CachedStandings>> {
+        if let Some(cached) = self.standings_cache.get(contest_id) {
+            return Some(cached);
+        }
+
+        let generation = self.standings_cache.generation();
+        let contest = self.get_contest(contest_id).await.ok().flatten()?;
+        let standings = CachedStandings::load(&self.db, &self.problems, contest)
+            .await
+            .ok()?;
+        Some(
+            self.standings_cache
+                .insert(contest_id, standings, generation),
+        )
+    }
+
+    /// Pick up problem packages changed on disk. Cached standings hold problem titles.
+    pub async fn poll_problems(&self) {
+        let rules_before: Vec<(String, Option<String>)> = self
+            .problems
+            .list()
+            .iter()
+            .map(|p| (p.problem.id.clone(), p.metadata.byte_count.clone()))
+            .collect();
+        let changed = self.problems.poll();
+        if changed.is_empty() {
+            return;
+        }
+        self.standings_cache.invalidate_all();
+
+        for problem_id in changed {
+            let before = rules_before
+                .iter()
+                .find(|(id, _)| *id == problem_id)
+                .and_then(|(_, rule)| rule.clone());
+            let after = self
+                .problems
+                .get(&problem_id)
+                .and_then(|p| p.metadata.byte_count.clone());
+            if before != after {
+                self.recount_problem(&problem_id).await;
+            }
+        }
+    }
+
+    /// Call after a problem's byte-counting rule changes, to re-measure its submissions.
+    /// Only contests that have not started are re-measured; running and finished ones keep
+    /// their lengths until an admin re-measures them from the contest page.
+    pub async fn recount_problem(&self, problem_id: &str) {
+        let contest_ids: Vec<i32> = sqlx::query_scalar(
+            "SELECT DISTINCT s.contest_id FROM submissions s
+             JOIN contests c ON c.id = s.contest_id
+             WHERE s.problem_id = $1 AND c.status = 'pending'",
+        )
+        .bind(problem_id)
+        .fetch_all(&self.db)
+        .await
+        .unwrap_or_default();
+        for contest_id in contest_ids {
+            let _ = counting::recount(&self.db, &self.problems, contest_id, Some(problem_id)).await;
+            self.standings_changed(contest_id).await;
+        }
+    }
+
+    /// Call after users are renamed or merged, so sessions check their user still exists
+    pub fn users_changed(&self) {
+        self.user_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn user_generation(&self) -> u64 {
+        self.user_generation.load(Ordering::Relaxed)
+    }
+
+    /// Call after writing a problem's files, so the change is served immediately
+    pub fn problem_changed(&self, problem_id: &str) -> Result<(), String> {
+        let result = self.problems.reload(problem_id);
+        self.standings_cache.invalidate_all();
+        result
+    }
+
+    /// Call after a contest's status, timing or settings change
+    pub async fn contest_changed(&self, contest_id: i32) {
+        self.standings_cache.invalidate(contest_id);
+        self.publish_status(contest_id).await;
+    }
+
+    /// Call after anything that may change a contest's standings
+    pub async fn standings_changed(&self, contest_id: i32) {
+        self.standings_cache.invalidate(contest_id);
+        self.publish_standings(contest_id).await;
+    }
+
+    /// Tell live clients about a contest's current status and end time
+    pub async fn publish_status(&self, contest_id: i32) {
+        if !self.events.has_subscribers() {
+            return;
+        }
+        if let Ok(Some(contest)) = self.get_contest(contest_id).await {
+            self.events.publish(ContestEvent::Status {
+                contest_id,
+                end_time: contest
+                    .start_time
+                    .map(|start| start + contest.duration as i64),
+                status: contest.status,
+            });
+        }
+    }
+
+    /// Push fresh standings to live leaderboards after anything that may change them
+    pub async fn publish_standings(&self, contest_id: i32) {
+        if !self.events.has_subscribers() {
+            return;
+        }
+        let Some(standings) = self.standings(contest_id).await else {
+            return;
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        self.events
+            .publish(ContestEvent::standings(&standings, now));
+    }
+
     /// Check and automatically end expired contests
     pub async fn auto_end_expired_contests(&self) -> Result<(), sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
 
         // Update contests that are active but have exceeded their duration
-        sqlx::query(
+        let ended: Vec<i32> = sqlx::query_scalar(
             "UPDATE contests
              SET status = 'ended'
              WHERE status = 'active'
              AND start_time IS NOT NULL
-             AND $1 >= start_time + duration",
+             AND $1 >= start_time + duration
+             RETURNING id",
         )
         .bind(now)
-        .execute(&self.db)
+        .fetch_all(&self.db)
         .await?;
 
+        for contest_id in ended {
+            self.contest_changed(contest_id).await;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,237 @@
+//! Per-problem statistics computed from a contest's submissions
+//! Solve counts, verdict breakdown, a histogram of each solver's best length and the
+//! timeline of the record (shortest accepted) length over the contest
+
+use std::collections::HashMap;
+
+/// Number of histogram buckets to aim for
+const TARGET_BUCKETS: i32 = 10;
+
+/// One submission to a problem
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Attempt {
+    pub username: String,
+    pub verdict: String,
+    pub code_length: i32,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    pub start: i32,
+    pub end: i32, // Inclusive
+    pub count: usize,
+}
+
+/// The record length improving
+#[derive(Debug, Clone)]
+pub struct RecordPoint {
+    pub username: String,
+    pub code_length: i32,
+    pub elapsed: i64, // Seconds since contest start
+}
+
+impl RecordPoint {
+    /// Contest time as `m:ss`
+    pub fn contest_time(&self) -> String {
+        format!("{}:{:02}", self.elapsed / 60, self.elapsed % 60)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProblemStats {
+    pub submissions: usize,
+    pub submitters: usize,
+    pub solvers: usize,
+    pub verdicts: Vec<(String, usize)>, // Most common first
+    pub histogram: Vec<Bucket>,         // Of each solver's best length
+    pub first_solve: Option<RecordPoint>,
+    pub records: Vec<RecordPoint>,
+}
+
+impl ProblemStats {
+    /// Percentage of submitters who solved the problem
+    pub fn solve_rate(&self) -> i32 {
+        if self.submitters == 0 {
+            return 0;
+        }
+        (self.solvers * 100 / self.submitters) as i32
+    }
+
+    pub fn largest_bucket(&self) -> usize {
+        self.histogram.iter().map(|b| b.count).max().unwrap_or(0)
+    }
+
+    /// SVG path for a step chart of the record length, scaled to `width` x `height`
+    /// Time runs left to right over `duration` seconds, shorter records are drawn higher
+    pub fn record_path(&self, duration: i64, width: i32, height: i32) -> String {
+        let (Some(first), Some(last)) = (self.records.first(), self.records.last()) else {
+            return String::new();
+        };
+        let (longest, shortest) = (first.code_length, last.code_length);
+        let x = |elapsed: i64| elapsed.clamp(0, duration.max(1)) * width as i64 / duration.max(1);
+        let y = |length: i32| {
+            if longest == shortest {
+                return height / 2;
+            }
+            (length - shortest) * height / (longest - shortest)
+        };
+
+        let mut path = format!("M {} {}", x(first.elapsed), y(first.code_length));
+        for point in &self.records[1..] {
+            path.push_str(&format!(
+                " H {} V {}",
+                x(point.elapsed),
+                y(point.code_length)
+            ));
+        }
+        path.push_str(&format!(" H {width}"));
+        path
+    }
+}
+
+/// Compute statistics from a problem's submissions, given in submission order
+pub fn compute(attempts: &[Attempt], contest_start: i64) -> ProblemStats {
+    let mut verdicts: HashMap<&str, usize> = HashMap::new();
+    let mut submitters: Vec<&str> = Vec::new();
+    let mut best: HashMap<&str, i32> = HashMap::new();
+    let mut records: Vec<RecordPoint> = Vec::new();
+
+    for attempt in attempts {
+        *verdicts.entry(&attempt.verdict).or_default() += 1;
+        if !submitters.contains(&attempt.username.as_str()) {
+            submitters.push(&attempt.username);
+        }
+        if attempt.verdict != "AC" {
+            continue;
+        }
+
+        let own = best.entry(&attempt.username).or_insert(attempt.code_length);
+        *own = (*own).min(attempt.code_length);
+        if records
+            .last()
+            .is_none_or(|r| attempt.code_length < r.code_length)
+        {
+            records.push(RecordPoint {
+                username: attempt.username.clone(),
+                code_length: attempt.code_length,
+                elapsed: attempt.created_at - contest_start,
+            });
+        }
+    }
+
+    let mut verdicts: Vec<(String, usize)> = verdicts
+        .into_iter()
+        .map(|(v, n)| (v.to_string(), n))
+        .collect();
+    verdicts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    ProblemStats {
+        submissions: attempts.len(),
+        submitters: submitters.len(),
+        solvers: best.len(),
+        verdicts,
+        histogram: histogram(&best.into_values().collect::<Vec<_>>()),
+        first_solve: records.first().cloned(),
+        records,
+    }
+}
+
+/// Equal-width buckets covering every length, about `TARGET_BUCKETS` of them
+fn histogram(lengths: &[i32]) -> Vec<Bucket> {
+    let (Some(&min), Some(&max)) = (lengths.iter().min(), lengths.iter().max()) else {
+        return Vec::new();
+    };
+    let width = ((max - min + 1) + TARGET_BUCKETS - 1) / TARGET_BUCKETS;
+
+    let mut buckets: Vec<Bucket> = (min..=max)
+        .step_by(width as usize)
+        .map(|start| Bucket {
+            start,
+            end: start + width - 1,
+            count: 0,
+        })
+        .collect();
+    for &length in lengths {
+        buckets[((length - min) / width) as usize].count += 1;
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(username: &str, verdict: &str, code_length: i32, created_at: i64) -> Attempt {
+        Attempt {
+            username: username.to_string(),
+            verdict: verdict.to_string(),
+            code_length,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_counts_and_records() {
+        let attempts = vec![
+            attempt("alice", "WA", 30, 1010),
+            attempt("alice", "AC", 50, 1020),
+            attempt("bob", "AC", 60, 1030),
+            attempt("bob", "AC", 40, 1040),
+            attempt("carol", "TLE", 20, 1050),
+            attempt("alice", "AC", 40, 1060), // Ties the record, not a new one
+        ];
+        let stats = compute(&attempts, 1000);
+
+        assert_eq!(
+            (stats.submissions, stats.submitters, stats.solvers),
+            (6, 3, 2)
+        );
+        assert_eq!(stats.solve_rate(), 66);
+        assert_eq!(
+            stats.verdicts,
+            vec![
+                ("AC".to_string(), 4),
+                ("TLE".to_string(), 1),
+                ("WA".to_string(), 1)
+            ]
+        );
+
+        let first = stats.first_solve.unwrap();
+        assert_eq!((first.username.as_str(), first.elapsed), ("alice", 20));
+        assert_eq!(first.contest_time(), "0:20");
+        let records: Vec<(&str, i32)> = stats
+            .records
+            .iter()
+            .map(|r| (r.username.as_str(), r.code_length))
+            .collect();
+        assert_eq!(records, vec![("alice", 50), ("bob", 40)]);
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let buckets = histogram(&[10, 12, 29, 30]);
+        assert_eq!(buckets.len(), 7);
+        assert_eq!(
+            (buckets[0].start, buckets[0].end, buckets[0].count),
+            (10, 12, 2)
+        );
+        assert_eq!((buckets[6].start, buckets[6].count), (28, 2));
+        assert_eq!(buckets.iter().map(|b| b.count).sum::<usize>(), 4);
+
+        let single = histogram(&[42, 42]);
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].count, 2);
+        assert!(histogram(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_record_path() {
+        let stats = compute(
+            &[attempt("alice", "AC", 50, 0), attempt("bob", "AC", 40, 50)],
+            0,
+        );
+        assert_eq!(stats.record_path(100, 200, 80), "M 0 80 H 100 V 0 H 200");
+        assert_eq!(ProblemStats::default().record_path(100, 200, 80), "");
+    }
+}
@@ -0,0 +1,121 @@
+//! Team rules for team contests: names, invite codes and size limits
+
+use sqlx::PgPool;
+
+use crate::usernames;
+
+/// Characters of invite codes; no 0/O or 1/I so codes can be read out loud
+const INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_LENGTH: usize = 8;
+
+pub fn generate_invite_code() -> String {
+    use rand::Rng;
+    let mut rng = rand::rng();
+    (0..INVITE_LENGTH)
+        .map(|_| INVITE_ALPHABET[rng.random_range(0..INVITE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Invite codes as typed, matched case-insensitively
+pub fn normalize_invite_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Check a new team's name against the contest's other teams. Team names share the
+/// username rules since both show up on the leaderboard.
+pub fn check_name(name: &str, existing: &[String]) -> Result<(), String> {
+    if let Err(e) = usernames::validate(name) {
+        let message = e.to_string();
+        return Err(message
+            .replace("Username", "Team name")
+            .replace("username", "team name"));
+    }
+    let taken = existing.iter().find(|t| *t == name).map(String::as_str);
+    match usernames::find_confusable(name, existing.iter().map(String::as_str)).or(taken) {
+        Some(other) => Err(format!("Team name is taken or too similar to \"{other}\"")),
+        None => Ok(()),
+    }
+}
+
+/// Whether a team with `members` members can take one more
+pub fn has_room(members: i64, team_size: i32) -> bool {
+    members < team_size as i64
+}
+
+/// Add a user to a team if it still has room, returning whether they joined. The team row
+/// is locked so concurrent joins cannot both take the last place.
+pub async fn join(
+    db: &PgPool,
+    team_id: i32,
+    contest_id: i32,
+    username: &str,
+    team_size: i32,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query("SELECT id FROM teams WHERE id = $1 FOR UPDATE")
+        .bind(team_id)
+        .execute(&mut *tx)
+        .await?;
+    let members: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM team_members WHERE team_id = $1")
+        .bind(team_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !has_room(members, team_size) {
+        return Ok(false);
+    }
+
+    let joined = sqlx::query(
+        "INSERT INTO team_members (team_id, contest_id, username, joined_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(team_id)
+    .bind(contest_id)
+    .bind(username)
+    .bind(chrono::Utc::now().timestamp())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(joined > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_codes() {
+        let code = generate_invite_code();
+        assert_eq!(code.len(), INVITE_LENGTH);
+        assert!(code.bytes().all(|b| INVITE_ALPHABET.contains(&b)));
+        assert_eq!(normalize_invite_code(" abcd2345\n"), "ABCD2345");
+    }
+
+    #[test]
+    fn test_team_names() {
+        let existing = vec!["rockets".to_string(), "owls".to_string()];
+        assert!(check_name("badgers", &existing).is_ok());
+        assert!(check_name("rockets", &existing).is_err());
+        assert!(check_name("r0ckets", &existing).is_err());
+        assert_eq!(
+            check_name("admin", &existing),
+            Err("That team name is reserved".to_string())
+        );
+        assert!(
+            check_name("a", &existing)
+                .unwrap_err()
+                .starts_with("Team name")
+        );
+    }
+
+    #[test]
+    fn test_size_limit() {
+        assert!(has_room(0, 3));
+        assert!(has_room(2, 3));
+        assert!(!has_room(3, 3));
+        assert!(!has_room(1, 1));
+    }
+}
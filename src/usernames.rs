@@ -0,0 +1,204 @@
+//! Username validation rules
+//! Usernames appear on the leaderboard and in admin filters, so they are restricted
+//! to a small ASCII alphabet and checked against reserved and look-alike names
+
+const MIN_LENGTH: usize = 2;
+const MAX_LENGTH: usize = 24;
+
+/// Names that nobody can log in as (compared by skeleton, so "Adm1n" is blocked too)
+const RESERVED_NAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "system",
+    "moderator",
+    "mod",
+    "staff",
+    "judge",
+    "cucats",
+    "anonymous",
+    "null",
+    "undefined",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsernameError {
+    Empty,
+    TooShort,
+    TooLong,
+    InvalidCharacter(char),
+    Reserved,
+    Confusable(String), // Existing username that looks the same
+}
+
+impl std::fmt::Display for UsernameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsernameError::Empty => write!(f, "Username is required"),
+            UsernameError::TooShort => {
+                write!(f, "Username must be at least {MIN_LENGTH} characters")
+            }
+            UsernameError::TooLong => {
+                write!(f, "Username must be at most {MAX_LENGTH} characters")
+            }
+            UsernameError::InvalidCharacter(c) => write!(
+                f,
+                "Username contains an invalid character {c:?} (use letters, digits, '_', '-' and '.')"
+            ),
+            UsernameError::Reserved => write!(f, "That username is reserved"),
+            UsernameError::Confusable(existing) => {
+                write!(f, "Username is too similar to existing user \"{existing}\"")
+            }
+        }
+    }
+}
+
+/// Check length, allowed characters and reserved names
+pub fn validate(username: &str) -> Result<(), UsernameError> {
+    if username.is_empty() {
+        return Err(UsernameError::Empty);
+    }
+
+    if let Some(c) = username
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+    {
+        return Err(UsernameError::InvalidCharacter(c));
+    }
+
+    // Only ASCII is allowed past this point, so bytes == characters
+    if username.len() < MIN_LENGTH {
+        return Err(UsernameError::TooShort);
+    }
+    if username.len() > MAX_LENGTH {
+        return Err(UsernameError::TooLong);
+    }
+
+    if is_reserved(username) {
+        return Err(UsernameError::Reserved);
+    }
+
+    Ok(())
+}
+
+/// Reserved names are also matched through multi-character look-alikes ("adrnin"), which
+/// are too common in ordinary names ("clara", "dara") to compare users by
+pub fn is_reserved(username: &str) -> bool {
+    let skel = fold_letter_pairs(&skeleton(username));
+    RESERVED_NAMES
+        .iter()
+        .any(|name| fold_letter_pairs(&skeleton(name)) == skel)
+}
+
+fn fold_letter_pairs(skeleton: &str) -> String {
+    skeleton
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .replace("cl", "d")
+}
+
+/// Reduce a name to a canonical form where visually confusable names collide
+/// Case is folded, separators are dropped and single-character homoglyphs
+/// (including Cyrillic/Greek look-alikes) are mapped onto one representative
+pub fn skeleton(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let mapped = match c {
+            '_' | '-' | '.' | ' ' => continue,
+            '0' | 'о' | 'ο' | 'օ' => 'o',
+            '1' | 'i' | 'l' | '|' | 'ı' | 'і' | 'ӏ' | 'ι' => 'l',
+            '3' | 'з' | 'ʒ' => 'e',
+            '5' | 'ѕ' => 's',
+            '8' => 'b',
+            'а' | 'α' => 'a',
+            'в' | 'β' => 'b',
+            'е' | 'ε' => 'e',
+            'к' | 'κ' => 'k',
+            'м' => 'm',
+            'н' | 'η' => 'h',
+            'р' | 'ρ' => 'p',
+            'с' | 'ϲ' => 'c',
+            'т' | 'τ' => 't',
+            'у' | 'γ' => 'y',
+            'х' | 'χ' => 'x',
+            'ԁ' => 'd',
+            'ɡ' => 'g',
+            'ν' => 'v',
+            'ω' | 'ш' => 'w',
+            c => c,
+        };
+        out.push(mapped);
+    }
+    out
+}
+
+/// Find an existing username that is confusable with (but not identical to) `username`
+pub fn find_confusable<'a>(
+    username: &str,
+    existing: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let skel = skeleton(username);
+    existing
+        .into_iter()
+        .find(|other| *other != username && skeleton(other) == skel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_usernames() {
+        assert!(validate("alice").is_ok());
+        assert!(validate("bob_42").is_ok());
+        assert!(validate("j.doe-x").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_usernames() {
+        assert_eq!(validate(""), Err(UsernameError::Empty));
+        assert_eq!(validate("a"), Err(UsernameError::TooShort));
+        assert_eq!(validate(&"a".repeat(25)), Err(UsernameError::TooLong));
+        assert_eq!(
+            validate("<b>hi</b>"),
+            Err(UsernameError::InvalidCharacter('<'))
+        );
+        assert_eq!(
+            validate("аlice"), // Cyrillic a
+            Err(UsernameError::InvalidCharacter('а'))
+        );
+    }
+
+    #[test]
+    fn test_reserved_usernames() {
+        assert_eq!(validate("admin"), Err(UsernameError::Reserved));
+        assert_eq!(validate("ADMIN"), Err(UsernameError::Reserved));
+        assert_eq!(validate("Adm1n"), Err(UsernameError::Reserved));
+        assert_eq!(validate("r00t"), Err(UsernameError::Reserved));
+        assert_eq!(validate("adrnin"), Err(UsernameError::Reserved));
+        assert!(validate("dara").is_ok());
+        assert!(validate("mode").is_ok());
+    }
+
+    #[test]
+    fn test_confusable_detection() {
+        let existing = ["alice", "bob", "modern"];
+        assert_eq!(find_confusable("Alice", existing), Some("alice"));
+        assert_eq!(find_confusable("a1ice", existing), Some("alice"));
+        assert_eq!(find_confusable("b0b", existing), Some("bob"));
+        assert_eq!(find_confusable("m0dern", existing), Some("modern"));
+        assert_eq!(find_confusable("alice", existing), None);
+        assert_eq!(find_confusable("carol", existing), None);
+    }
+
+    #[test]
+    fn test_letter_pairs_do_not_collide() {
+        // Names differing by "rn"/"m", "vv"/"w" or "cl"/"d" are ordinary, distinct names
+        assert_eq!(find_confusable("clara", ["dara"]), None);
+        assert_eq!(find_confusable("burn", ["bum"]), None);
+        assert_eq!(find_confusable("clive", ["dive"]), None);
+        assert_eq!(find_confusable("savvy", ["sawy"]), None);
+        assert!(validate("clara").is_ok());
+        assert!(validate("modern").is_ok());
+    }
+}
@@ -0,0 +1,367 @@
+//! Stored versions of problem packages
+//! A package is stored under its content hash the first time a submission is judged against
+//! it or a contest starts with it, numbered 1, 2... per problem, so verdicts can be traced
+//! back to the exact statement, tests and checker they were given with
+
+use sqlx::PgPool;
+
+use crate::{
+    checker::CHECKER_FILES,
+    problems::{self, GENERATOR_FILES, METADATA_FILE, Package},
+    registry::ProblemRegistry,
+};
+
+/// A stored version of a problem package, so old verdicts can be checked against it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ProblemVersion {
+    pub hash: String,
+    pub version: i32,
+    pub title: String,
+    pub statement: String,
+    pub test_input: String,
+    pub test_output: String,
+    pub grader: String,
+    pub metadata: Option<String>, // problem.json, if the version had one
+    pub checker: Option<Vec<u8>>, // Custom checker file, if the version had one
+    pub generator: Option<String>,
+    pub solution: Option<String>, // Reference solution, present with the generator
+    pub created_at: i64,
+}
+
+impl ProblemVersion {
+    /// The custom checker as text, unless it is a compiled program
+    pub fn checker_source(&self) -> Option<&str> {
+        std::str::from_utf8(self.checker.as_deref()?).ok()
+    }
+
+    /// Rebuild the package as it was, to judge against it again. The statement is stored
+    /// without its title line, so the files may not hash the same and the version's own
+    /// hash is kept.
+    pub fn package(&self, problem_id: &str) -> std::io::Result<Package> {
+        let mut files = vec![
+            (
+                "statement.md",
+                format!("# {}\n\n{}\n", self.title, self.statement).into_bytes(),
+            ),
+            ("input.txt", self.test_input.clone().into_bytes()),
+            ("output.txt", self.test_output.clone().into_bytes()),
+            ("grader.py", self.grader.clone().into_bytes()),
+        ];
+        if let Some(metadata) = &self.metadata {
+            files.push((METADATA_FILE, metadata.clone().into_bytes()));
+        }
+        if let Some(checker) = &self.checker {
+            // Only the contents are stored: a compiled checker is not text
+            let file = match self.checker_source() {
+                Some(_) => CHECKER_FILES[0],
+                None => CHECKER_FILES[1],
+            };
+            files.push((file, checker.clone()));
+        }
+        if let (Some(generator), Some(solution)) = (&self.generator, &self.solution) {
+            files.push((GENERATOR_FILES[0], generator.clone().into_bytes()));
+            files.push((GENERATOR_FILES[1], solution.clone().into_bytes()));
+        }
+
+        let mut package = problems::load_staged_package(problem_id, &files, false)?;
+        package.hash = self.hash.clone();
+        Ok(package)
+    }
+}
+
+/// Where a package falls among a problem's stored versions
+#[derive(Debug, PartialEq, Eq)]
+enum Slot {
+    Stored(i32), // Already stored with this version number
+    Next(i32),   // New, and would be stored as this version
+}
+
+fn slot(stored: &[(String, i32)], hash: &str) -> Slot {
+    match stored.iter().find(|(h, _)| h == hash) {
+        Some((_, version)) => Slot::Stored(*version),
+        None => Slot::Next(stored.iter().map(|(_, v)| v + 1).max().unwrap_or(1)),
+    }
+}
+
+/// Store the package as a version if it is new, returning its version number
+pub async fn record(db: &PgPool, package: &Package) -> Result<i32, sqlx::Error> {
+    // A concurrent insert of another new version can take the number we picked, so retry
+    for _ in 0..3 {
+        let stored: Vec<(String, i32)> =
+            sqlx::query_as("SELECT hash, version FROM problem_snapshots WHERE problem_id = $1")
+                .bind(&package.problem.id)
+                .fetch_all(db)
+                .await?;
+        let version = match slot(&stored, &package.hash) {
+            Slot::Stored(version) => return Ok(version),
+            Slot::Next(version) => version,
+        };
+
+        let problem = &package.problem;
+        sqlx::query(
+            "INSERT INTO problem_snapshots (problem_id, hash, version, title, statement, test_input, test_output, grader, metadata, checker, generator, solution, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(&problem.id)
+        .bind(&package.hash)
+        .bind(version)
+        .bind(&problem.title)
+        .bind(&problem.statement)
+        .bind(&problem.test_input)
+        .bind(&problem.test_output)
+        .bind(&package.grader)
+        .bind(&package.metadata_json)
+        .bind(package.checker.custom().map(|c| &c.contents))
+        .bind(package.generator.as_ref().map(|g| &g.generator))
+        .bind(package.generator.as_ref().map(|g| &g.solution))
+        .bind(chrono::Utc::now().timestamp())
+        .execute(db)
+        .await?;
+    }
+    Err(sqlx::Error::RowNotFound)
+}
+
+/// Version number of a stored package, without storing it. For pages that only look.
+pub async fn find(db: &PgPool, problem_id: &str, hash: &str) -> Option<i32> {
+    sqlx::query_scalar("SELECT version FROM problem_snapshots WHERE problem_id = $1 AND hash = $2")
+        .bind(problem_id)
+        .bind(hash)
+        .fetch_optional(db)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Record the versions of a contest's problems as it starts
+pub async fn pin_contest(
+    db: &PgPool,
+    problems: &ProblemRegistry,
+    contest_id: i32,
+) -> Result<(), sqlx::Error> {
+    let problem_ids: Vec<String> =
+        sqlx::query_scalar("SELECT problem_id FROM contest_problems WHERE contest_id = $1")
+            .bind(contest_id)
+            .fetch_all(db)
+            .await?;
+
+    for problem_id in problem_ids {
+        let Some(package) = problems.get(&problem_id) else {
+            continue;
+        };
+        record(db, &package).await?;
+        sqlx::query(
+            "UPDATE contest_problems SET start_hash = $3 WHERE contest_id = $1 AND problem_id = $2",
+        )
+        .bind(contest_id)
+        .bind(&problem_id)
+        .bind(&package.hash)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// A contest problem that changed after the contest started
+pub struct Drift {
+    pub problem_id: String,
+    pub title: String,
+    pub start_version: i32,
+    pub current_version: Option<i32>, // None if not stored as a version (yet)
+    pub loadable: bool,
+    pub judged: Vec<(i32, i64)>, // Submissions per version judged against
+    start_hash: String,
+    current_hash: Option<String>, // None if the problem can no longer be loaded
+}
+
+impl Drift {
+    /// Whether the package served now, or any version judged against, is not the one
+    /// the contest started with
+    fn is_drifted(&self) -> bool {
+        self.current_hash.as_ref() != Some(&self.start_hash)
+            || self.judged.iter().any(|(v, _)| *v != self.start_version)
+    }
+
+    /// Submissions judged against a version other than the one the contest started with
+    pub fn stale_submissions(&self) -> i64 {
+        self.judged
+            .iter()
+            .filter(|(v, _)| *v != self.start_version)
+            .map(|(_, n)| n)
+            .sum()
+    }
+}
+
+/// Problems of a started contest whose current version, or the version some
+/// submissions were judged against, differs from the one at the start
+pub async fn contest_drift(db: &PgPool, problems: &ProblemRegistry, contest_id: i32) -> Vec<Drift> {
+    let pinned: Vec<(String, String, String, i32)> = sqlx::query_as(
+        "SELECT cp.problem_id, ps.title, ps.hash, ps.version FROM contest_problems cp
+         JOIN problem_snapshots ps ON ps.problem_id = cp.problem_id AND ps.hash = cp.start_hash
+         WHERE cp.contest_id = $1
+         ORDER BY cp.problem_order",
+    )
+    .bind(contest_id)
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+
+    let mut drift = Vec::new();
+    for (problem_id, title, start_hash, start_version) in pinned {
+        let current_hash = problems.get(&problem_id).map(|p| p.hash.clone());
+        let current_version = match &current_hash {
+            Some(hash) => find(db, &problem_id, hash).await,
+            None => None,
+        };
+        let judged: Vec<(i32, i64)> = sqlx::query_as(
+            "SELECT ps.version, COUNT(*) FROM submissions s
+             JOIN problem_snapshots ps ON ps.problem_id = s.problem_id AND ps.hash = s.problem_hash
+             WHERE s.contest_id = $1 AND s.problem_id = $2
+             GROUP BY ps.version
+             ORDER BY ps.version",
+        )
+        .bind(contest_id)
+        .bind(&problem_id)
+        .fetch_all(db)
+        .await
+        .unwrap_or_default();
+
+        let problem = Drift {
+            problem_id,
+            title,
+            start_version,
+            current_version,
+            loadable: current_hash.is_some(),
+            judged,
+            start_hash,
+            current_hash,
+        };
+        if problem.is_drifted() {
+            drift.push(problem);
+        }
+    }
+    drift
+}
+
+/// All stored versions of a problem, newest first
+pub async fn list(db: &PgPool, problem_id: &str) -> Vec<(ProblemVersion, i64)> {
+    let versions = sqlx::query_as::<_, ProblemVersion>(
+        "SELECT * FROM problem_snapshots WHERE problem_id = $1 ORDER BY version DESC",
+    )
+    .bind(problem_id)
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+    let counts: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT problem_hash, COUNT(*) FROM submissions
+         WHERE problem_id = $1 AND problem_hash IS NOT NULL
+         GROUP BY problem_hash",
+    )
+    .bind(problem_id)
+    .fetch_all(db)
+    .await
+    .unwrap_or_default();
+
+    versions
+        .into_iter()
+        .map(|v| {
+            let submissions = counts
+                .iter()
+                .find(|(hash, _)| *hash == v.hash)
+                .map_or(0, |(_, n)| *n);
+            (v, submissions)
+        })
+        .collect()
+}
+
+pub async fn get(db: &PgPool, problem_id: &str, version: i32) -> Option<ProblemVersion> {
+    sqlx::query_as::<_, ProblemVersion>(
+        "SELECT * FROM problem_snapshots WHERE problem_id = $1 AND version = $2",
+    )
+    .bind(problem_id)
+    .bind(version)
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drift(current_hash: Option<&str>, judged: Vec<(i32, i64)>) -> Drift {
+        Drift {
+            problem_id: "0".to_string(),
+            title: "Zero".to_string(),
+            start_version: 1,
+            current_version: None,
+            loadable: current_hash.is_some(),
+            judged,
+            start_hash: "a".to_string(),
+            current_hash: current_hash.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_record_dedup() {
+        assert_eq!(slot(&[], "a"), Slot::Next(1));
+        let stored = vec![("a".to_string(), 1), ("b".to_string(), 2)];
+        assert_eq!(slot(&stored, "a"), Slot::Stored(1));
+        assert_eq!(slot(&stored, "b"), Slot::Stored(2));
+        assert_eq!(slot(&stored, "c"), Slot::Next(3));
+    }
+
+    #[test]
+    fn test_pinning_again_keeps_the_version() {
+        // A contest restarted with an unchanged package pins the version stored the first time
+        let pinned = vec![("a".to_string(), 1)];
+        assert_eq!(slot(&pinned, "a"), Slot::Stored(1));
+        assert!(!drift(Some("a"), vec![(1, 5)]).is_drifted());
+    }
+
+    #[test]
+    fn test_drift_detection() {
+        assert!(!drift(Some("a"), Vec::new()).is_drifted());
+        assert!(drift(Some("b"), Vec::new()).is_drifted());
+        assert!(drift(None, Vec::new()).is_drifted());
+
+        let stale = drift(Some("a"), vec![(1, 5), (2, 3)]);
+        assert!(stale.is_drifted());
+        assert_eq!(stale.stale_submissions(), 3);
+    }
+
+    #[test]
+    fn test_rebuilding_a_stored_version() {
+        let snapshot = ProblemVersion {
+            hash: "abc".to_string(),
+            version: 2,
+            title: "Sum".to_string(),
+            statement: "Add the numbers.".to_string(),
+            test_input: "1 2\n".to_string(),
+            test_output: "3\n".to_string(),
+            grader: String::new(),
+            metadata: Some("{\"kind\": \"program\"}\n".to_string()),
+            checker: Some(vec![0x7f, b'E', b'L', b'F', 0xff, 0x00]),
+            generator: None,
+            solution: None,
+            created_at: 1000,
+        };
+        let package = snapshot.package("sum").unwrap();
+        assert_eq!(package.hash, "abc");
+        assert_eq!(package.problem.title, "Sum");
+        assert_eq!(package.problem.statement, "Add the numbers.");
+        assert_eq!(package.problem.test_input, "1 2\n");
+        assert_eq!(package.kind(), problems::ProblemKind::Program);
+        let custom = package.checker.custom().unwrap();
+        assert_eq!(custom.file, CHECKER_FILES[1]);
+        assert_eq!(Some(&custom.contents), snapshot.checker.as_ref());
+
+        let python = ProblemVersion {
+            checker: Some(b"import sys\n".to_vec()),
+            ..snapshot
+        };
+        let package = python.package("sum").unwrap();
+        assert_eq!(package.checker.custom().unwrap().file, CHECKER_FILES[0]);
+    }
+}